@@ -1,18 +1,55 @@
 use std::io::Read;
 use std::path::PathBuf;
 
-use anyhow::Result;
+use anyhow::{bail, Result};
 use structopt::StructOpt;
 
 #[derive(StructOpt, Debug)]
 struct Dump {
-    /// File containing a BUFR message to be dumped
+    /// File containing BUFR messages to be dumped
     #[structopt(parse(from_os_str))]
     message: PathBuf,
 
-    /// Print raw data for section 4
+    /// Print raw data for section 4 and the decoded subsets
     #[structopt(long = "show-data")]
     show_data: bool,
+
+    /// Accept non-zero reserved bytes and padded sections
+    #[structopt(long = "lenient")]
+    lenient: bool,
+}
+
+/// Render an error over several lines, one for each known part of its location
+fn diagnostic(error: &bufr::Error) -> String {
+    let mut lines = vec![format!("error: {}", error.kind())];
+    if let Some(location) = error.location() {
+        if let Some(message) = location.message() {
+            let mut line = format!("  message: {}", message);
+            if let Some(offset) = location.message_offset() {
+                line.push_str(&format!(" (byte {} of the file)", offset));
+            }
+            lines.push(line);
+        }
+        if let Some(section) = location.section() {
+            lines.push(format!("  section: {}", section));
+        }
+        if let Some(offset) = location.offset() {
+            lines.push(format!("  byte:    {}", offset));
+        }
+        if let Some(bit) = location.bit_offset() {
+            lines.push(format!("  bit:     {} of the data", bit));
+        }
+        if let Some(subset) = location.subset() {
+            lines.push(format!("  subset:  {}", subset));
+        }
+        if !location.descriptor_path().is_empty() {
+            lines.push(format!(
+                "  path:    {}",
+                location.descriptor_path().join(" > ")
+            ));
+        }
+    }
+    lines.join("\n")
 }
 
 fn main() -> Result<()> {
@@ -23,12 +60,69 @@ fn main() -> Result<()> {
     let mut reader = std::io::BufReader::new(file);
     reader.read_to_end(&mut buffer)?;
 
-    let data = bufr::decode(&buffer[..])?;
+    let mut n_messages = 0;
+    let mut n_errors = 0;
+    let options = bufr::DecodeOptions::default().strict(!args.lenient);
+    let mut messages = bufr::messages_with(&buffer, &options);
+    while let Some(result) = messages.next() {
+        n_messages += 1;
+        if n_messages > 1 {
+            println!();
+        }
 
-    if args.show_data {
-        println!("{:?}", data);
-    } else {
-        println!("{}", data);
+        let data = match result {
+            Ok(data) => data,
+            Err(e) => {
+                n_errors += 1;
+                eprintln!("{}", diagnostic(&e));
+                continue;
+            }
+        };
+        for warning in data.warnings() {
+            eprintln!("warning: {}\n  message: {}", warning, messages.index());
+        }
+
+        if args.show_data {
+            println!("{:?}", data);
+            match data.subsets() {
+                Ok(subsets) => {
+                    for (j, subset) in subsets.iter().enumerate() {
+                        println!("Subset {}", j);
+                        for field in subset.fields() {
+                            match data.meaning(field) {
+                                Some(meaning) => println!(
+                                    "  {}: {} ({})",
+                                    field.descriptor(),
+                                    field.value(),
+                                    meaning
+                                ),
+                                None => println!("  {}: {}", field.descriptor(), field.value()),
+                            }
+                        }
+                    }
+                }
+                Err(e) => {
+                    n_errors += 1;
+                    eprintln!(
+                        "{}",
+                        diagnostic(&e.with_message(messages.index(), messages.offset()))
+                    );
+                }
+            }
+        } else {
+            println!("{}", data);
+        }
+    }
+
+    if n_messages == 0 {
+        bail!("No BUFR message found");
+    }
+    if n_errors > 0 {
+        bail!(
+            "{} of {} messages could not be decoded",
+            n_errors,
+            n_messages
+        );
     }
 
     Ok(())
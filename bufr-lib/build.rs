@@ -0,0 +1,357 @@
+//! Compiles the WMO Table B, Table D and code and flag tables shipped in
+//! tables/ into static arrays sorted by descriptor, so a malformed table
+//! fails the build rather than a run, and nothing is parsed at runtime.
+//!
+//! Each version of the master table has its own directory, like
+//! `tables/35/`, and is only embedded when its `wmo-*` feature is enabled.
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+/// Table B entry: name, unit, scale, reference value and data width
+type Element = (String, String, i32, i32, u16);
+
+/// Classes of the WMO Table B reserved for future use, as
+/// `RESERVED_CLASSES` in src/tables.rs
+const RESERVED_CLASSES: &[u8] = &[9, 16, 17, 18, 32, 34, 36, 37, 38, 39, 43, 44, 45, 46, 47];
+
+/// Table D entry: title and descriptors
+type Sequence = (Option<String>, Vec<(u8, u8, u8)>);
+
+/// Code or flag table: name of the element and its entries, from the
+/// first to the last code figure or bit number sharing a meaning
+type CodeTable = (String, Vec<(u64, u64, String)>);
+
+fn main() {
+    let manifest_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+    let dir = manifest_dir.join("tables");
+    let units = manifest_dir.join("src/units.rs");
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed={}", dir.display());
+    println!("cargo:rerun-if-changed={}", units.display());
+    let units = unit_variants(&units);
+
+    let mut code = String::new();
+    let mut versions = vec![];
+    for version in 0..=u8::MAX {
+        let feature = format!("CARGO_FEATURE_WMO_{}", version);
+        if env::var_os(feature).is_none() {
+            continue;
+        }
+        let version_dir = dir.join(version.to_string());
+        if !version_dir.is_dir() {
+            panic!("feature wmo-{} needs {}", version, version_dir.display());
+        }
+        embed_version(&mut code, version, &version_dir, &units);
+        versions.push(version);
+    }
+
+    code.push_str("static EMBEDDED: &[EmbeddedVersion] = &[\n");
+    for version in &versions {
+        writeln!(
+            code,
+            "    EmbeddedVersion {{ version: {0}, table_b: TABLE_B_{0}, table_d: TABLE_D_{0}, code_flag: CODE_FLAG_{0} }},",
+            version
+        )
+        .unwrap();
+    }
+    code.push_str("];\n\n");
+
+    code.push_str("/// Master tables and versions of the WMO tables embedded in the crate\n");
+    code.push_str("pub const EMBEDDED_VERSIONS: &[(u8, u8)] = &[");
+    for version in &versions {
+        write!(code, "(0, {}), ", version).unwrap();
+    }
+    code.push_str("];\n");
+
+    let out = PathBuf::from(env::var("OUT_DIR").unwrap()).join("embedded_tables.rs");
+    fs::write(out, code).unwrap();
+}
+
+/// Writes the statics holding the tables of one version, read from
+/// `version_dir`
+fn embed_version(code: &mut String, version: u8, version_dir: &Path, units: &[(String, String)]) {
+    // Later files replace the entries of earlier ones
+    let mut table_b = BTreeMap::new();
+    for filename in csv_files(version_dir, "BUFRCREX_TableB_en_") {
+        table_b.extend(parse_table_b(&filename));
+    }
+    // Older versions may lack recent classes, so they are only reported
+    for class in (0..48).filter(|x| !RESERVED_CLASSES.contains(x)) {
+        if !table_b.keys().any(|&(x, _)| x == class) {
+            println!(
+                "cargo:warning={}: no element of Table B class {:02}",
+                version_dir.display(),
+                class
+            );
+        }
+    }
+    let mut table_d = BTreeMap::new();
+    for filename in csv_files(version_dir, "BUFR_TableD_en_") {
+        table_d.extend(parse_table_d(&filename));
+    }
+    let mut code_flag = BTreeMap::new();
+    for filename in csv_files(version_dir, "BUFRCREX_CodeFlag_en_") {
+        code_flag.extend(parse_code_flag(&filename));
+    }
+
+    writeln!(
+        code,
+        "static TABLE_B_{}: &[((u8, u8), ElementDescriptor)] = &[",
+        version
+    )
+    .unwrap();
+    for ((x, y), (name, unit, scale, reference, width)) in &table_b {
+        let variant = unit_variant(units, unit).unwrap_or_else(|| {
+            panic!(
+                "{}: unit {:?} of 0-{:02}-{:03} isn't in src/units.rs",
+                version_dir.display(),
+                unit,
+                x,
+                y
+            )
+        });
+        writeln!(
+            code,
+            "    (({}, {}), ElementDescriptor {{ name: Cow::Borrowed({:?}), unit: BUFRUnit::{}, scale: {}, reference_value: {}, data_width: {} }}),",
+            x, y, name, variant, scale, reference, width
+        )
+        .unwrap();
+    }
+    code.push_str("];\n\n");
+
+    writeln!(code, "static TABLE_D_{}: &[EmbeddedSequence] = &[", version).unwrap();
+    for ((x, y), (title, descriptors)) in &table_d {
+        let descriptors: Vec<_> = descriptors
+            .iter()
+            .map(|&(f, x, y)| format!("Descriptor::new({}, {}, {})", f, x, y))
+            .collect();
+        writeln!(
+            code,
+            "    (({}, {}), {:?}, &[{}]),",
+            x,
+            y,
+            title,
+            descriptors.join(", ")
+        )
+        .unwrap();
+    }
+    code.push_str("];\n\n");
+
+    writeln!(
+        code,
+        "static CODE_FLAG_{}: &[((u8, u8), CodeTable)] = &[",
+        version
+    )
+    .unwrap();
+    for ((x, y), (name, entries)) in &code_flag {
+        let entries: Vec<_> = entries
+            .iter()
+            .map(|(first, last, meaning)| {
+                format!(
+                    "crate::codes::CodeEntry {{ first: {}, last: {}, meaning: Cow::Borrowed({:?}) }}",
+                    first, last, meaning
+                )
+            })
+            .collect();
+        writeln!(
+            code,
+            "    (({}, {}), CodeTable {{ name: Cow::Borrowed({:?}), entries: Cow::Borrowed(&[{}]) }}),",
+            x,
+            y,
+            name,
+            entries.join(", ")
+        )
+        .unwrap();
+    }
+    code.push_str("];\n\n");
+}
+
+/// Spellings of the units in the registry of src/units.rs, with their
+/// variant of `BUFRUnit`
+///
+/// The registry has one `entry(BUFRUnit::Variant, &["spelling", ...], ...)`
+/// per line.
+fn unit_variants(filename: &Path) -> Vec<(String, String)> {
+    let text = fs::read_to_string(filename).unwrap();
+    let mut variants = vec![];
+    for line in text.lines() {
+        let Some(entry) = line.trim().strip_prefix("entry(BUFRUnit::") else {
+            continue;
+        };
+        let invalid = || panic!("{}: invalid registry entry {:?}", filename.display(), line);
+        let (variant, rest) = entry.split_once(", &[\"").unwrap_or_else(invalid);
+        let (symbols, _) = rest.split_once("\"]").unwrap_or_else(invalid);
+        for symbol in symbols.split("\", \"") {
+            variants.push((symbol.to_string(), variant.to_string()));
+        }
+    }
+    variants
+}
+
+/// Variant of `BUFRUnit` for a unit spelled as in the tables, found like
+/// `BUFRUnit::from` does
+fn unit_variant<'a>(units: &'a [(String, String)], unit: &str) -> Option<&'a str> {
+    let unit = unit.trim();
+    units
+        .iter()
+        .find(|(symbol, _)| symbol == unit)
+        .or_else(|| {
+            units
+                .iter()
+                .find(|(symbol, _)| symbol.eq_ignore_ascii_case(unit))
+        })
+        .map(|(_, variant)| variant.as_str())
+}
+
+/// CSV files of `dir` starting with `prefix`, in name order
+fn csv_files(dir: &Path, prefix: &str) -> Vec<PathBuf> {
+    let mut filenames: Vec<_> = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| {
+            let name = path.file_name().unwrap().to_string_lossy();
+            name.starts_with(prefix) && name.ends_with(".csv")
+        })
+        .collect();
+    filenames.sort();
+    filenames
+}
+
+/// Record of a CSV file, with its columns found by name
+struct Record {
+    filename: String,
+    line: usize,
+    headers: Rc<csv::StringRecord>,
+    record: csv::StringRecord,
+}
+
+impl Record {
+    fn optional(&self, column: &str) -> Option<String> {
+        self.headers
+            .iter()
+            .position(|h| h == column)
+            .and_then(|i| self.record.get(i))
+            .map(|s| s.to_string())
+    }
+
+    fn get(&self, column: &str) -> String {
+        self.optional(column)
+            .unwrap_or_else(|| panic!("{}:{}: no {}", self.filename, self.line, column))
+    }
+}
+
+/// Records of a CSV file
+fn records(filename: &Path) -> Vec<Record> {
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .from_path(filename)
+        .unwrap_or_else(|e| panic!("{}: {}", filename.display(), e));
+    let headers = Rc::new(reader.headers().unwrap().clone());
+    reader
+        .records()
+        .enumerate()
+        .map(|(i, record)| Record {
+            filename: filename.display().to_string(),
+            line: i + 2,
+            headers: headers.clone(),
+            record: record.unwrap_or_else(|e| panic!("{}: {}", filename.display(), e)),
+        })
+        .collect()
+}
+
+fn number<T: std::str::FromStr>(filename: &Path, line: usize, text: &str) -> T {
+    text.trim()
+        .parse()
+        .unwrap_or_else(|_| panic!("{}:{}: invalid number {:?}", filename.display(), line, text))
+}
+
+fn fxy(filename: &Path, line: usize, code: &str) -> (u8, u8, u8) {
+    let valid = code.len() == 6 && code.bytes().all(|b| b.is_ascii_digit()) && code[..1] <= *"3";
+    if !valid {
+        panic!(
+            "{}:{}: invalid descriptor {:?}",
+            filename.display(),
+            line,
+            code
+        );
+    }
+    (
+        number(filename, line, &code[..1]),
+        number(filename, line, &code[1..3]),
+        number(filename, line, &code[3..]),
+    )
+}
+
+fn parse_table_b(filename: &Path) -> BTreeMap<(u8, u8), Element> {
+    let mut table = BTreeMap::new();
+    for record in records(filename) {
+        let line = record.line;
+        let (f, x, y) = fxy(filename, line, &record.get("FXY"));
+        if f != 0 {
+            panic!("{}:{}: not an element", filename.display(), line);
+        }
+        let element = (
+            record.get("ElementName_en"),
+            record.get("BUFR_Unit"),
+            number(filename, line, &record.get("BUFR_Scale")),
+            number(filename, line, &record.get("BUFR_ReferenceValue")),
+            number(filename, line, &record.get("BUFR_DataWidth_Bits")),
+        );
+        table.insert((x, y), element);
+    }
+    table
+}
+
+fn parse_table_d(filename: &Path) -> BTreeMap<(u8, u8), Sequence> {
+    let mut table: BTreeMap<_, Sequence> = BTreeMap::new();
+    for record in records(filename) {
+        let line = record.line;
+        let (f, x, y) = fxy(filename, line, &record.get("FXY1"));
+        if f != 3 {
+            panic!("{}:{}: not a sequence", filename.display(), line);
+        }
+        let descriptor = fxy(filename, line, &record.get("FXY2"));
+        let title = Some(record.get("Title_en")).filter(|t| !t.is_empty());
+        table
+            .entry((x, y))
+            .or_insert_with(|| (title, vec![]))
+            .1
+            .push(descriptor);
+    }
+    table
+}
+
+/// Parses code and flag tables, like `parse_code_flag` in src/codes.rs
+fn parse_code_flag(filename: &Path) -> BTreeMap<(u8, u8), CodeTable> {
+    let mut table: BTreeMap<_, CodeTable> = BTreeMap::new();
+    for record in records(filename) {
+        let line = record.line;
+        let (_, x, y) = fxy(filename, line, &record.get("FXY"));
+        let code_table = table
+            .entry((x, y))
+            .or_insert_with(|| (record.get("ElementName_en"), vec![]));
+        // Other forms, like "All 4" for the missing value of flag tables,
+        // aren't needed for lookups
+        let figure = record.get("CodeFigure");
+        let figure = figure.trim();
+        let figures = match figure.split_once('-') {
+            Some((first, last)) => first.trim().parse().ok().zip(last.trim().parse().ok()),
+            None => figure.parse().ok().map(|n| (n, n)),
+        };
+        if let Some((first, last)) = figures {
+            let meaning: Vec<_> = ["EntryName_en", "EntryName_sub1_en", "EntryName_sub2_en"]
+                .iter()
+                .filter_map(|column| record.optional(column))
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+                .collect();
+            code_table.1.push((first, last, meaning.join(": ")));
+        }
+    }
+    table
+}
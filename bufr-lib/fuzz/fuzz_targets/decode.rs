@@ -0,0 +1,9 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+// Any input must result in Ok or Err, never in a panic
+fuzz_target!(|data: &[u8]| {
+    if let Ok(message) = bufr::decode(data) {
+        let _ = message.subsets();
+    }
+});
@@ -0,0 +1,306 @@
+//! Messages read one by one from a tokio stream
+
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+use crate::error::Error;
+use crate::reader::find_start;
+use crate::{decode_with, DecodeOptions, Message};
+
+/// Bytes asked from the stream at once
+const CHUNK: usize = 64 * 1024;
+
+/// Reader of the messages of an asynchronous stream, such as a socket
+///
+/// Messages are found like `Messages` does: anything between them is
+/// skipped, and after a message that can't be decoded the search restarts
+/// right after its "BUFR" marker. Only the message being decoded is kept in
+/// memory.
+///
+/// ```no_run
+/// # async fn ingest() -> Result<(), Box<dyn std::error::Error>> {
+/// let stream = tokio::net::TcpStream::connect("localhost:4000").await?;
+/// let mut messages = bufr::async_messages(stream);
+/// while let Some(message) = messages.next().await {
+///     println!("{}", message?);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct AsyncMessages<R> {
+    reader: R,
+    options: DecodeOptions,
+    // Bytes read but not consumed yet
+    buf: Vec<u8>,
+    // Position in the stream of the start of buf
+    consumed: usize,
+    eof: bool,
+    index: usize,
+    // Start of the last message returned
+    offset: usize,
+}
+
+/// Read the messages of `reader`
+pub fn async_messages<R: AsyncRead + Unpin>(reader: R) -> AsyncMessages<R> {
+    async_messages_with(reader, &DecodeOptions::default())
+}
+
+/// Read the messages of `reader`, decoding them with `options`
+pub fn async_messages_with<R: AsyncRead + Unpin>(
+    reader: R,
+    options: &DecodeOptions,
+) -> AsyncMessages<R> {
+    AsyncMessages {
+        reader,
+        options: options.clone(),
+        buf: vec![],
+        consumed: 0,
+        eof: false,
+        index: 0,
+        offset: 0,
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncMessages<R> {
+    /// Index of the last message returned
+    pub fn index(&self) -> usize {
+        self.index.saturating_sub(1)
+    }
+
+    /// Position in the stream of the last message returned
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Next message of the stream, or `None` at its end
+    ///
+    /// The message owns its data, as the stream doesn't keep it.
+    ///
+    /// This method is cancel safe: when its future is dropped before it
+    /// completes, for instance in a branch of `tokio::select!` that didn't
+    /// win, no byte of the stream is lost and the next call resumes the
+    /// search where it stopped.
+    pub async fn next(&mut self) -> Option<Result<Message<'static>, Error>> {
+        let start = loop {
+            if let Some(start) = find_start(&self.buf) {
+                break start;
+            }
+            // Keep what may be the beginning of a marker
+            self.consume(self.buf.len().saturating_sub(3));
+            if self.eof {
+                return None;
+            }
+            if let Err(e) = self.fill().await {
+                return Some(Err(e));
+            }
+        };
+        self.consume(start);
+
+        // Section 0 gives the length of the whole message
+        let mut needed = 8;
+        loop {
+            if self.buf.len() >= 8 {
+                needed = (usize::from(self.buf[4]) << 16)
+                    | (usize::from(self.buf[5]) << 8)
+                    | usize::from(self.buf[6]);
+            }
+            if self.buf.len() >= needed || self.eof {
+                break;
+            }
+            if let Err(e) = self.fill().await {
+                return Some(Err(e));
+            }
+        }
+
+        let index = self.index;
+        self.index += 1;
+        self.offset = self.consumed;
+        match decode_with(&self.buf, &self.options) {
+            Ok(message) => {
+                let message = message.into_owned();
+                self.consume(message.total_length() as usize);
+                Some(Ok(message))
+            }
+            Err(e) => {
+                let e = e.with_message(index, self.offset);
+                self.consume(4);
+                Some(Err(e))
+            }
+        }
+    }
+
+    /// Read more of the stream
+    ///
+    /// The buffer only grows by the bytes actually read, so that dropping
+    /// the future while it waits for the stream leaves it unchanged.
+    async fn fill(&mut self) -> Result<(), Error> {
+        self.buf.reserve(CHUNK);
+        match self.reader.read_buf(&mut self.buf).await {
+            Ok(0) => {
+                self.eof = true;
+                Ok(())
+            }
+            Ok(_) => Ok(()),
+            Err(e) => {
+                self.eof = true;
+                Err(Error::from(e))
+            }
+        }
+    }
+
+    fn consume(&mut self, n: usize) {
+        self.buf.drain(..n);
+        self.consumed += n;
+    }
+}
+
+#[cfg(test)]
+mod test_async_messages {
+    use std::io;
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+
+    use tokio::io::{AsyncRead, ReadBuf};
+
+    use super::async_messages;
+    use crate::{messages, Error};
+
+    // Smallest valid message, as in the tests of the sync reader
+    fn message() -> Vec<u8> {
+        let mut buf = b"BUFR".to_vec();
+        buf.extend_from_slice(&[0, 0, 49, 4]);
+        buf.extend_from_slice(&[
+            0, 0, 22, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 7, 228, 1, 1, 0, 0, 0,
+        ]);
+        buf.extend_from_slice(&[0, 0, 9, 0, 0, 1, 128, 64, 1]);
+        buf.extend_from_slice(&[0, 0, 6, 0, 7, 228]);
+        buf.extend_from_slice(b"7777");
+        buf
+    }
+
+    /// Gives a few bytes at a time, like a slow socket
+    struct Trickle {
+        data: Vec<u8>,
+        position: usize,
+    }
+
+    impl AsyncRead for Trickle {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+            buf: &mut ReadBuf<'_>,
+        ) -> Poll<io::Result<()>> {
+            let end = (self.position + 5).min(self.data.len());
+            let end = end.min(self.position + buf.remaining());
+            buf.put_slice(&self.data[self.position..end]);
+            self.position = end;
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    /// Waits once at `stall`, like a socket before more data arrives
+    struct Stall {
+        data: Vec<u8>,
+        position: usize,
+        stall: Option<usize>,
+    }
+
+    impl AsyncRead for Stall {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &mut ReadBuf<'_>,
+        ) -> Poll<io::Result<()>> {
+            if self.stall == Some(self.position) {
+                self.stall = None;
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+            let end = self.stall.unwrap_or(self.data.len());
+            let end = end.min(self.position + buf.remaining());
+            buf.put_slice(&self.data[self.position..end]);
+            self.position = end;
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    fn stream() -> Vec<u8> {
+        let mut corrupted = message();
+        let n = corrupted.len();
+        corrupted[n - 1] = b'0';
+
+        let mut buf = b"garbage BU".to_vec();
+        buf.extend(message());
+        buf.extend_from_slice(b"\n\n");
+        buf.extend(corrupted);
+        buf.extend(message());
+        // Cut in the middle of the last message
+        buf.extend_from_slice(&message()[..20]);
+        buf
+    }
+
+    #[tokio::test]
+    async fn same_as_sync() {
+        let buf = stream();
+        let mut reader = async_messages(Trickle {
+            data: buf.clone(),
+            position: 0,
+        });
+        let mut all = vec![];
+        let mut offsets = vec![];
+        while let Some(result) = reader.next().await {
+            all.push(result);
+            offsets.push(reader.offset());
+        }
+
+        let mut sync = messages(&buf);
+        let mut expected = vec![];
+        let mut expected_offsets = vec![];
+        while let Some(result) = sync.next() {
+            expected.push(result.map(|m| m.total_length()));
+            expected_offsets.push(sync.offset());
+        }
+
+        assert_eq!(all.len(), 4);
+        assert_eq!(offsets, expected_offsets);
+        for (result, expected) in all.iter().zip(&expected) {
+            match (result, expected) {
+                (Ok(m), Ok(length)) => assert_eq!(m.total_length(), *length),
+                (Err(e), Err(expected)) => assert_eq!(e.to_string(), expected.to_string()),
+                _ => panic!("{:?} instead of {:?}", result, expected),
+            }
+        }
+        assert!(matches!(
+            all[1].as_ref().unwrap_err().kind(),
+            Error::EndSection { .. }
+        ));
+        assert!(matches!(
+            all[3].as_ref().unwrap_err().kind(),
+            Error::TruncatedMessage
+        ));
+    }
+
+    #[tokio::test]
+    async fn cancelled() {
+        let mut reader = async_messages(Stall {
+            data: message(),
+            position: 0,
+            stall: Some(20),
+        });
+        // The read of the rest of the message is pending when dropped
+        tokio::select! {
+            biased;
+            _ = reader.next() => panic!("the stream should be waiting"),
+            _ = std::future::ready(()) => (),
+        }
+        let message = reader.next().await.unwrap().unwrap();
+        assert_eq!(message.total_length(), 49);
+        assert_eq!(reader.offset(), 0);
+        assert!(reader.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn empty_stream() {
+        let mut reader = async_messages(&b"no message"[..]);
+        assert!(reader.next().await.is_none());
+    }
+}
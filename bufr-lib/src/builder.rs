@@ -0,0 +1,354 @@
+//! Assemble a complete BUFR message from its parts
+
+use std::convert::TryFrom;
+use std::sync::Arc;
+
+use crate::data::encode_subsets;
+use crate::error::Error;
+use crate::identification::Section1;
+use crate::tables::{default_table_set, TableSet};
+use crate::{DecodeOptions, Descriptor, Message, Section2, Section3, Section4, Value};
+
+/// Largest message that fits in the 24 bits length of section 0
+const MAX_TOTAL_LENGTH: usize = 0xff_ff_ff;
+
+/// Builder for a whole BUFR message
+///
+/// Lengths of every section, the total length, the number of subsets and
+/// the edition are computed from the content, so they can't disagree. The
+/// edition is that of section 1; edition 3 sections are padded to an even
+/// length.
+///
+/// The message is decoded with the tables it was encoded with.
+///
+/// ```
+/// use bufr::{Descriptor, MessageBuilder, Section1, Section1v4Builder, Value};
+///
+/// # #[cfg(feature = "wmo-35")]
+/// # {
+/// let section1 = Section1v4Builder::default()
+///     .master_table(0)
+///     .sub_center(0)
+///     .center(0)
+///     .update_version(0)
+///     .optional_section(false)
+///     .data_category(31)
+///     .data_subcategory(0)
+///     .local_subcategory(0)
+///     .master_table_version(35)
+///     .local_table_version(0)
+///     .year(2020)
+///     .month(10)
+///     .day(6)
+///     .hour(19)
+///     .minute(24)
+///     .second(0)
+///     .build()
+///     .unwrap();
+///
+/// let message = MessageBuilder::default()
+///     .section1(Section1::V4(section1))
+///     .descriptors(vec![Descriptor::new(3, 1, 11)])
+///     .subset(vec![Value::Integer(2020), Value::Integer(10), Value::Integer(6)])
+///     .build()
+///     .unwrap();
+///
+/// let mut buf = vec![];
+/// message.encode(&mut buf).unwrap();
+/// assert_eq!(buf.len(), message.total_length() as usize);
+/// # }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct MessageBuilder {
+    section1: Option<Section1>,
+    section2: Option<Vec<u8>>,
+    is_observed: Option<bool>,
+    descriptors: Vec<Descriptor>,
+    subsets: Vec<Vec<Value>>,
+    tables: Option<Arc<TableSet>>,
+}
+
+impl MessageBuilder {
+    /// Identification section. Its variant defines the edition.
+    pub fn section1(&mut self, value: Section1) -> &mut Self {
+        self.section1 = Some(value);
+        self
+    }
+
+    /// Local data for the optional section 2
+    pub fn section2(&mut self, value: Vec<u8>) -> &mut Self {
+        self.section2 = Some(value);
+        self
+    }
+
+    /// Observed (default) or other data
+    pub fn is_observed(&mut self, value: bool) -> &mut Self {
+        self.is_observed = Some(value);
+        self
+    }
+
+    /// Unexpanded descriptors for section 3
+    pub fn descriptors(&mut self, value: Vec<Descriptor>) -> &mut Self {
+        self.descriptors = value;
+        self
+    }
+
+    /// Append one subset
+    ///
+    /// The values follow the expanded descriptors, including the delayed
+    /// replication factors, exactly as returned by `Subset::values`.
+    pub fn subset(&mut self, values: Vec<Value>) -> &mut Self {
+        self.subsets.push(values);
+        self
+    }
+
+    /// Replace all subsets
+    pub fn subsets(&mut self, values: Vec<Vec<Value>>) -> &mut Self {
+        self.subsets = values;
+        self
+    }
+
+    /// Tables to encode the values with, instead of the embedded ones
+    pub fn tables(&mut self, value: TableSet) -> &mut Self {
+        self.tables = Some(Arc::new(value));
+        self
+    }
+
+    /// Validate the content and assemble the message
+    pub fn build(&self) -> Result<Message<'static>, Error> {
+        let mut section1 = self
+            .section1
+            .clone()
+            .ok_or(Error::UninitializedField("section1"))?;
+        let version = section1.edition();
+        section1.prepare(self.section2.is_some());
+
+        if self.descriptors.is_empty() {
+            return Err(Error::UninitializedField("descriptors"));
+        }
+        if self.subsets.is_empty() {
+            return Err(Error::UninitializedField("subsets"));
+        }
+        let n_subsets = u16::try_from(self.subsets.len())
+            .map_err(|_| Error::TooManySubsets(self.subsets.len()))?;
+
+        let mut section2 = self.section2.clone().map(Section2::new);
+        let mut section3 = Section3 {
+            length: 7 + 2 * self.descriptors.len(),
+            n_subsets,
+            is_observed: self.is_observed.unwrap_or(true),
+            is_compressed: false,
+            descriptors: self.descriptors.clone(),
+        };
+        let tables = match self.tables.as_deref() {
+            Some(tables) => tables,
+            None => default_table_set()?,
+        };
+        let tables = tables.resolve(&section1)?;
+        let mut section4 =
+            Section4::new(encode_subsets(&self.descriptors, &self.subsets, &tables)?);
+        if version == 3 {
+            if let Some(section2) = &mut section2 {
+                section2.pad();
+            }
+            section3.length += section3.length % 2;
+            section4.pad();
+        }
+
+        let total_length = 8
+            + section1.length()
+            + section2.as_ref().map_or(0, |s| s.length())
+            + section3.length()
+            + section4.length()
+            + 4;
+        if total_length > MAX_TOTAL_LENGTH {
+            return Err(Error::MessageTooLong(total_length));
+        }
+
+        // Decode with the tables the values were encoded with
+        let mut options = DecodeOptions::default();
+        options.tables = self.tables.clone();
+
+        Ok(Message {
+            total_length: total_length as u32,
+            version,
+            section1,
+            section2,
+            section3,
+            section4,
+            options,
+            data: None,
+            warnings: vec![],
+        })
+    }
+}
+
+#[cfg(all(test, feature = "wmo-35"))]
+mod test_message_builder {
+    use super::MessageBuilder;
+    use crate::identification::{Section1, Section1v4Builder};
+    use crate::{Checks, Descriptor, Error, TableSet, Tables, Value};
+
+    fn section1() -> Section1 {
+        section1_local(0)
+    }
+
+    fn section1_local(local_table_version: u8) -> Section1 {
+        Section1::V4(
+            Section1v4Builder::default()
+                .master_table(0)
+                .sub_center(0)
+                .center(0)
+                .update_version(0)
+                .optional_section(false)
+                .data_category(31)
+                .data_subcategory(0)
+                .local_subcategory(0)
+                .master_table_version(35)
+                .local_table_version(local_table_version)
+                .year(2020)
+                .month(10)
+                .day(6)
+                .hour(19)
+                .minute(24)
+                .second(0)
+                .local_use(vec![1, 2, 3])
+                .build()
+                .unwrap(),
+        )
+    }
+
+    #[test]
+    fn roundtrip() -> Result<(), Box<dyn std::error::Error>> {
+        let subsets = vec![
+            vec![
+                Value::Integer(2020),
+                Value::Integer(10),
+                Value::Integer(6),
+                Value::Float(36.803),
+            ],
+            vec![
+                Value::Integer(2021),
+                Value::Integer(1),
+                Value::Integer(2),
+                Value::Missing,
+            ],
+        ];
+        let message = MessageBuilder::default()
+            .section1(section1())
+            .section2(vec![42; 5])
+            .descriptors(vec![Descriptor::new(3, 1, 11), Descriptor::new(0, 5, 1)])
+            .subsets(subsets.clone())
+            .build()?;
+
+        assert_eq!(message.section1().length(), 25);
+        assert!(message.section1().optional_section());
+        assert_eq!(message.section2().unwrap().length(), 9);
+        assert_eq!(message.section3().length(), 11);
+        assert_eq!(message.section3().n_subsets(), 2);
+        // 2 * (12 + 4 + 6 + 25) bits
+        assert_eq!(message.section4().length(), 4 + 12);
+
+        let mut buf = vec![];
+        let n = message.encode(&mut buf)?;
+        assert_eq!(n, buf.len());
+        assert_eq!(message.total_length() as usize, buf.len());
+
+        let decoded = crate::decode(&buf)?;
+        assert_eq!(decoded.section2().unwrap().data(), &[42; 5]);
+        let values: Vec<Vec<Value>> = decoded
+            .subsets()?
+            .iter()
+            .map(|s| s.values().cloned().collect())
+            .collect();
+        assert_eq!(values, subsets);
+
+        Ok(())
+    }
+
+    #[test]
+    fn edition_3() -> Result<(), Box<dyn std::error::Error>> {
+        // Section 1 of edition 3, with its padding byte
+        let mut buf = vec![0, 0, 18, 0, 0, 98, 0, 0, 31, 0, 35, 0, 20, 10, 6, 19, 24, 0];
+        let section1 = Section1::decode(&buf, 8, 3, &mut Checks::new(true))?;
+        let values = vec![Value::Integer(2020), Value::Integer(10), Value::Integer(6)];
+        let message = MessageBuilder::default()
+            .section1(section1)
+            .section2(vec![42; 5])
+            .descriptors(vec![Descriptor::new(3, 1, 11)])
+            .subset(values.clone())
+            .build()?;
+        assert_eq!(message.version(), 3);
+        assert_eq!(message.section1().length(), 18);
+        assert_eq!(message.section2().unwrap().length(), 10);
+        assert_eq!(message.section3().length(), 10);
+        // 12 + 4 + 6 bits
+        assert_eq!(message.section4().length(), 8);
+
+        buf.clear();
+        let n = message.encode(&mut buf)?;
+        assert_eq!(n, buf.len());
+        assert_eq!(message.total_length() as usize, buf.len());
+
+        let decoded = crate::decode(&buf)?;
+        assert_eq!(decoded.version(), 3);
+        assert_eq!(decoded.section1().center(), 98);
+        let decoded: Vec<_> = decoded.subsets()?[0].values().cloned().collect();
+        assert_eq!(decoded, values);
+        Ok(())
+    }
+
+    #[test]
+    fn local_tables() -> Result<(), Box<dyn std::error::Error>> {
+        let sequence = "Category,CategoryOfSequences_en,FXY1,Title_en,SubTitle_en,FXY2,ElementName_en,ElementDescription_en,Note_en,Status\n\
+            50,Local,350001,Local,,004001,Year,,,Operational\n\
+            50,Local,350001,Local,,004002,Month,,,Operational\n";
+        let mut local = Tables::default();
+        local.add_table_d(sequence.as_bytes())?;
+        let mut tables = TableSet::wmo();
+        tables.insert_local(0, 0, 1, local);
+
+        let values = vec![Value::Integer(2020), Value::Integer(10)];
+        let message = MessageBuilder::default()
+            .section1(section1_local(1))
+            .descriptors(vec![Descriptor::new(3, 50, 1)])
+            .subset(values.clone())
+            .tables(tables)
+            .build()?;
+
+        // Section 4 is decoded with the tables of the builder
+        let decoded: Vec<_> = message.subsets()?[0].values().cloned().collect();
+        assert_eq!(decoded, values);
+
+        // The encoded message needs them too
+        let mut buf = vec![];
+        message.encode(&mut buf)?;
+        assert!(matches!(
+            crate::decode(&buf)?.subsets(),
+            Err(e) if matches!(e.kind(), Error::UnknownDescriptor(3, 50, 1))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn validation() {
+        let mut builder = MessageBuilder::default();
+        assert!(matches!(
+            builder.build(),
+            Err(Error::UninitializedField("section1"))
+        ));
+
+        builder
+            .section1(section1())
+            .descriptors(vec![Descriptor::new(0, 4, 1)]);
+        assert!(matches!(
+            builder.build(),
+            Err(Error::UninitializedField("subsets"))
+        ));
+
+        builder.subset(vec![Value::String("2020".into())]);
+        let err = builder.build().unwrap_err();
+        assert!(matches!(err.kind(), Error::InvalidValue(..)));
+        assert_eq!(err.location().unwrap().subset(), Some(0));
+    }
+}
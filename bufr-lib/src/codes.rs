@@ -0,0 +1,330 @@
+//! Code and flag tables
+//!
+//! Elements whose unit is a code table hold a number standing for one of
+//! the entries of the table, while flag tables give a meaning to each bit,
+//! numbered from 1 for the most significant one.
+//!
+//! Only the tables of 0-02-001 to 0-02-003, 0-08-001 and 0-08-021 are
+//! shipped with the crate. The values of other elements have no meaning
+//! until their tables are loaded, with `Tables::load_code_flag` or from
+//! the directory in `BUFR_TABLES_PATH`.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::Error;
+
+pub(crate) type TableCodeFlag = HashMap<(u8, u8), CodeTable>;
+
+/// Code or flag table of one element descriptor
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CodeTable {
+    pub(crate) name: Cow<'static, str>,
+    pub(crate) entries: Cow<'static, [CodeEntry]>,
+}
+
+/// Code figures, or bit numbers, from `first` to `last` sharing a meaning
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct CodeEntry {
+    pub(crate) first: u64,
+    pub(crate) last: u64,
+    pub(crate) meaning: Cow<'static, str>,
+}
+
+impl CodeTable {
+    /// Name of the element
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Meaning of a code figure, or of a bit number for flag tables
+    pub fn entry(&self, figure: u64) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|e| e.first <= figure && figure <= e.last)
+            .map(|e| &*e.meaning)
+    }
+
+    /// Bits set in a flag table value of `width` bits, with their meaning
+    ///
+    /// Bits without an entry in the table are left out.
+    pub fn flags(&self, value: u64, width: u16) -> Vec<(u16, &str)> {
+        (1..=width)
+            .filter(|bit| width - bit < 64 && (value >> (width - bit)) & 1 == 1)
+            .filter_map(|bit| self.entry(u64::from(bit)).map(|m| (bit, m)))
+            .collect()
+    }
+}
+
+/// Meaning of a value of a code or flag table
+#[derive(Clone, Debug, PartialEq)]
+pub enum Meaning {
+    /// Entry of a code table
+    Code(String),
+    /// Bits set in a flag table, with their meaning
+    Flags(Vec<(u16, String)>),
+}
+
+impl fmt::Display for Meaning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Meaning::Code(s) => write!(f, "{}", s),
+            Meaning::Flags(flags) => {
+                let names: Vec<_> = flags.iter().map(|(_, s)| s.as_str()).collect();
+                write!(f, "{}", names.join(", "))
+            }
+        }
+    }
+}
+
+#[allow(dead_code, non_snake_case)]
+#[derive(Debug, Deserialize, Clone)]
+struct RecordCodeFlag {
+    FXY: String,
+    ElementName_en: String,
+    CodeFigure: String,
+    EntryName_en: String,
+    #[serde(default)]
+    EntryName_sub1_en: Option<String>,
+    #[serde(default)]
+    EntryName_sub2_en: Option<String>,
+    #[serde(default)]
+    Status: Option<String>,
+}
+
+impl RecordCodeFlag {
+    /// Code figures covered by the entry, either "N" or "N-M"
+    ///
+    /// Other forms, like "All 4" for the missing value of flag tables, are
+    /// not needed for lookups.
+    fn figures(&self) -> Option<(u64, u64)> {
+        let figure = self.CodeFigure.trim();
+        match figure.split_once('-') {
+            Some((first, last)) => Some((first.trim().parse().ok()?, last.trim().parse().ok()?)),
+            None => figure.parse().ok().map(|n| (n, n)),
+        }
+    }
+
+    fn meaning(&self) -> String {
+        [
+            Some(&self.EntryName_en),
+            self.EntryName_sub1_en.as_ref(),
+            self.EntryName_sub2_en.as_ref(),
+        ]
+        .iter()
+        .flatten()
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<_>>()
+        .join(": ")
+    }
+}
+
+pub(crate) fn load_code_flag<P: AsRef<Path>>(filename: P) -> Result<TableCodeFlag, Error> {
+    let file = File::open(filename)?;
+    let reader = BufReader::new(file);
+    parse_code_flag(reader)
+}
+
+/// Parses code and flag tables in the CSV format of the WMO
+pub(crate) fn parse_code_flag<R: std::io::Read>(reader: R) -> Result<TableCodeFlag, Error> {
+    let mut table = TableCodeFlag::default();
+
+    let mut rdr = csv::Reader::from_reader(reader);
+    for result in rdr.deserialize() {
+        let record: RecordCodeFlag = result.map_err(|e| Error::InvalidTable(e.to_string()))?;
+        let (_, x, y) = crate::tables::parse_fxy(&record.FXY)?;
+        let code_table = table.entry((x, y)).or_insert_with(|| CodeTable {
+            name: record.ElementName_en.clone().into(),
+            entries: Cow::Owned(vec![]),
+        });
+        if let Some((first, last)) = record.figures() {
+            code_table.entries.to_mut().push(CodeEntry {
+                first,
+                last,
+                meaning: record.meaning().into(),
+            });
+        }
+    }
+    Ok(table)
+}
+
+#[cfg(all(test, feature = "wmo-35"))]
+mod test_codes {
+    use super::{parse_code_flag, Meaning};
+    use crate::identification::{Section1, Section1v4Builder};
+    use crate::{Descriptor, MessageBuilder, TableProvider, TableSet, Tables, Value};
+
+    #[test]
+    fn code_table() {
+        let tables = Tables::embedded();
+        let table = tables.code_table(8, 21).unwrap();
+        assert_eq!(table.name(), "Time significance");
+        assert_eq!(table.entry(25), Some("Nominal reporting time"));
+        assert_eq!(table.entry(32), None);
+
+        let table = tables.code_table(2, 3).unwrap();
+        assert_eq!(table.entry(12), Some("Reserved"));
+    }
+
+    #[test]
+    fn flag_table() {
+        let tables = Tables::embedded();
+        let table = tables.code_table(2, 2).unwrap();
+        // Bits 1 and 3 of 4
+        assert_eq!(
+            table.flags(0b1010, 4),
+            vec![
+                (1, "Certified instruments"),
+                (3, "Originally measured in km h-1")
+            ]
+        );
+        assert!(table.flags(0, 4).is_empty());
+        // Bit 4 has no entry
+        assert_eq!(table.flags(1, 4), vec![]);
+    }
+
+    #[test]
+    fn parse() {
+        let csv = "FXY,ElementName_en,CodeFigure,EntryName_en,EntryName_sub1_en,EntryName_sub2_en,Note_en,noteIDs,Status
+020199,Test,0,Clear,,,,,Operational
+020199,Test,1,Cloud,Low,,,,Operational
+020199,Test,2-6,Reserved,,,,,Operational
+020199,Test,All 3,Missing value,,,,,Operational
+";
+        let table = parse_code_flag(csv.as_bytes()).unwrap();
+        let table = table.get(&(20, 199)).unwrap();
+        assert_eq!(table.entry(1), Some("Cloud: Low"));
+        assert_eq!(table.entry(4), Some("Reserved"));
+        assert_eq!(table.entry(7), None);
+
+        let meaning = Meaning::Flags(vec![(1, "A".to_string()), (2, "B".to_string())]);
+        assert_eq!(meaning.to_string(), "A, B");
+    }
+
+    #[test]
+    fn message_meaning() -> Result<(), Box<dyn std::error::Error>> {
+        let section1 = Section1v4Builder::default()
+            .master_table(0)
+            .sub_center(0)
+            .center(0)
+            .update_version(0)
+            .optional_section(false)
+            .data_category(0)
+            .data_subcategory(0)
+            .local_subcategory(0)
+            .master_table_version(35)
+            .local_table_version(0)
+            .year(2020)
+            .month(10)
+            .day(6)
+            .hour(19)
+            .minute(24)
+            .second(0)
+            .build()?;
+        let message = MessageBuilder::default()
+            .section1(Section1::V4(section1))
+            .descriptors(vec![
+                Descriptor::new(0, 8, 21),
+                Descriptor::new(0, 2, 2),
+                Descriptor::new(0, 2, 1),
+                Descriptor::new(0, 4, 1),
+            ])
+            .subsets(vec![vec![
+                Value::Integer(25),
+                Value::Integer(0b1010),
+                Value::Missing,
+                Value::Integer(2020),
+            ]])
+            .build()?;
+
+        let subsets = message.subsets()?;
+        let meanings: Vec<_> = subsets[0]
+            .fields()
+            .iter()
+            .map(|f| message.meaning(f))
+            .collect();
+        assert_eq!(
+            meanings[0],
+            Some(Meaning::Code("Nominal reporting time".to_string()))
+        );
+        assert_eq!(
+            meanings[1].as_ref().map(|m| m.to_string()).as_deref(),
+            Some("Certified instruments, Originally measured in km h-1")
+        );
+        assert_eq!(meanings[2], None);
+        assert_eq!(meanings[3], None);
+        Ok(())
+    }
+
+    #[test]
+    fn common_code_tables() -> Result<(), Box<dyn std::error::Error>> {
+        // Common Code table C-14 isn't embedded, but can be loaded
+        let csv = "FXY,ElementName_en,CodeFigure,EntryName_en,EntryName_sub1_en,EntryName_sub2_en,Note_en,noteIDs,Status
+008046,Atmospheric chemical or physical constituent type,0,Ozone,,,,,Operational
+";
+        let mut constituents = Tables::default();
+        constituents.add_code_flag(csv.as_bytes())?;
+        let mut tables = TableSet::wmo();
+        tables.set_default_local(constituents);
+
+        let section1 = Section1v4Builder::default()
+            .master_table(0)
+            .sub_center(0)
+            .center(7)
+            .update_version(0)
+            .optional_section(false)
+            .data_category(8)
+            .data_subcategory(0)
+            .local_subcategory(0)
+            .master_table_version(35)
+            .local_table_version(0)
+            .year(2020)
+            .month(10)
+            .day(6)
+            .hour(19)
+            .minute(24)
+            .second(0)
+            .build()?;
+        let message = MessageBuilder::default()
+            .section1(Section1::V4(section1))
+            .descriptors(vec![
+                Descriptor::new(0, 1, 33),
+                Descriptor::new(0, 1, 34),
+                Descriptor::new(0, 1, 35),
+                Descriptor::new(0, 8, 46),
+            ])
+            .subset(vec![
+                Value::Integer(98),
+                Value::Integer(3),
+                Value::Integer(34),
+                Value::Integer(0),
+            ])
+            .tables(tables)
+            .build()?;
+
+        let subsets = message.subsets()?;
+        let meanings: Vec<_> = subsets[0]
+            .fields()
+            .iter()
+            .map(|f| message.meaning(f).map(|m| m.to_string()))
+            .collect();
+        assert_eq!(
+            meanings,
+            [
+                Some("European Centre for Medium-Range Weather Forecasts".to_string()),
+                // Sub-centre of the centre of section 1
+                Some("NCEP Central Operations".to_string()),
+                Some("Tokyo (RSMC), Japan Meteorological Agency".to_string()),
+                Some("Ozone".to_string()),
+            ]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,141 @@
+//! BUFR Table A and the Common Code Tables used in section 1
+//!
+//! C-1 (8-bit originating centres, edition 3) and C-11 (16-bit originating
+//! or generating centres) share their code figures, so both are looked up
+//! in the same list.
+//!
+//! Only Table A is complete. C-1 and C-11 only have a few major centres,
+//! C-12 the sub-centres of NCEP and C-13 the sub-categories of land
+//! surface data and soundings, while C-14 isn't embedded at all. Other
+//! entries are named by code tables loaded for the elements using them,
+//! like 0-01-035 or 0-08-046, in the WMO code and flag table format.
+
+/// BUFR Table A, data categories
+#[rustfmt::skip]
+static DATA_CATEGORIES: &[(u8, u8, &str)] = &[
+    (0, 0, "Surface data - land"),
+    (1, 1, "Surface data - sea"),
+    (2, 2, "Vertical soundings (other than satellite)"),
+    (3, 3, "Vertical soundings (satellite)"),
+    (4, 4, "Single level upper-air data (other than satellite)"),
+    (5, 5, "Single level upper-air data (satellite)"),
+    (6, 6, "Radar data"),
+    (7, 7, "Synoptic features"),
+    (8, 8, "Physical/chemical constituents"),
+    (9, 9, "Dispersal and transport"),
+    (10, 10, "Radiological data"),
+    (11, 11, "BUFR tables, complete replacement or update"),
+    (12, 12, "Surface data (satellite)"),
+    (13, 13, "Forecasts"),
+    (14, 14, "Warnings"),
+    (15, 19, "Reserved"),
+    (20, 20, "Status information"),
+    (21, 21, "Radiances (satellite measured)"),
+    (22, 22, "Radar (satellite) but not altimeter and scatterometer"),
+    (23, 23, "Lidar (satellite)"),
+    (24, 24, "Scatterometry (satellite)"),
+    (25, 25, "Altimetry (satellite)"),
+    (26, 26, "Spectrometry (satellite)"),
+    (27, 27, "Gravity measurement (satellite)"),
+    (28, 28, "Precision orbit (satellite)"),
+    (29, 29, "Space environment (satellite)"),
+    (30, 30, "Calibration datasets (satellite)"),
+    (31, 31, "Oceanographic data"),
+    (101, 101, "Image data (satellite)"),
+    (240, 254, "For experimental use"),
+    (255, 255, "Indicator for local use"),
+];
+
+/// Common Code Table C-13, international data sub-categories of each
+/// data category
+#[rustfmt::skip]
+static DATA_SUBCATEGORIES: &[(u8, u8, &str)] = &[
+    (0, 0, "Hourly synoptic observations from fixed-land stations (SYNOP)"),
+    (0, 1, "Intermediate synoptic observations from fixed-land stations (SYNOP)"),
+    (0, 2, "Main synoptic observations from fixed-land stations (SYNOP)"),
+    (0, 3, "Synoptic observations from mobile-land stations (SYNOP MOBIL)"),
+    (2, 1, "Upper-wind reports from fixed-land stations (PILOT)"),
+    (2, 2, "Upper-wind reports from ships (PILOT SHIP)"),
+    (2, 3, "Upper-wind reports from mobile-land stations (PILOT MOBIL)"),
+    (2, 4, "Upper-level temperature/humidity/wind reports from fixed-land stations (TEMP)"),
+    (2, 5, "Upper-level temperature/humidity/wind reports from ships (TEMP SHIP)"),
+    (2, 6, "Upper-level temperature/humidity/wind reports from mobile-land stations (TEMP MOBIL)"),
+];
+
+/// Common Code Tables C-1 and C-11, originating or generating centres
+#[rustfmt::skip]
+static CENTRES: &[(u16, &str)] = &[
+    (0, "WMO Secretariat"),
+    (7, "US National Weather Service, National Centres for Environmental Prediction (NCEP)"),
+    (34, "Tokyo (RSMC), Japan Meteorological Agency"),
+    (74, "UK Meteorological Office Exeter (RSMC)"),
+    (78, "Offenbach (RSMC)"),
+    (85, "Toulouse (RSMC)"),
+    (98, "European Centre for Medium-Range Weather Forecasts"),
+    (254, "EUMETSAT Operation Centre"),
+];
+
+/// Common Code Table C-12, sub-centres of each originating centre
+#[rustfmt::skip]
+static SUB_CENTRES: &[(u16, u16, &str)] = &[
+    (7, 1, "NCEP Re-Analysis Project"),
+    (7, 2, "NCEP Ensemble Products"),
+    (7, 3, "NCEP Central Operations"),
+    (7, 4, "Environmental Modeling Center"),
+];
+
+/// Name of a data category, from BUFR Table A
+pub fn data_category_name(category: u8) -> Option<&'static str> {
+    DATA_CATEGORIES
+        .iter()
+        .find(|(first, last, _)| *first <= category && category <= *last)
+        .map(|(_, _, name)| *name)
+}
+
+/// Name of an international data sub-category, from Common Code Table C-13
+pub fn data_subcategory_name(category: u8, subcategory: u8) -> Option<&'static str> {
+    DATA_SUBCATEGORIES
+        .iter()
+        .find(|(c, s, _)| *c == category && *s == subcategory)
+        .map(|(_, _, name)| *name)
+}
+
+/// Name of an originating centre, from Common Code Tables C-1 and C-11
+pub fn centre_name(centre: u16) -> Option<&'static str> {
+    CENTRES
+        .iter()
+        .find(|(c, _)| *c == centre)
+        .map(|(_, name)| *name)
+}
+
+/// Name of a sub-centre of a centre, from Common Code Table C-12
+pub fn sub_centre_name(centre: u16, sub_centre: u16) -> Option<&'static str> {
+    SUB_CENTRES
+        .iter()
+        .find(|(c, s, _)| *c == centre && *s == sub_centre)
+        .map(|(_, _, name)| *name)
+}
+
+#[cfg(test)]
+mod test_common {
+    use super::{centre_name, data_category_name, data_subcategory_name, sub_centre_name};
+
+    #[test]
+    fn lookups() {
+        assert_eq!(
+            centre_name(34),
+            Some("Tokyo (RSMC), Japan Meteorological Agency")
+        );
+        assert_eq!(centre_name(65535), None);
+        assert_eq!(sub_centre_name(7, 3), Some("NCEP Central Operations"));
+        assert_eq!(sub_centre_name(34, 3), None);
+        assert_eq!(data_category_name(12), Some("Surface data (satellite)"));
+        assert_eq!(data_category_name(17), Some("Reserved"));
+        assert_eq!(data_category_name(100), None);
+        assert_eq!(
+            data_subcategory_name(2, 4),
+            Some("Upper-level temperature/humidity/wind reports from fixed-land stations (TEMP)")
+        );
+        assert_eq!(data_subcategory_name(12, 7), None);
+    }
+}
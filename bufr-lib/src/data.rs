@@ -0,0 +1,1320 @@
+//! Data section (section 4) values and their bit level encoding
+//!
+//! The descriptors from section 3 are walked in order, expanding sequences
+//! (F=3), replications (F=1) and applying operators (F=2). Every element
+//! (F=0) met on the way corresponds to one value in the data section.
+
+use std::convert::TryFrom;
+use std::fmt;
+
+use crate::options::{DecodeOptions, MissingValues};
+use crate::plan::{Count, DecodePlan, Step};
+use crate::tables::TableProvider;
+use crate::{BUFRUnit, BufferReader, Descriptor, ElementDescriptor, Error};
+
+/// A single value from the data section
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    /// All bits set, i.e. the value is missing
+    Missing,
+    /// Code and flag tables, and numeric elements without decimal scale
+    Integer(i64),
+    /// Numeric elements with a positive decimal scale
+    Float(f64),
+    /// CCITT IA5 character data
+    String(String),
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Missing => write!(f, "MISSING"),
+            Value::Integer(v) => write!(f, "{}", v),
+            Value::Float(v) => write!(f, "{}", v),
+            Value::String(v) => write!(f, "{}", v),
+        }
+    }
+}
+
+impl Value {
+    /// Converts a numeric value from the unit of its element to another unit
+    ///
+    /// Missing values stay missing.
+    pub fn convert(&self, from: &BUFRUnit, to: &BUFRUnit) -> Result<Value, Error> {
+        let value = match self {
+            Value::Missing => return Ok(Value::Missing),
+            Value::Integer(v) => *v as f64,
+            Value::Float(v) => *v,
+            Value::String(_) => {
+                return Err(Error::IncompatibleUnits(from.to_string(), to.to_string()))
+            }
+        };
+        Ok(Value::Float(from.convert(value, to)?))
+    }
+}
+
+/// A value together with the descriptor that defines it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Field {
+    descriptor: Descriptor,
+    value: Value,
+}
+
+impl Field {
+    /// Element (or operator) descriptor that defines this value
+    pub fn descriptor(&self) -> &Descriptor {
+        &self.descriptor
+    }
+
+    /// The value itself
+    pub fn value(&self) -> &Value {
+        &self.value
+    }
+}
+
+/// All the values of one subset, in the order of the expanded descriptors
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Subset {
+    fields: Vec<Field>,
+}
+
+impl Subset {
+    /// Values with their descriptors
+    pub fn fields(&self) -> &[Field] {
+        &self.fields
+    }
+
+    /// Values only, as expected by `MessageBuilder::subset`
+    pub fn values(&self) -> impl Iterator<Item = &Value> {
+        self.fields.iter().map(|f| &f.value)
+    }
+
+    /// Number of values in this subset
+    pub fn len(&self) -> usize {
+        self.fields.len()
+    }
+
+    /// True if there are no values in this subset
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Kind {
+    Numeric,
+    Code,
+    String,
+}
+
+/// Effective description of an element after applying the operators
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Spec {
+    width: usize,
+    scale: i32,
+    reference: i64,
+    kind: Kind,
+    // Delayed replication factors and 1 bit fields can't be missing
+    can_be_missing: bool,
+}
+
+impl Spec {
+    /// Data width in bits
+    pub(crate) fn width(&self) -> usize {
+        self.width
+    }
+
+    fn from_table(descriptor: &Descriptor, element: &ElementDescriptor) -> Spec {
+        let kind = match element.unit {
+            BUFRUnit::CCITTIA5 => Kind::String,
+            ref unit if unit.is_code() => Kind::Code,
+            _ => Kind::Numeric,
+        };
+        let width = usize::from(element.data_width);
+        Spec {
+            width,
+            scale: element.scale,
+            reference: i64::from(element.reference_value),
+            kind,
+            can_be_missing: width > 1 && descriptor.x != 31,
+        }
+    }
+
+    fn string(width: usize) -> Spec {
+        Spec {
+            width,
+            scale: 0,
+            reference: 0,
+            kind: Kind::String,
+            can_be_missing: false,
+        }
+    }
+
+    fn all_ones(&self) -> u64 {
+        if self.width >= 64 {
+            u64::MAX
+        } else {
+            (1 << self.width) - 1
+        }
+    }
+
+    fn decode(&self, raw: u64) -> Value {
+        if self.can_be_missing && raw == self.all_ones() {
+            return Value::Missing;
+        }
+        match self.kind {
+            Kind::Code => Value::Integer(raw as i64),
+            _ => {
+                // Wide elements could overflow, in which case fall back to f64
+                let v = i64::try_from(raw)
+                    .ok()
+                    .and_then(|v| v.checked_add(self.reference));
+                let factor = 10i64.checked_pow(self.scale.unsigned_abs());
+                match (v, factor) {
+                    (Some(v), _) if self.scale > 0 => {
+                        Value::Float(v as f64 / 10f64.powi(self.scale))
+                    }
+                    (Some(v), Some(factor)) => match v.checked_mul(factor) {
+                        Some(v) => Value::Integer(v),
+                        None => Value::Float(v as f64 * factor as f64),
+                    },
+                    _ => {
+                        Value::Float((raw as f64 + self.reference as f64) * 10f64.powi(-self.scale))
+                    }
+                }
+            }
+        }
+    }
+
+    fn encode(&self, descriptor: &Descriptor, value: &Value) -> Result<u64, Error> {
+        let invalid =
+            || Error::InvalidValue(descriptor.f, descriptor.x, descriptor.y, value.to_string());
+        let scaled = match (value, self.kind) {
+            (Value::Missing, _) if self.can_be_missing => return Ok(self.all_ones()),
+            (Value::Integer(v), Kind::Code) => *v,
+            (Value::Integer(v), Kind::Numeric) if self.scale >= 0 => v
+                .checked_mul(10i64.saturating_pow(self.scale as u32))
+                .ok_or_else(invalid)?,
+            (Value::Integer(v), Kind::Numeric) => {
+                (*v as f64 / 10f64.powi(-self.scale)).round() as i64
+            }
+            (Value::Float(v), Kind::Numeric) if v.is_finite() => {
+                (v * 10f64.powi(self.scale)).round() as i64
+            }
+            _ => return Err(invalid()),
+        };
+        let raw = scaled.checked_sub(self.reference).ok_or_else(invalid)?;
+        let max = if self.can_be_missing {
+            self.all_ones() - 1
+        } else {
+            self.all_ones()
+        };
+        if raw < 0 || raw as u64 > max {
+            return Err(invalid());
+        }
+        Ok(raw as u64)
+    }
+}
+
+fn element_descriptor<'t>(
+    tables: &'t dyn TableProvider,
+    descriptor: &Descriptor,
+) -> Result<&'t ElementDescriptor, Error> {
+    tables
+        .element(descriptor.x, descriptor.y)
+        .ok_or(Error::UnknownDescriptor(
+            descriptor.f,
+            descriptor.x,
+            descriptor.y,
+        ))
+}
+
+fn expand_sequence(
+    tables: &dyn TableProvider,
+    descriptor: &Descriptor,
+) -> Result<Vec<Descriptor>, Error> {
+    tables
+        .sequence(descriptor.x, descriptor.y)
+        .ok_or(Error::UnknownDescriptor(
+            descriptor.f,
+            descriptor.x,
+            descriptor.y,
+        ))
+}
+
+/// Something that consumes or produces one value per element
+pub(crate) trait Visitor {
+    /// Handle one element and return its value
+    fn visit(&mut self, descriptor: &Descriptor, spec: &Spec) -> Result<Value, Error>;
+
+    /// Current position in the data, in bits, if any
+    fn bit_offset(&self) -> Option<usize> {
+        None
+    }
+
+    /// Whether the value of an element is needed
+    fn wants(&self, _descriptor: &Descriptor) -> bool {
+        true
+    }
+
+    /// Move past the bits of values that aren't needed
+    ///
+    /// Returns false if the visitor can't, and has to visit them.
+    fn skip(&mut self, _width: usize) -> Result<bool, Error> {
+        Ok(false)
+    }
+}
+
+/// Operators (Table C) currently in effect
+#[derive(Debug, Default, Clone, PartialEq)]
+pub(crate) struct Operators {
+    // 2-01-YYY
+    width: i32,
+    // 2-02-YYY
+    scale: i32,
+    // 2-07-YYY
+    increase: i32,
+    // 2-08-YYY, in bits
+    string_width: Option<usize>,
+    // 2-06-YYY, only for the next element
+    local_width: Option<usize>,
+}
+
+impl Operators {
+    /// Spec of an element with the operators applied
+    pub(crate) fn element(
+        &mut self,
+        tables: &dyn TableProvider,
+        descriptor: &Descriptor,
+    ) -> Result<Spec, Error> {
+        let spec = match (
+            element_descriptor(tables, descriptor),
+            self.local_width.take(),
+        ) {
+            (Ok(element), _) => self.apply(Spec::from_table(descriptor, element)),
+            (Err(_), Some(width)) => Spec {
+                width,
+                scale: 0,
+                reference: 0,
+                kind: Kind::Numeric,
+                can_be_missing: width > 1,
+            },
+            (Err(e), None) => return Err(e),
+        };
+        if spec.width == 0 || (spec.kind != Kind::String && spec.width > 64) {
+            return Err(Error::UnsupportedDataWidth(spec.width));
+        }
+        Ok(spec)
+    }
+
+    fn apply(&self, mut spec: Spec) -> Spec {
+        match spec.kind {
+            Kind::Code => (),
+            Kind::String => {
+                if let Some(width) = self.string_width {
+                    spec.width = width;
+                }
+            }
+            Kind::Numeric => {
+                let width = spec.width as i32 + self.width + (10 * self.increase + 2) / 3;
+                spec.width = width.max(0) as usize;
+                spec.scale += self.scale + self.increase;
+                spec.reference = spec
+                    .reference
+                    .saturating_mul(10i64.saturating_pow(self.increase as u32));
+            }
+        }
+        spec
+    }
+
+    /// Put an operator in effect
+    ///
+    /// Returns the spec of the value it inserts in the data, if any.
+    pub(crate) fn operate(&mut self, descriptor: &Descriptor) -> Result<Option<Spec>, Error> {
+        let y = i32::from(descriptor.y);
+        match descriptor.x {
+            1 => self.width = if y == 0 { 0 } else { y - 128 },
+            2 => self.scale = if y == 0 { 0 } else { y - 128 },
+            5 => return Ok(Some(Spec::string(8 * usize::from(descriptor.y)))),
+            6 => self.local_width = Some(usize::from(descriptor.y)),
+            7 => self.increase = y,
+            8 => {
+                self.string_width = if y == 0 {
+                    None
+                } else {
+                    Some(8 * usize::from(descriptor.y))
+                }
+            }
+            x => return Err(Error::UnsupportedOperator(x, descriptor.y)),
+        }
+        Ok(None)
+    }
+}
+
+/// Walks a list of descriptors calling the visitor for every element
+pub(crate) struct Walker<'v, 't, V: Visitor> {
+    visitor: &'v mut V,
+    tables: &'t dyn TableProvider,
+    operators: Operators,
+    // Sequences and replications currently being walked
+    path: Vec<String>,
+    // Where the last element started in the data
+    element_start: Option<usize>,
+    max_depth: usize,
+    max_replication: usize,
+    // Descriptors walked so far, including those of previous subsets
+    expanded: usize,
+    max_expanded: usize,
+}
+
+impl<'v, 't, V: Visitor> Walker<'v, 't, V> {
+    pub(crate) fn new(visitor: &'v mut V, tables: &'t dyn TableProvider) -> Self {
+        Self::with_options(visitor, tables, &DecodeOptions::default())
+    }
+
+    pub(crate) fn with_options(
+        visitor: &'v mut V,
+        tables: &'t dyn TableProvider,
+        options: &DecodeOptions,
+    ) -> Self {
+        Walker {
+            visitor,
+            tables,
+            operators: Operators::default(),
+            path: vec![],
+            element_start: None,
+            max_depth: options.max_depth,
+            max_replication: options.max_replication,
+            expanded: 0,
+            max_expanded: options.max_expanded_descriptors,
+        }
+    }
+
+    /// Count one more descriptor walked
+    fn expand(&mut self) -> Result<(), Error> {
+        if self.expanded >= self.max_expanded {
+            return Err(Error::ExpansionLimit(self.max_expanded));
+        }
+        self.expanded += 1;
+        Ok(())
+    }
+
+    /// Walk nested descriptors, labelled in the descriptor path
+    fn nested<F>(&mut self, label: String, walk: F) -> Result<(), Error>
+    where
+        F: FnOnce(&mut Self) -> Result<(), Error>,
+    {
+        if self.path.len() >= self.max_depth {
+            return Err(Error::DepthLimit(self.max_depth));
+        }
+        self.expand()?;
+        self.path.push(label);
+        let result = walk(self);
+        self.path.pop();
+        result
+    }
+
+    /// Add the descriptor path to an error, unless a nested walk already did
+    fn locate(&self, error: Error, descriptor: &Descriptor) -> Error {
+        if let Some(location) = error.location() {
+            if !location.descriptor_path().is_empty() {
+                return error;
+            }
+        }
+        let mut path = self.path.clone();
+        path.push(descriptor.to_string());
+        let bit_offset = self.element_start.or_else(|| self.visitor.bit_offset());
+        error.locate(|l| l.set_descriptor_path(path, bit_offset))
+    }
+
+    pub(crate) fn walk(&mut self, descriptors: &[Descriptor]) -> Result<(), Error> {
+        let mut i = 0;
+        while i < descriptors.len() {
+            let descriptor = &descriptors[i];
+            i += 1;
+            let result = self.expand().and_then(|_| match descriptor.f {
+                0 => self.element(descriptor).map(|_| ()),
+                1 => self.replicate(descriptor, descriptors, &mut i),
+                2 => self.operator(descriptor),
+                3 => self.sequence(descriptor),
+                _ => Err(Error::UnknownDescriptor(
+                    descriptor.f,
+                    descriptor.x,
+                    descriptor.y,
+                )),
+            });
+            result.map_err(|e| self.locate(e, descriptor))?;
+        }
+        Ok(())
+    }
+
+    fn sequence(&mut self, descriptor: &Descriptor) -> Result<(), Error> {
+        let descriptors = expand_sequence(self.tables, descriptor)?;
+        self.element_start = None;
+        self.nested(descriptor.to_string(), |w| w.walk(&descriptors))
+    }
+
+    /// Replicate the descriptors following `descriptors[*i - 1]`
+    fn replicate(
+        &mut self,
+        descriptor: &Descriptor,
+        descriptors: &[Descriptor],
+        i: &mut usize,
+    ) -> Result<(), Error> {
+        let incomplete = || Error::IncompleteReplication(descriptor.x, descriptor.y);
+        let count = if descriptor.y == 0 {
+            let factor = descriptors.get(*i).ok_or_else(incomplete)?;
+            *i += 1;
+            let value = self.element(factor).map_err(|e| self.locate(e, factor))?;
+            match value {
+                Value::Integer(n) if n >= 0 => n as usize,
+                _ => return Err(incomplete()),
+            }
+        } else {
+            usize::from(descriptor.y)
+        };
+        if count > self.max_replication {
+            return Err(Error::ReplicationLimit(count, self.max_replication));
+        }
+        let n = usize::from(descriptor.x);
+        let group = descriptors.get(*i..*i + n).ok_or_else(incomplete)?;
+        *i += n;
+        for k in 0..count {
+            self.nested(format!("{}[{}]", descriptor, k), |w| w.walk(group))?;
+        }
+        Ok(())
+    }
+
+    /// Walk the descriptors, with their plan when there is one
+    fn walk_planned(
+        &mut self,
+        descriptors: &[Descriptor],
+        plan: Option<&DecodePlan>,
+    ) -> Result<(), Error> {
+        match plan {
+            Some(plan) => self.run(plan.steps()),
+            None => self.walk(descriptors),
+        }
+    }
+
+    /// Walk the steps of a decode plan, like `walk` does the descriptors
+    /// the plan was compiled from
+    pub(crate) fn run(&mut self, steps: &[Step]) -> Result<(), Error> {
+        for step in steps {
+            let result = match self.expand().and_then(|_| self.skip_step(step)) {
+                Ok(true) => Ok(()),
+                Ok(false) => self.run_step(step),
+                Err(e) => Err(e),
+            };
+            result.map_err(|e| self.locate(e, step.descriptor()))?;
+        }
+        Ok(())
+    }
+
+    /// Skip a sequence or replication of a known width whose values are
+    /// all unwanted
+    fn skip_step(&mut self, step: &Step) -> Result<bool, Error> {
+        if matches!(step, Step::Value { .. }) || step.any_value(&|d| self.visitor.wants(d)) {
+            return Ok(false);
+        }
+        match step.bit_width() {
+            Some(width) => {
+                self.element_start = self.visitor.bit_offset();
+                self.visitor.skip(width)
+            }
+            None => Ok(false),
+        }
+    }
+
+    fn run_step(&mut self, step: &Step) -> Result<(), Error> {
+        match step {
+            Step::Value { descriptor, spec } => {
+                if descriptor.f == 0 {
+                    self.element_start = self.visitor.bit_offset();
+                }
+                self.visitor.visit(descriptor, spec).map(|_| ())
+            }
+            Step::Sequence { descriptor, steps } => {
+                self.element_start = None;
+                self.nested(descriptor.to_string(), |w| w.run(steps))
+            }
+            Step::Replication {
+                descriptor,
+                count,
+                first,
+                rest,
+            } => self.run_replication(descriptor, count, first, rest.as_deref()),
+        }
+    }
+
+    fn run_replication(
+        &mut self,
+        descriptor: &Descriptor,
+        count: &Count,
+        first: &[Step],
+        rest: Option<&[Step]>,
+    ) -> Result<(), Error> {
+        let count = match count {
+            Count::Fixed(n) => *n,
+            Count::Delayed(factor, spec) => {
+                self.element_start = self.visitor.bit_offset();
+                let value = self
+                    .visitor
+                    .visit(factor, spec)
+                    .map_err(|e| self.locate(e, factor))?;
+                match value {
+                    Value::Integer(n) if n >= 0 => n as usize,
+                    _ => return Err(Error::IncompleteReplication(descriptor.x, descriptor.y)),
+                }
+            }
+        };
+        if count > self.max_replication {
+            return Err(Error::ReplicationLimit(count, self.max_replication));
+        }
+        for k in 0..count {
+            let steps = if k == 0 { first } else { rest.unwrap_or(first) };
+            self.nested(format!("{}[{}]", descriptor, k), |w| w.run(steps))?;
+        }
+        Ok(())
+    }
+
+    fn element(&mut self, descriptor: &Descriptor) -> Result<Value, Error> {
+        let spec = self.operators.element(self.tables, descriptor)?;
+        self.element_start = self.visitor.bit_offset();
+        self.visitor.visit(descriptor, &spec)
+    }
+
+    fn operator(&mut self, descriptor: &Descriptor) -> Result<(), Error> {
+        if let Some(spec) = self.operators.operate(descriptor)? {
+            self.visitor.visit(descriptor, &spec)?;
+        }
+        Ok(())
+    }
+}
+
+fn read_string(reader: &mut BufferReader, width: usize) -> Result<Option<String>, Error> {
+    let bytes = reader.consume(width)?;
+    if bytes.iter().all(|b| *b == 0xff) {
+        return Ok(None);
+    }
+    let text: String = bytes.iter().map(|b| char::from(*b)).collect();
+    Ok(Some(text.trim_end_matches(&[' ', '\0'][..]).to_string()))
+}
+
+/// Whether a value is needed, because it is selected or may be the count of
+/// a delayed replication
+fn wanted(options: &DecodeOptions, descriptor: &Descriptor) -> bool {
+    (descriptor.f == 0 && descriptor.x == 31) || options.selects(descriptor)
+}
+
+/// Decodes the values of an uncompressed data section, one subset at a time
+pub(crate) struct Decoder<'a> {
+    reader: BufferReader<'a>,
+    fields: Vec<Field>,
+    options: &'a DecodeOptions,
+}
+
+impl<'a> Visitor for Decoder<'a> {
+    fn visit(&mut self, descriptor: &Descriptor, spec: &Spec) -> Result<Value, Error> {
+        if !self.wants(descriptor) {
+            self.reader.skip(spec.width)?;
+            return Ok(Value::Missing);
+        }
+        let value = match spec.kind {
+            Kind::String => match read_string(&mut self.reader, spec.width)? {
+                Some(text) => Value::String(text),
+                None => Value::Missing,
+            },
+            _ => spec.decode(self.reader.read(spec.width)?),
+        };
+        if self.options.selects(descriptor) {
+            self.fields.push(Field {
+                descriptor: *descriptor,
+                value: value.clone(),
+            });
+        }
+        Ok(value)
+    }
+
+    fn bit_offset(&self) -> Option<usize> {
+        Some(self.reader.position())
+    }
+
+    fn wants(&self, descriptor: &Descriptor) -> bool {
+        wanted(self.options, descriptor)
+    }
+
+    fn skip(&mut self, width: usize) -> Result<bool, Error> {
+        self.reader.skip(width)?;
+        Ok(true)
+    }
+}
+
+/// Decodes the values of a compressed data section, all subsets at once
+pub(crate) struct CompressedDecoder<'a> {
+    reader: BufferReader<'a>,
+    subsets: Vec<Vec<Field>>,
+    options: &'a DecodeOptions,
+}
+
+impl<'a> Visitor for CompressedDecoder<'a> {
+    fn visit(&mut self, descriptor: &Descriptor, spec: &Spec) -> Result<Value, Error> {
+        let n = self.subsets.len();
+        if !self.wants(descriptor) {
+            // Reference value, then the increments of every subset
+            self.reader.skip(spec.width)?;
+            let increment = self.reader.read(6)? as usize;
+            let increment = match spec.kind {
+                Kind::String => 8 * increment,
+                _ => increment,
+            };
+            self.reader.skip(n * increment)?;
+            return Ok(Value::Missing);
+        }
+        let values: Vec<Value> = match spec.kind {
+            Kind::String => {
+                let reference = read_string(&mut self.reader, spec.width)?;
+                let increment = self.reader.read(6)? as usize;
+                if increment == 0 {
+                    let value = reference.map_or(Value::Missing, Value::String);
+                    vec![value; n]
+                } else {
+                    (0..n)
+                        .map(|_| {
+                            read_string(&mut self.reader, 8 * increment)
+                                .map(|v| v.map_or(Value::Missing, Value::String))
+                        })
+                        .collect::<Result<_, _>>()?
+                }
+            }
+            _ => {
+                let reference = self.reader.read(spec.width)?;
+                let increment = self.reader.read(6)? as usize;
+                if increment == 0 {
+                    vec![spec.decode(reference); n]
+                } else {
+                    let missing = (1u64 << increment) - 1;
+                    (0..n)
+                        .map(|_| {
+                            self.reader.read(increment).map(|v| {
+                                if spec.can_be_missing && v == missing {
+                                    Value::Missing
+                                } else {
+                                    spec.decode(reference.saturating_add(v))
+                                }
+                            })
+                        })
+                        .collect::<Result<_, _>>()?
+                }
+            }
+        };
+        if self.options.selects(descriptor) {
+            for (subset, value) in self.subsets.iter_mut().zip(values.iter()) {
+                subset.push(Field {
+                    descriptor: *descriptor,
+                    value: value.clone(),
+                });
+            }
+        }
+        // Replication factors must be the same for all subsets
+        let first = values.first().cloned().unwrap_or(Value::Missing);
+        if values.iter().any(|v| v != &first) && descriptor.f == 0 && descriptor.x == 31 {
+            return Err(Error::InconsistentCompression(descriptor.x, descriptor.y));
+        }
+        Ok(first)
+    }
+
+    fn bit_offset(&self) -> Option<usize> {
+        Some(self.reader.position())
+    }
+
+    fn wants(&self, descriptor: &Descriptor) -> bool {
+        wanted(self.options, descriptor)
+    }
+}
+
+/// Decode all the subsets of a data section
+///
+/// The plan, when given, must be compiled from `descriptors` and `tables`.
+pub(crate) fn decode_subsets(
+    data: &[u8],
+    descriptors: &[Descriptor],
+    n_subsets: usize,
+    is_compressed: bool,
+    tables: &dyn TableProvider,
+    plan: Option<&DecodePlan>,
+    options: &DecodeOptions,
+) -> Result<Vec<Subset>, Error> {
+    match decode_subsets_partial(
+        data,
+        descriptors,
+        n_subsets,
+        is_compressed,
+        tables,
+        plan,
+        options,
+    ) {
+        (_, Some(e)) if !options.truncates(&e) => Err(e),
+        (subsets, _) => Ok(subsets),
+    }
+}
+
+/// Decode subsets until the first error
+///
+/// Subsets decoded before the error are returned with it. When truncating
+/// on unknown descriptors, the values before the unknown descriptor are
+/// kept as well.
+pub(crate) fn decode_subsets_partial(
+    data: &[u8],
+    descriptors: &[Descriptor],
+    n_subsets: usize,
+    is_compressed: bool,
+    tables: &dyn TableProvider,
+    plan: Option<&DecodePlan>,
+    options: &DecodeOptions,
+) -> (Vec<Subset>, Option<Error>) {
+    let subset = |mut fields: Vec<Field>| {
+        if options.missing_values == MissingValues::Skip {
+            fields.retain(|f| f.value != Value::Missing);
+        }
+        convert_units(&mut fields, tables, options);
+        Subset { fields }
+    };
+
+    if is_compressed {
+        let mut decoder = CompressedDecoder {
+            reader: BufferReader::new(data),
+            subsets: vec![vec![]; n_subsets],
+            options,
+        };
+        let mut walker = Walker::with_options(&mut decoder, tables, options);
+        let error = match walker.walk_planned(descriptors, plan) {
+            Ok(()) => None,
+            Err(e) if options.truncates(&e) => Some(e),
+            // All subsets fail at the same place
+            Err(e) => return (vec![], Some(e.locate(|l| l.set_subset(0)))),
+        };
+        (decoder.subsets.into_iter().map(subset).collect(), error)
+    } else {
+        let mut decoder = Decoder {
+            reader: BufferReader::new(data),
+            fields: vec![],
+            options,
+        };
+        let mut subsets = Vec::with_capacity(n_subsets);
+        // The limit of expanded descriptors is for all subsets together
+        let mut expanded = 0;
+        for i in 0..n_subsets {
+            let mut walker = Walker::with_options(&mut decoder, tables, options);
+            walker.expanded = expanded;
+            let result = walker.walk_planned(descriptors, plan);
+            expanded = walker.expanded;
+            match result {
+                Ok(()) => subsets.push(subset(std::mem::take(&mut decoder.fields))),
+                Err(e) => {
+                    if options.truncates(&e) {
+                        subsets.push(subset(std::mem::take(&mut decoder.fields)));
+                    }
+                    return (subsets, Some(e.locate(|l| l.set_subset(i))));
+                }
+            }
+        }
+        (subsets, None)
+    }
+}
+
+/// Applies the output units of the options to the fields
+fn convert_units(fields: &mut [Field], tables: &dyn TableProvider, options: &DecodeOptions) {
+    for field in fields {
+        let Descriptor { f: 0, x, y } = field.descriptor else {
+            continue;
+        };
+        let Some(element) = tables.element(x, y) else {
+            continue;
+        };
+        if let Some(to) = options.output_unit_for(&element.unit) {
+            if let Ok(value) = field.value.convert(&element.unit, to) {
+                field.value = value;
+            }
+        }
+    }
+}
+
+/// Accumulates values as a sequence of bits
+#[derive(Debug, Default)]
+struct BitWriter {
+    buffer: Vec<u8>,
+    n_bits: usize,
+}
+
+impl BitWriter {
+    fn write(&mut self, value: u64, width: usize) {
+        for i in (0..width).rev() {
+            let bit = if i < 64 { (value >> i) & 1 } else { 0 };
+            let offset = self.n_bits % 8;
+            if offset == 0 {
+                self.buffer.push(0);
+            }
+            if bit == 1 {
+                let last = self.buffer.len() - 1;
+                self.buffer[last] |= 0x80 >> offset;
+            }
+            self.n_bits += 1;
+        }
+    }
+
+    fn into_bytes(self) -> Vec<u8> {
+        self.buffer
+    }
+}
+
+/// Encodes values for an uncompressed data section
+struct Encoder<'a> {
+    writer: BitWriter,
+    values: std::slice::Iter<'a, Value>,
+}
+
+impl<'a> Visitor for Encoder<'a> {
+    fn visit(&mut self, descriptor: &Descriptor, spec: &Spec) -> Result<Value, Error> {
+        let value = self.values.next().ok_or(Error::MissingValue(
+            descriptor.f,
+            descriptor.x,
+            descriptor.y,
+        ))?;
+        match (spec.kind, value) {
+            (Kind::String, Value::String(text)) => {
+                let n_chars = spec.width / 8;
+                if text.len() > n_chars || !text.is_ascii() {
+                    return Err(Error::InvalidValue(
+                        descriptor.f,
+                        descriptor.x,
+                        descriptor.y,
+                        text.clone(),
+                    ));
+                }
+                for b in text.bytes().chain(std::iter::repeat(b' ')).take(n_chars) {
+                    self.writer.write(u64::from(b), 8);
+                }
+            }
+            (Kind::String, Value::Missing) => {
+                for _ in 0..(spec.width / 8) {
+                    self.writer.write(0xff, 8);
+                }
+            }
+            _ => {
+                let raw = spec.encode(descriptor, value)?;
+                self.writer.write(raw, spec.width);
+            }
+        }
+        Ok(value.clone())
+    }
+}
+
+/// Encode the values of all subsets into an uncompressed data section
+pub(crate) fn encode_subsets(
+    descriptors: &[Descriptor],
+    subsets: &[Vec<Value>],
+    tables: &dyn TableProvider,
+) -> Result<Vec<u8>, Error> {
+    let mut encoder = Encoder {
+        writer: BitWriter::default(),
+        values: [].iter(),
+    };
+    for (i, values) in subsets.iter().enumerate() {
+        encoder.values = values.iter();
+        Walker::new(&mut encoder, tables)
+            .walk(descriptors)
+            .map_err(|e| e.locate(|l| l.set_subset(i)))?;
+        let left = encoder.values.len();
+        if left > 0 {
+            return Err(Error::TooManyValues(left).locate(|l| l.set_subset(i)));
+        }
+    }
+    Ok(encoder.writer.into_bytes())
+}
+
+#[cfg(all(test, feature = "wmo-35"))]
+mod tests {
+    use super::{decode_subsets, encode_subsets, BitWriter, Value};
+    use crate::plan::DecodePlan;
+    use crate::tables::Tables;
+    use crate::{BUFRUnit, DecodeOptions, Descriptor, Error, MissingValues, UnknownDescriptors};
+
+    #[test]
+    fn bit_writer() {
+        let mut writer = BitWriter::default();
+        writer.write(0b1111, 4);
+        writer.write(0, 8);
+        writer.write(0b1111, 4);
+        writer.write(1, 1);
+        assert_eq!(
+            writer.into_bytes(),
+            vec![0b1111_0000, 0b0000_1111, 0b1000_0000]
+        );
+    }
+
+    #[test]
+    // 3-01-011: Year, month, day
+    fn roundtrip_date() -> Result<(), Box<dyn std::error::Error>> {
+        let tables = Tables::embedded();
+        let descriptors = vec![Descriptor::new(3, 1, 11)];
+        let values = vec![Value::Integer(2020), Value::Integer(10), Value::Integer(6)];
+        let data = encode_subsets(&descriptors, std::slice::from_ref(&values), &tables)?;
+        // 12 + 4 + 6 bits
+        assert_eq!(data.len(), 3);
+
+        let subsets = decode_subsets(
+            &data,
+            &descriptors,
+            1,
+            false,
+            &tables,
+            None,
+            &DecodeOptions::default(),
+        )?;
+        assert_eq!(subsets[0].values().cloned().collect::<Vec<_>>(), values);
+        Ok(())
+    }
+
+    #[test]
+    // 0-05-001 is a latitude in degrees
+    fn output_units() -> Result<(), Box<dyn std::error::Error>> {
+        let tables = Tables::embedded();
+        let descriptors = vec![Descriptor::new(0, 5, 1)];
+        let data = encode_subsets(&descriptors, &[vec![Value::Float(90.0)]], &tables)?;
+        let options = DecodeOptions::default().output_unit(BUFRUnit::Degree, BUFRUnit::Radian)?;
+        let subsets = decode_subsets(&data, &descriptors, 1, false, &tables, None, &options)?;
+        match subsets[0].fields()[0].value() {
+            Value::Float(v) => assert!((v - std::f64::consts::FRAC_PI_2).abs() < 1e-9),
+            v => panic!("{:?}", v),
+        }
+
+        // Dimensionless in SI, but not interchangeable
+        for (from, to) in [
+            (BUFRUnit::Degree, BUFRUnit::Numeric),
+            (BUFRUnit::Percent, BUFRUnit::KilogramPerKilogram),
+        ] {
+            assert!(matches!(
+                DecodeOptions::default().output_unit(from.clone(), to.clone()),
+                Err(Error::IncompatibleUnits(..))
+            ));
+            assert!(matches!(
+                Value::Float(50.0).convert(&from, &to),
+                Err(Error::IncompatibleUnits(..))
+            ));
+        }
+        Ok(())
+    }
+
+    #[test]
+    // 0-05-001 has scale 5 and reference -9000000
+    fn roundtrip_scaled() -> Result<(), Box<dyn std::error::Error>> {
+        let tables = Tables::embedded();
+        let descriptors = vec![Descriptor::new(0, 5, 1), Descriptor::new(0, 5, 1)];
+        let values = vec![Value::Float(-36.803), Value::Missing];
+        let data = encode_subsets(&descriptors, std::slice::from_ref(&values), &tables)?;
+
+        let subsets = decode_subsets(
+            &data,
+            &descriptors,
+            1,
+            false,
+            &tables,
+            None,
+            &DecodeOptions::default(),
+        )?;
+        assert_eq!(subsets[0].values().cloned().collect::<Vec<_>>(), values);
+        Ok(())
+    }
+
+    #[test]
+    // 1-01-000, 0-31-001, 0-01-019: delayed replication of a station name
+    fn roundtrip_delayed_replication() -> Result<(), Box<dyn std::error::Error>> {
+        let tables = Tables::embedded();
+        let descriptors = vec![
+            Descriptor::new(1, 1, 0),
+            Descriptor::new(0, 31, 1),
+            Descriptor::new(0, 1, 19),
+        ];
+        let values = vec![
+            Value::Integer(2),
+            Value::String("Monterey Bay".to_string()),
+            Value::String("Cabot".to_string()),
+        ];
+        let data = encode_subsets(
+            &descriptors,
+            &[values.clone(), vec![Value::Integer(0)]],
+            &tables,
+        )?;
+        assert_eq!(data.len(), 1 + 2 * 32 + 1);
+
+        let subsets = decode_subsets(
+            &data,
+            &descriptors,
+            2,
+            false,
+            &tables,
+            None,
+            &DecodeOptions::default(),
+        )?;
+        assert_eq!(subsets[0].values().cloned().collect::<Vec<_>>(), values);
+        assert_eq!(subsets[1].len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    // The second station name is cut in the middle
+    fn descriptor_path() -> Result<(), Box<dyn std::error::Error>> {
+        let tables = Tables::embedded();
+        let descriptors = vec![
+            Descriptor::new(1, 1, 0),
+            Descriptor::new(0, 31, 1),
+            Descriptor::new(0, 1, 19),
+        ];
+        let values = vec![
+            Value::Integer(2),
+            Value::String("Monterey Bay".to_string()),
+            Value::String("Cabot".to_string()),
+        ];
+        let data = encode_subsets(&descriptors, &[vec![Value::Integer(0)], values], &tables)?;
+
+        let err = decode_subsets(
+            &data[..40],
+            &descriptors,
+            2,
+            false,
+            &tables,
+            None,
+            &DecodeOptions::default(),
+        )
+        .unwrap_err();
+        assert!(matches!(err.kind(), Error::DataTooShort));
+        let location = err.location().unwrap();
+        assert_eq!(location.subset(), Some(1));
+        assert_eq!(location.bit_offset(), Some(8 + 8 + 32 * 8));
+        assert_eq!(location.descriptor_path(), &["1-01-000[1]", "0-01-019"]);
+        Ok(())
+    }
+
+    #[test]
+    fn limits() -> Result<(), Box<dyn std::error::Error>> {
+        let tables = Tables::embedded();
+        let descriptors = vec![
+            Descriptor::new(1, 1, 0),
+            Descriptor::new(0, 31, 1),
+            Descriptor::new(0, 4, 1),
+        ];
+        let mut values = vec![Value::Integer(3)];
+        values.extend(vec![Value::Integer(2020); 3]);
+        let data = encode_subsets(&descriptors, &[values], &tables)?;
+
+        let options = DecodeOptions::default().max_replication(2);
+        let err =
+            decode_subsets(&data, &descriptors, 1, false, &tables, None, &options).unwrap_err();
+        assert!(matches!(err.kind(), Error::ReplicationLimit(3, 2)));
+
+        // 3-01-011 inside the replication
+        let descriptors = vec![Descriptor::new(1, 1, 2), Descriptor::new(3, 1, 11)];
+        let data = encode_subsets(&descriptors, &[vec![Value::Missing; 6]], &tables)?;
+        let options = DecodeOptions::default().max_depth(1);
+        let err =
+            decode_subsets(&data, &descriptors, 1, false, &tables, None, &options).unwrap_err();
+        assert!(matches!(err.kind(), Error::DepthLimit(1)));
+        let options = DecodeOptions::default().max_depth(2);
+        assert!(decode_subsets(&data, &descriptors, 1, false, &tables, None, &options).is_ok());
+
+        // 13 descriptors for each subset, counting the repetitions
+        let subsets = vec![vec![Value::Missing; 6]; 2];
+        let data = encode_subsets(&descriptors, &subsets, &tables)?;
+        let options = DecodeOptions::default().max_expanded_descriptors(13);
+        assert!(decode_subsets(&data, &descriptors, 1, false, &tables, None, &options).is_ok());
+        let err =
+            decode_subsets(&data, &descriptors, 2, false, &tables, None, &options).unwrap_err();
+        assert!(matches!(err.kind(), Error::ExpansionLimit(13)));
+        let options = DecodeOptions::default().max_expanded_descriptors(26);
+        assert!(decode_subsets(&data, &descriptors, 2, false, &tables, None, &options).is_ok());
+        Ok(())
+    }
+
+    #[test]
+    fn policies() -> Result<(), Box<dyn std::error::Error>> {
+        let tables = Tables::embedded();
+        let descriptors = vec![Descriptor::new(0, 4, 1), Descriptor::new(0, 4, 2)];
+        let data = encode_subsets(
+            &descriptors,
+            &[
+                vec![Value::Integer(2020), Value::Missing],
+                vec![Value::Missing, Value::Integer(5)],
+            ],
+            &tables,
+        )?;
+        let options = DecodeOptions::default().missing_values(MissingValues::Skip);
+        let subsets = decode_subsets(&data, &descriptors, 2, false, &tables, None, &options)?;
+        assert_eq!(
+            subsets[0].values().collect::<Vec<_>>(),
+            [&Value::Integer(2020)]
+        );
+        assert_eq!(
+            subsets[1].values().collect::<Vec<_>>(),
+            [&Value::Integer(5)]
+        );
+
+        // Class 63 isn't in the tables
+        let unknown = vec![Descriptor::new(0, 4, 1), Descriptor::new(0, 63, 255)];
+        let options = DecodeOptions::default();
+        let err = decode_subsets(&data, &unknown, 2, false, &tables, None, &options).unwrap_err();
+        assert!(matches!(err.kind(), Error::UnknownDescriptor(0, 63, 255)));
+        let options = options.unknown_descriptors(UnknownDescriptors::Truncate);
+        let subsets = decode_subsets(&data, &unknown, 2, false, &tables, None, &options)?;
+        assert_eq!(subsets.len(), 1);
+        assert_eq!(
+            subsets[0].values().collect::<Vec<_>>(),
+            [&Value::Integer(2020)]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_values() {
+        let tables = Tables::embedded();
+        let descriptors = vec![Descriptor::new(0, 4, 2)];
+        // Month has 4 bits, and 15 is reserved for missing
+        assert!(encode_subsets(&descriptors, &[vec![Value::Integer(15)]], &tables).is_err());
+        assert!(
+            encode_subsets(&descriptors, &[vec![Value::String("May".into())]], &tables).is_err()
+        );
+        assert!(encode_subsets(&descriptors, &[vec![]], &tables).is_err());
+        assert!(encode_subsets(
+            &descriptors,
+            &[vec![Value::Integer(5), Value::Integer(6)]],
+            &tables
+        )
+        .is_err());
+
+        // Latitude isn't a code, so floats are accepted, but only finite ones
+        let descriptors = vec![Descriptor::new(0, 5, 1)];
+        for value in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+            let err = encode_subsets(&descriptors, &[vec![Value::Float(value)]], &tables);
+            assert!(matches!(
+                err.unwrap_err().kind(),
+                Error::InvalidValue(0, 5, 1, _)
+            ));
+        }
+    }
+
+    #[test]
+    // Two subsets of 0-04-001 (year): 2020 and 2022
+    fn decode_compressed() -> Result<(), Box<dyn std::error::Error>> {
+        let tables = Tables::embedded();
+        let mut writer = BitWriter::default();
+        writer.write(2020, 12);
+        writer.write(2, 6);
+        writer.write(0, 2);
+        writer.write(2, 2);
+        let data = writer.into_bytes();
+
+        let descriptors = vec![Descriptor::new(0, 4, 1)];
+        let subsets = decode_subsets(
+            &data,
+            &descriptors,
+            2,
+            true,
+            &tables,
+            None,
+            &DecodeOptions::default(),
+        )?;
+        assert_eq!(subsets[0].fields()[0].value(), &Value::Integer(2020));
+        assert_eq!(subsets[1].fields()[0].value(), &Value::Integer(2022));
+        Ok(())
+    }
+
+    #[test]
+    // Some values and their replications are skipped
+    fn selection() -> Result<(), Box<dyn std::error::Error>> {
+        let tables = Tables::embedded();
+        let d = |code: &str| code.parse::<Descriptor>().unwrap();
+        let descriptors = vec![
+            d("201130"),
+            d("004001"),
+            d("201000"),
+            d("301011"),
+            d("102000"),
+            d("031001"),
+            d("005001"),
+            d("006001"),
+            d("102002"),
+            d("001015"),
+            d("022043"),
+        ];
+        let values = vec![
+            Value::Integer(2020),
+            Value::Integer(2020),
+            Value::Integer(10),
+            Value::Integer(6),
+            Value::Integer(2),
+            Value::Float(46.5),
+            Value::Float(6.5),
+            Value::Float(47.25),
+            Value::Float(7.0),
+            Value::String("Cabot".to_string()),
+            Value::Float(290.15),
+            Value::String("Melonhead".to_string()),
+            Value::Float(291.0),
+        ];
+        let data = encode_subsets(&descriptors, &[values.clone(), values], &tables)?;
+        let plan = DecodePlan::compile(&descriptors, &tables)?;
+
+        let options = DecodeOptions::default();
+        let all = decode_subsets(&data, &descriptors, 2, false, &tables, None, &options)?;
+        let selected = [d("005001"), d("006001"), d("022043")];
+        let expected: Vec<_> = all[1]
+            .fields()
+            .iter()
+            .filter(|f| selected.contains(f.descriptor()))
+            .cloned()
+            .collect();
+        assert_eq!(expected.len(), 6);
+
+        let options = DecodeOptions::default().select(selected.iter().copied());
+        for plan in [None, Some(&plan)] {
+            let subsets = decode_subsets(&data, &descriptors, 2, false, &tables, plan, &options)?;
+            assert_eq!(subsets.len(), 2);
+            assert_eq!(subsets[1].fields(), &expected[..]);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn selection_compressed() -> Result<(), Box<dyn std::error::Error>> {
+        let tables = Tables::embedded();
+        let mut writer = BitWriter::default();
+        // 2 characters, different in each subset
+        writer.write(u64::from(u16::from_be_bytes(*b"AB")), 16);
+        writer.write(2, 6);
+        writer.write(u64::from(u16::from_be_bytes(*b"CD")), 16);
+        writer.write(u64::from(u16::from_be_bytes(*b"EF")), 16);
+        // Years 2020 and 2022, then month 10 in both
+        writer.write(2020, 12);
+        writer.write(2, 6);
+        writer.write(0, 2);
+        writer.write(2, 2);
+        writer.write(10, 4);
+        writer.write(0, 6);
+        let data = writer.into_bytes();
+
+        let descriptors = vec![
+            Descriptor::new(2, 5, 2),
+            Descriptor::new(0, 4, 1),
+            Descriptor::new(0, 4, 2),
+        ];
+        let options = DecodeOptions::default().select(vec![Descriptor::new(0, 4, 2)]);
+        let subsets = decode_subsets(&data, &descriptors, 2, true, &tables, None, &options)?;
+        for subset in &subsets {
+            assert_eq!(
+                subset.values().collect::<Vec<_>>(),
+                vec![&Value::Integer(10)]
+            );
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,269 @@
+//! Table B and Table D in the format of ecCodes
+//!
+//! ecCodes keeps each set of tables in a directory under
+//! `definitions/bufr/tables/`, with Table B in `element.table`:
+//!
+//! ```text
+//! #code|abbreviation|type|name|unit|scale|reference|width|crex_unit|crex_scale|crex_width
+//! 001001|blockNumber|long|WMO BLOCK NUMBER|Numeric|0|0|7|Numeric|0|2
+//! ```
+//!
+//! and Table D in `sequence.def`:
+//!
+//! ```text
+//! "301001" = [  001001, 001002 ]
+//! ```
+//!
+//! The abbreviations are only used by ecCodes, so they are dropped on
+//! import and made up from the names on export.
+
+use std::io::{BufRead, BufReader, Read, Write};
+
+use crate::tables::{parse_descriptor, parse_fxy, TableF0, TableF3, F3};
+use crate::{BUFRUnit, Descriptor, ElementDescriptor, Error};
+
+const ELEMENT_HEADER: &str =
+    "#code|abbreviation|type|name|unit|scale|reference|width|crex_unit|crex_scale|crex_width";
+
+/// Parses an ecCodes `element.table`
+pub(crate) fn parse_element_table<R: Read>(reader: R) -> Result<TableF0, Error> {
+    let mut table = TableF0::default();
+
+    for line in BufReader::new(reader).lines() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let invalid = || Error::InvalidTable(format!("Invalid element.table line: {}", line));
+        let fields: Vec<_> = line.split('|').map(str::trim).collect();
+        if fields.len() < 8 {
+            return Err(invalid());
+        }
+        let (x, y) = match parse_fxy(fields[0])? {
+            (0, x, y) => (x, y),
+            _ => return Err(invalid()),
+        };
+        let element = ElementDescriptor {
+            name: fields[3].to_string().into(),
+            unit: unit(fields[2], fields[4]),
+            scale: fields[5].parse().map_err(|_| invalid())?,
+            reference_value: fields[6].parse().map_err(|_| invalid())?,
+            data_width: fields[7].parse().map_err(|_| invalid())?,
+        };
+        table.insert((x, y), element);
+    }
+    Ok(table)
+}
+
+/// Unit of an element, from its ecCodes type and unit
+fn unit(kind: &str, unit: &str) -> BUFRUnit {
+    let unit = BUFRUnit::from(unit);
+    match kind {
+        "table" if !unit.is_code() => BUFRUnit::CodeTable,
+        "flag" => BUFRUnit::FlagTable,
+        "string" => BUFRUnit::CCITTIA5,
+        _ => unit,
+    }
+}
+
+/// Parses an ecCodes `sequence.def`
+///
+/// The descriptors of a sequence may span several lines.
+pub(crate) fn parse_sequence_def<R: Read>(mut reader: R) -> Result<TableF3, Error> {
+    let mut table = TableF3::default();
+
+    let mut text = String::new();
+    reader.read_to_string(&mut text)?;
+    for entry in text.split(']') {
+        if entry.trim().is_empty() {
+            continue;
+        }
+        let invalid = || Error::InvalidTable(format!("Invalid sequence.def entry: {}", entry));
+        let (code, list) = entry.split_once('=').ok_or_else(invalid)?;
+        let (x, y) = match parse_fxy(code.trim().trim_matches('"'))? {
+            (3, x, y) => (x, y),
+            _ => return Err(invalid()),
+        };
+        let list = list.trim().strip_prefix('[').ok_or_else(invalid)?;
+        let descriptors = list
+            .split(',')
+            .map(str::trim)
+            .filter(|code| !code.is_empty())
+            .map(parse_descriptor)
+            .collect::<Result<_, _>>()?;
+        table.insert(
+            (x, y),
+            F3 {
+                descriptors,
+                title: None,
+            },
+        );
+    }
+    Ok(table)
+}
+
+/// Writes Table B as an ecCodes `element.table`, sorted by descriptor
+pub(crate) fn write_element_table<W: Write>(table: &TableF0, mut writer: W) -> Result<(), Error> {
+    let mut keys: Vec<_> = table.keys().collect();
+    keys.sort();
+
+    writeln!(writer, "{}", ELEMENT_HEADER)?;
+    for &(x, y) in keys {
+        let e = &table[&(x, y)];
+        let (kind, unit) = match e.unit {
+            BUFRUnit::FlagTable => ("flag", "FLAG TABLE".to_string()),
+            BUFRUnit::CodeTable => ("table", "CODE TABLE".to_string()),
+            ref unit if unit.is_code() => ("table", unit.to_string()),
+            BUFRUnit::CCITTIA5 => ("string", "CCITT IA5".to_string()),
+            ref unit if e.scale > 0 => ("double", unit.to_string()),
+            ref unit => ("long", unit.to_string()),
+        };
+        // Characters needed for the largest value, or one per byte of text
+        let crex_width = match e.unit {
+            BUFRUnit::CCITTIA5 => e.data_width / 8,
+            _ => (f64::from(e.data_width) * std::f64::consts::LOG10_2).ceil() as u16,
+        };
+        writeln!(
+            writer,
+            "0{:02}{:03}|{}|{}|{}|{}|{}|{}|{}|{}|{}|{}",
+            x,
+            y,
+            abbreviation(&e.name),
+            kind,
+            e.name,
+            unit,
+            e.scale,
+            e.reference_value,
+            e.data_width,
+            unit,
+            e.scale,
+            crex_width
+        )?;
+    }
+    Ok(())
+}
+
+/// ecCodes key of an element, the camel case of its name
+fn abbreviation(name: &str) -> String {
+    let mut key = String::new();
+    for word in name
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|w| !w.is_empty())
+    {
+        let word = word.to_ascii_lowercase();
+        if key.is_empty() {
+            key.push_str(&word);
+        } else {
+            key.push_str(&word[..1].to_ascii_uppercase());
+            key.push_str(&word[1..]);
+        }
+    }
+    key
+}
+
+/// Writes Table D as an ecCodes `sequence.def`, sorted by descriptor
+pub(crate) fn write_sequence_def<W: Write>(table: &TableF3, mut writer: W) -> Result<(), Error> {
+    let mut keys: Vec<_> = table.keys().collect();
+    keys.sort();
+
+    for &(x, y) in keys {
+        let codes: Vec<_> = table[&(x, y)].iter().map(code).collect();
+        writeln!(writer, "\"3{:02}{:03}\" = [  {} ]", x, y, codes.join(", "))?;
+    }
+    Ok(())
+}
+
+fn code(d: &Descriptor) -> String {
+    format!("{}{:02}{:03}", d.f, d.x, d.y)
+}
+
+#[cfg(test)]
+mod test_eccodes {
+    use super::{
+        abbreviation, parse_element_table, parse_sequence_def, write_element_table,
+        write_sequence_def,
+    };
+    use crate::{BUFRUnit, Descriptor};
+
+    const ELEMENTS: &str = "#code|abbreviation|type|name|unit|scale|reference|width|crex_unit|crex_scale|crex_width
+001001|blockNumber|long|WMO BLOCK NUMBER|Numeric|0|0|7|Numeric|0|2
+001015|stationOrSiteName|string|STATION OR SITE NAME|CCITT IA5|0|0|160|Character|0|20
+002001|stationType|table|TYPE OF STATION|CODE TABLE|0|0|2|CODE TABLE|0|1
+002002|instrumentationForWindMeasurement|flag|TYPE OF INSTRUMENTATION FOR WIND MEASUREMENT|FLAG TABLE|0|0|4|FLAG TABLE|0|2
+005001|latitude|double|LATITUDE (HIGH ACCURACY)|deg|5|-9000000|25|deg|5|7
+";
+
+    const SEQUENCES: &str = "\"301001\" = [  001001, 001002 ]
+\"301004\" = [  001001, 001002,
+               001015, 002001 ]
+";
+
+    #[test]
+    fn elements() {
+        let table = parse_element_table(ELEMENTS.as_bytes()).unwrap();
+        assert_eq!(table.len(), 5);
+        let e = &table[&(1, 15)];
+        assert_eq!(e.name(), "STATION OR SITE NAME");
+        assert_eq!(e.unit(), &BUFRUnit::CCITTIA5);
+        assert_eq!(e.data_width(), 160);
+        assert_eq!(table[&(2, 1)].unit(), &BUFRUnit::CodeTable);
+        assert_eq!(table[&(2, 2)].unit(), &BUFRUnit::FlagTable);
+        let e = &table[&(5, 1)];
+        assert_eq!(e.unit(), &BUFRUnit::Degree);
+        assert_eq!(e.scale(), 5);
+        assert_eq!(e.reference_value(), -9000000);
+
+        assert!(parse_element_table("001001|blockNumber|long".as_bytes()).is_err());
+        assert!(parse_element_table("301001|a|long|A|Numeric|0|0|7".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn sequences() {
+        let table = parse_sequence_def(SEQUENCES.as_bytes()).unwrap();
+        assert_eq!(table.len(), 2);
+        let sequence: Vec<_> = table[&(1, 4)].iter().cloned().collect();
+        assert_eq!(
+            sequence,
+            vec![
+                Descriptor::new(0, 1, 1),
+                Descriptor::new(0, 1, 2),
+                Descriptor::new(0, 1, 15),
+                Descriptor::new(0, 2, 1),
+            ]
+        );
+
+        assert!(parse_sequence_def("\"301001\" [ 001001 ]".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn round_trip() {
+        let elements = parse_element_table(ELEMENTS.as_bytes()).unwrap();
+        let mut output = vec![];
+        write_element_table(&elements, &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("001015|stationOrSiteName|string|STATION OR SITE NAME|CCITT IA5|0|0|160|CCITT IA5|0|20\n"));
+        assert!(output.contains("005001|latitudeHighAccuracy|double|"));
+        assert_eq!(parse_element_table(output.as_bytes()).unwrap(), elements);
+
+        let sequences = parse_sequence_def(SEQUENCES.as_bytes()).unwrap();
+        let mut output = vec![];
+        write_sequence_def(&sequences, &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(
+            output,
+            "\"301001\" = [  001001, 001002 ]\n\"301004\" = [  001001, 001002, 001015, 002001 ]\n"
+        );
+        assert_eq!(parse_sequence_def(output.as_bytes()).unwrap(), sequences);
+    }
+
+    #[test]
+    fn abbreviations() {
+        assert_eq!(abbreviation("WMO BLOCK NUMBER"), "wmoBlockNumber");
+        assert_eq!(
+            abbreviation("Latitude (high accuracy)"),
+            "latitudeHighAccuracy"
+        );
+        assert_eq!(abbreviation(""), "");
+    }
+}
@@ -1,5 +1,87 @@
 //#![deny(missing_docs)]
 
+use std::fmt;
+
+use getset::{CopyGetters, Getters};
+
+/// Where in the input an error was found
+///
+/// Every part is optional, since how much is known depends on where the
+/// error was found. Offsets are in bytes from the start of the message.
+#[derive(Clone, CopyGetters, Getters, Debug, Default, PartialEq)]
+pub struct Location {
+    /// Index of the message in the file, starting at 0
+    #[getset(get_copy = "pub")]
+    message: Option<usize>,
+    /// Position of the message in the file
+    #[getset(get_copy = "pub")]
+    message_offset: Option<usize>,
+    /// Section of the message
+    #[getset(get_copy = "pub")]
+    section: Option<u8>,
+    /// Byte in the message
+    #[getset(get_copy = "pub")]
+    offset: Option<usize>,
+    /// Bit from the start of the data in section 4
+    #[getset(get_copy = "pub")]
+    bit_offset: Option<usize>,
+    /// Index of the subset, starting at 0
+    #[getset(get_copy = "pub")]
+    subset: Option<usize>,
+    /// Sequences, replications and the descriptor being processed
+    #[getset(get = "pub")]
+    descriptor_path: Vec<String>,
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut parts = vec![];
+        if let Some(message) = self.message {
+            parts.push(format!("message {}", message));
+        }
+        if let Some(offset) = self.message_offset {
+            parts.push(format!("starting at byte {} of the file", offset));
+        }
+        if let Some(section) = self.section {
+            parts.push(format!("section {}", section));
+        }
+        if let Some(offset) = self.offset {
+            parts.push(format!("byte {}", offset));
+        }
+        if let Some(bit) = self.bit_offset {
+            parts.push(format!("bit {} of the data", bit));
+        }
+        if let Some(subset) = self.subset {
+            parts.push(format!("subset {}", subset));
+        }
+        if !self.descriptor_path.is_empty() {
+            parts.push(self.descriptor_path.join(" > "));
+        }
+        write!(f, "{}", parts.join(", "))
+    }
+}
+
+impl Location {
+    pub(crate) fn set_message(&mut self, index: usize, offset: usize) {
+        self.message = Some(index);
+        self.message_offset = Some(offset);
+    }
+
+    pub(crate) fn set_section(&mut self, section: u8, offset: usize) {
+        self.section = Some(section);
+        self.offset = Some(offset);
+    }
+
+    pub(crate) fn set_subset(&mut self, subset: usize) {
+        self.subset = Some(subset);
+    }
+
+    pub(crate) fn set_descriptor_path(&mut self, path: Vec<String>, bit_offset: Option<usize>) {
+        self.descriptor_path = path;
+        self.bit_offset = bit_offset;
+    }
+}
+
 /// Possible errors when parsing BUFR messages
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
@@ -12,8 +94,8 @@ pub enum Error {
     MagicNumber,
 
     /// Wrong end section
-    #[error("BUFR message should end with 7777")]
-    EndSection,
+    #[error("BUFR message should end with 7777 at byte {offset}")]
+    EndSection { offset: usize },
 
     /// Message shorter than specified on section 0
     #[error("Message shorter than expected, is it truncated?")]
@@ -32,9 +114,200 @@ pub enum Error {
         buffer_size: usize,
     },
     */
-    #[error("Section 3 must be larger than 7 and an odd number, got {0}")]
-    InvalidSection3Length(usize),
+    /// Section shorter than its length, or than the message
+    #[error("Section {section} at byte {offset} is truncated")]
+    TruncatedSection { section: u8, offset: usize },
+
+    /// Section length not valid for that section
+    #[error("Section {section} at byte {offset} has an invalid length of {length}")]
+    InvalidSectionLength {
+        section: u8,
+        offset: usize,
+        length: usize,
+    },
+
+    /// Reserved byte not set to zero
+    #[error("Reserved byte {offset} in section {section} should be zero, got {value}")]
+    ReservedNotZero {
+        section: u8,
+        offset: usize,
+        value: u8,
+    },
+
+    /// Flags with reserved bits set, or an unknown combination
+    #[error("Invalid flags {value:#010b} at byte {offset} in section {section}")]
+    InvalidFlags {
+        section: u8,
+        offset: usize,
+        value: u8,
+    },
+
+    /// Fortran record markers that don't match the records
+    #[error("Invalid Fortran record marker at byte {0}")]
+    InvalidRecord(usize),
+
+    /// Conversion between units of different dimensions, or not physical units
+    #[error("Can't convert from {0} to {1}")]
+    IncompatibleUnits(String, String),
+
+    /// Not a descriptor in the form "FXXYYY" or "F-XX-YYY"
+    #[error("Invalid descriptor: {0}")]
+    InvalidDescriptor(String),
+
+    /// Malformed table
+    #[error("Invalid table entry: {0}")]
+    InvalidTable(String),
+
+    /// The tables of `BUFR_TABLES_PATH` couldn't be loaded
+    #[error("Can't load the tables of BUFR_TABLES_PATH: {0}")]
+    TablesPath(String),
+
+    /// No version of the master table is available
+    #[error("No tables for master table {0}")]
+    NoTables(u8),
+
+    /// The tables of another version than the one requested were used
+    #[error(
+        "No tables for version {requested} of master table {master_table}, using version {used}"
+    )]
+    TableVersion {
+        master_table: u8,
+        requested: u8,
+        used: u8,
+    },
+
+    /// Section 1 refers to local tables that aren't available
+    #[error("No local tables version {version} for centre {center}, sub-centre {sub_center}")]
+    NoLocalTables {
+        center: u16,
+        sub_center: u16,
+        version: u8,
+    },
+
+    /// Descriptor not available in the tables
+    #[error("Descriptor {0}-{1:02}-{2:03} not found in the tables")]
+    UnknownDescriptor(u8, u8, u8),
+
+    /// Descriptor used where an element (F=0) is expected
+    #[error("Descriptor {0}-{1:02}-{2:03} is not an element")]
+    NotAnElement(u8, u8, u8),
+
+    /// Operator descriptor (F=2) not supported
+    #[error("Operator 2-{0:02}-{1:03} is not supported")]
+    UnsupportedOperator(u8, u8),
+
+    /// Replication without the descriptors it applies to
+    #[error("Replication 1-{0:02}-{1:03} is incomplete")]
+    IncompleteReplication(u8, u8),
+
+    /// Replication repeated more times than allowed by the options
+    #[error("Replication of {0} times exceeds the limit of {1}")]
+    ReplicationLimit(usize, usize),
+
+    /// Delayed replication changing the operators of what follows
+    #[error("Replication 1-{0:02}-{1:03} changes the operators depending on its count")]
+    UnplannableReplication(u8, u8),
+
+    /// Sequence whose expansion contains itself
+    #[error("Sequence 3-{0:02}-{1:03} contains itself")]
+    CyclicSequence(u8, u8),
+
+    /// Sequences and replications nested deeper than allowed by the options
+    #[error("Descriptors nested deeper than the limit of {0}")]
+    DepthLimit(usize),
+
+    /// Data section expanding to more descriptors than allowed by the
+    /// options
+    #[error("Descriptors expand to more than the limit of {0}")]
+    ExpansionLimit(usize),
+
+    /// Element too wide to be represented
+    #[error("Data width of {0} bits is not supported")]
+    UnsupportedDataWidth(usize),
+
+    /// Section 4 is shorter than required by the descriptors
+    #[error("Section 4 ended before all descriptors were decoded")]
+    DataTooShort,
+
+    /// Compressed values that should be the same for all subsets differ
+    #[error("Compressed values of 0-{0:02}-{1:03} differ between subsets")]
+    InconsistentCompression(u8, u8),
+
+    /// Not enough values for the descriptors
+    #[error("Missing value for descriptor {0}-{1:02}-{2:03}")]
+    MissingValue(u8, u8, u8),
+
+    /// Value can't be encoded with the descriptor
+    #[error("Value {3} is invalid for descriptor {0}-{1:02}-{2:03}")]
+    InvalidValue(u8, u8, u8, String),
+
+    /// More values than required by the descriptors
+    #[error("{0} values left after encoding all descriptors")]
+    TooManyValues(usize),
+
+    /// Number of subsets larger than section 3 can hold
+    #[error("{0} subsets don't fit in section 3")]
+    TooManySubsets(usize),
+
+    /// Message larger than section 0 can describe
+    #[error("Message would be {0} bytes long, more than the maximum of 16777215")]
+    MessageTooLong(usize),
+
+    /// Required field not set in a builder
+    #[error("Field {0} must be initialized")]
+    UninitializedField(&'static str),
 
     #[error(transparent)]
     IOError(#[from] std::io::Error),
+
+    /// Any of the above, with where it happened
+    #[error("{error} ({location})")]
+    Located {
+        location: Box<Location>,
+        error: Box<Error>,
+    },
+}
+
+impl Error {
+    /// The error itself, without its location
+    pub fn kind(&self) -> &Error {
+        match self {
+            Error::Located { error, .. } => error.kind(),
+            e => e,
+        }
+    }
+
+    /// Where the error happened, if known
+    pub fn location(&self) -> Option<&Location> {
+        match self {
+            Error::Located { location, .. } => Some(location),
+            _ => None,
+        }
+    }
+
+    /// Tag the error with the index and position of the message in a file
+    pub fn with_message(self, index: usize, offset: usize) -> Error {
+        self.locate(|l| l.set_message(index, offset))
+    }
+
+    /// Add or update the location of this error
+    pub(crate) fn locate<F: FnOnce(&mut Location)>(self, f: F) -> Error {
+        match self {
+            Error::Located {
+                mut location,
+                error,
+            } => {
+                f(&mut location);
+                Error::Located { location, error }
+            }
+            error => {
+                let mut location = Box::<Location>::default();
+                f(&mut location);
+                Error::Located {
+                    location,
+                    error: Box::new(error),
+                }
+            }
+        }
+    }
 }
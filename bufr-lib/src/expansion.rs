@@ -0,0 +1,319 @@
+//! Expansion of the descriptors of section 3
+//!
+//! Sequences (F=3) are replaced by their Table D entries, recursively,
+//! while replications (F=1) group the descriptors they repeat and
+//! operators (F=2) stay where they are. Nothing is read from section 4, so
+//! delayed replications keep their count descriptor instead of a count.
+
+use crate::{Descriptor, Error, TableProvider};
+
+/// Node of the tree of descriptors of a message
+#[derive(Clone, Debug, PartialEq)]
+pub enum DescriptorNode {
+    /// Element of Table B (F=0)
+    Element(Descriptor),
+    /// Operator of Table C (F=2)
+    Operator(Descriptor),
+    /// Sequence of Table D (F=3) and its expanded descriptors
+    Sequence {
+        descriptor: Descriptor,
+        children: Vec<DescriptorNode>,
+    },
+    /// Replication (F=1) of the descriptors of `children`
+    Replication {
+        descriptor: Descriptor,
+        count: ReplicationCount,
+        children: Vec<DescriptorNode>,
+    },
+}
+
+/// Number of times the descriptors of a replication are repeated
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReplicationCount {
+    /// Given by the Y of the replication descriptor
+    Fixed(u8),
+    /// Given in the data, by the element of the descriptor
+    Delayed(Descriptor),
+}
+
+impl DescriptorNode {
+    /// Descriptor of the node, before expansion
+    pub fn descriptor(&self) -> &Descriptor {
+        match self {
+            DescriptorNode::Element(descriptor) | DescriptorNode::Operator(descriptor) => {
+                descriptor
+            }
+            DescriptorNode::Sequence { descriptor, .. }
+            | DescriptorNode::Replication { descriptor, .. } => descriptor,
+        }
+    }
+
+    /// Nodes below a sequence or a replication
+    pub fn children(&self) -> &[DescriptorNode] {
+        match self {
+            DescriptorNode::Sequence { children, .. }
+            | DescriptorNode::Replication { children, .. } => children,
+            _ => &[],
+        }
+    }
+
+    /// Add the descriptors of the node, with sequences expanded
+    fn flatten(&self, output: &mut Vec<Descriptor>) {
+        match self {
+            DescriptorNode::Element(descriptor) | DescriptorNode::Operator(descriptor) => {
+                output.push(*descriptor)
+            }
+            DescriptorNode::Sequence { children, .. } => {
+                children.iter().for_each(|child| child.flatten(output))
+            }
+            DescriptorNode::Replication {
+                descriptor,
+                count,
+                children,
+            } => {
+                output.push(*descriptor);
+                if let ReplicationCount::Delayed(factor) = count {
+                    output.push(*factor);
+                }
+                children.iter().for_each(|child| child.flatten(output));
+            }
+        }
+    }
+
+    fn fmt_indented(&self, f: &mut std::fmt::Formatter, indent: usize) -> std::fmt::Result {
+        write!(f, "{:width$}{}", "", self.descriptor(), width = 4 * indent)?;
+        match self {
+            DescriptorNode::Replication {
+                count: ReplicationCount::Fixed(n),
+                ..
+            } => writeln!(f, " x {}", n)?,
+            DescriptorNode::Replication {
+                count: ReplicationCount::Delayed(factor),
+                ..
+            } => writeln!(f, " x {}", factor)?,
+            _ => writeln!(f)?,
+        }
+        for child in self.children() {
+            child.fmt_indented(f, indent + 1)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for DescriptorNode {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        self.fmt_indented(f, 0)
+    }
+}
+
+/// Tree of the descriptors, with sequences expanded through `tables`
+pub(crate) fn descriptor_tree(
+    descriptors: &[Descriptor],
+    tables: &dyn TableProvider,
+) -> Result<Vec<DescriptorNode>, Error> {
+    Expander {
+        tables,
+        sequences: vec![],
+    }
+    .expand(descriptors)
+}
+
+/// Descriptors in the order their values appear, with sequences expanded
+///
+/// Replication descriptors are kept as they are, so their X counts the
+/// descriptors of the group before expansion.
+pub(crate) fn expanded_descriptors(
+    descriptors: &[Descriptor],
+    tables: &dyn TableProvider,
+) -> Result<Vec<Descriptor>, Error> {
+    let mut output = vec![];
+    for node in descriptor_tree(descriptors, tables)? {
+        node.flatten(&mut output);
+    }
+    Ok(output)
+}
+
+struct Expander<'t> {
+    tables: &'t dyn TableProvider,
+    // Sequences being expanded, to find those containing themselves
+    sequences: Vec<Descriptor>,
+}
+
+impl<'t> Expander<'t> {
+    fn expand(&mut self, descriptors: &[Descriptor]) -> Result<Vec<DescriptorNode>, Error> {
+        let mut nodes = vec![];
+        let mut i = 0;
+        while i < descriptors.len() {
+            let descriptor = descriptors[i];
+            i += 1;
+            let node = match descriptor.f {
+                0 => DescriptorNode::Element(descriptor),
+                1 => {
+                    let incomplete = || Error::IncompleteReplication(descriptor.x, descriptor.y);
+                    let count = if descriptor.y == 0 {
+                        let factor = descriptors.get(i).ok_or_else(incomplete)?;
+                        i += 1;
+                        ReplicationCount::Delayed(*factor)
+                    } else {
+                        ReplicationCount::Fixed(descriptor.y)
+                    };
+                    let n = usize::from(descriptor.x);
+                    let group = descriptors.get(i..i + n).ok_or_else(incomplete)?;
+                    i += n;
+                    DescriptorNode::Replication {
+                        descriptor,
+                        count,
+                        children: self.expand(group)?,
+                    }
+                }
+                2 => DescriptorNode::Operator(descriptor),
+                _ => DescriptorNode::Sequence {
+                    descriptor,
+                    children: self.sequence(descriptor)?,
+                },
+            };
+            nodes.push(node);
+        }
+        Ok(nodes)
+    }
+
+    fn sequence(&mut self, descriptor: Descriptor) -> Result<Vec<DescriptorNode>, Error> {
+        if self.sequences.contains(&descriptor) {
+            return Err(Error::CyclicSequence(descriptor.x, descriptor.y));
+        }
+        let descriptors = descriptor
+            .sequence(self.tables)
+            .ok_or(Error::UnknownDescriptor(
+                descriptor.f,
+                descriptor.x,
+                descriptor.y,
+            ))?;
+        self.sequences.push(descriptor);
+        let children = self.expand(&descriptors);
+        self.sequences.pop();
+        children
+    }
+}
+
+#[cfg(test)]
+mod test_expansion {
+    use super::{descriptor_tree, expanded_descriptors, DescriptorNode, ReplicationCount};
+    use crate::{Descriptor, Error, Tables};
+
+    const TABLE_D_HEADER: &str =
+        "Category,CategoryOfSequences_en,FXY1,Title_en,SubTitle_en,FXY2,ElementName_en,ElementDescription_en,Note_en,Status\n";
+
+    fn d(code: &str) -> Descriptor {
+        code.parse().unwrap()
+    }
+
+    fn tables(rows: &[(&str, &str)]) -> Tables {
+        let mut csv = TABLE_D_HEADER.to_string();
+        for (sequence, descriptor) in rows {
+            csv.push_str(&format!(
+                "50,Local,{},Local,,{},,,,Operational\n",
+                sequence, descriptor
+            ));
+        }
+        let mut tables = Tables::default();
+        tables.add_table_d(csv.as_bytes()).unwrap();
+        tables
+    }
+
+    #[test]
+    fn nested_sequences() {
+        let tables = tables(&[
+            ("350001", "001001"),
+            ("350001", "350002"),
+            ("350002", "001002"),
+            ("350002", "201129"),
+            ("350002", "004001"),
+        ]);
+        let expanded = expanded_descriptors(&[d("350001"), d("001015")], &tables).unwrap();
+        assert_eq!(
+            expanded,
+            vec![
+                d("001001"),
+                d("001002"),
+                d("201129"),
+                d("004001"),
+                d("001015")
+            ]
+        );
+
+        let tree = descriptor_tree(&[d("350001")], &tables).unwrap();
+        assert_eq!(tree.len(), 1);
+        assert_eq!(tree[0].descriptor(), &d("350001"));
+        assert_eq!(
+            tree[0].children()[1].children()[1],
+            DescriptorNode::Operator(d("201129"))
+        );
+        assert_eq!(
+            tree[0].to_string(),
+            "3-50-001\n    0-01-001\n    3-50-002\n        0-01-002\n        2-01-129\n        0-04-001\n"
+        );
+    }
+
+    #[test]
+    fn replications() {
+        let tables = tables(&[("350001", "001001"), ("350001", "001002")]);
+        let descriptors = [
+            d("102003"),
+            d("350001"),
+            d("004001"),
+            d("101000"),
+            d("031001"),
+            d("005001"),
+        ];
+        let tree = descriptor_tree(&descriptors, &tables).unwrap();
+        assert_eq!(tree.len(), 2);
+        let DescriptorNode::Replication {
+            count, children, ..
+        } = &tree[0]
+        else {
+            panic!("Not a replication: {:?}", tree[0]);
+        };
+        assert_eq!(*count, ReplicationCount::Fixed(3));
+        assert_eq!(children.len(), 2);
+        let DescriptorNode::Replication { count, .. } = &tree[1] else {
+            panic!("Not a replication: {:?}", tree[1]);
+        };
+        assert_eq!(*count, ReplicationCount::Delayed(d("031001")));
+
+        let expanded = expanded_descriptors(&descriptors, &tables).unwrap();
+        assert_eq!(
+            expanded,
+            vec![
+                d("102003"),
+                d("001001"),
+                d("001002"),
+                d("004001"),
+                d("101000"),
+                d("031001"),
+                d("005001")
+            ]
+        );
+
+        let err = descriptor_tree(&[d("102000"), d("031001"), d("001001")], &tables);
+        assert!(matches!(err, Err(Error::IncompleteReplication(2, 0))));
+    }
+
+    #[test]
+    fn invalid_sequences() {
+        let tables = tables(&[
+            ("350001", "001001"),
+            ("350001", "350002"),
+            ("350002", "350001"),
+        ]);
+        let err = descriptor_tree(&[d("350001")], &tables);
+        assert!(matches!(err, Err(Error::CyclicSequence(50, 1))));
+
+        let err = expanded_descriptors(&[d("001001"), d("350099")], &tables);
+        assert!(matches!(err, Err(Error::UnknownDescriptor(3, 50, 99))));
+
+        // The same sequence twice, but not within itself
+        let tables = self::tables(&[("350001", "001001")]);
+        let expanded = expanded_descriptors(&[d("350001"), d("350001")], &tables).unwrap();
+        assert_eq!(expanded, vec![d("001001"), d("001001")]);
+    }
+}
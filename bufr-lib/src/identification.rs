@@ -7,10 +7,12 @@ use byteorder::{BigEndian, WriteBytesExt};
 use derive_builder::Builder;
 use getset::{CopyGetters, Getters};
 
+use crate::common::{centre_name, data_category_name, data_subcategory_name, sub_centre_name};
 use crate::error::Error;
+use crate::{flags, section_length, Checks};
 
 /// Identification Section (section 1) of the BUFR format
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub enum Section1 {
     /// Version 3 for the section 1
     V3(Section1v3),
@@ -21,25 +23,30 @@ pub enum Section1 {
 impl fmt::Display for Section1 {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            Section1::V3(_) => writeln!(f, "{:?}", self),
+            Section1::V3(s) => writeln!(f, "{}", s),
             Section1::V4(s) => writeln!(f, "{}", s),
         }
     }
 }
 
 impl Section1 {
-    pub(super) fn decode(buf: &[u8], version: u8) -> Result<Section1, Error> {
+    pub(super) fn decode(
+        buf: &[u8],
+        offset: usize,
+        version: u8,
+        checks: &mut Checks,
+    ) -> Result<Section1, Error> {
         Ok(match version {
-            3 => Section1::V3(Section1v3::decode(buf)?),
-            4 => Section1::V4(Section1v4::decode(buf)?),
-            _ => unimplemented!(),
+            3 => Section1::V3(Section1v3::decode(buf, offset, checks)?),
+            4 => Section1::V4(Section1v4::decode(buf, offset, checks)?),
+            v => return Err(Error::VersionNotSupported(v)),
         })
     }
 
     pub(super) fn encode<W: std::io::Write>(&self, wtr: &mut W) -> Result<usize, Error> {
         match self {
+            Section1::V3(v) => v.encode(wtr),
             Section1::V4(v) => v.encode(wtr),
-            _ => unimplemented!(),
         }
     }
 
@@ -58,10 +65,113 @@ impl Section1 {
             Section1::V4(v) => v.optional_section(),
         }
     }
+
+    /// Master table used for the data
+    pub fn master_table(&self) -> u8 {
+        match self {
+            Section1::V3(v) => v.master_table(),
+            Section1::V4(v) => v.master_table(),
+        }
+    }
+
+    /// Version of the master table used for the data
+    pub fn master_table_version(&self) -> u8 {
+        match self {
+            Section1::V3(v) => v.master_table_version(),
+            Section1::V4(v) => v.master_table_version(),
+        }
+    }
+
+    /// Originating centre
+    pub fn center(&self) -> u16 {
+        match self {
+            Section1::V3(v) => u16::from(v.center()),
+            Section1::V4(v) => v.center(),
+        }
+    }
+
+    /// Originating sub-centre
+    pub fn sub_center(&self) -> u16 {
+        match self {
+            Section1::V3(v) => u16::from(v.sub_center()),
+            Section1::V4(v) => v.sub_center(),
+        }
+    }
+
+    /// Data category, from BUFR Table A
+    pub fn data_category(&self) -> u8 {
+        match self {
+            Section1::V3(v) => v.data_category(),
+            Section1::V4(v) => v.data_category(),
+        }
+    }
+
+    /// Version of the local tables of the centre, 0 or 255 when not used
+    pub fn local_table_version(&self) -> u8 {
+        match self {
+            Section1::V3(v) => v.local_table_version(),
+            Section1::V4(v) => v.local_table_version(),
+        }
+    }
+
+    /// Typical time of the data: year, month, day, hour, minute, second
+    ///
+    /// Edition 3 only has the year of the century, and no seconds.
+    pub fn typical_time(&self) -> (u16, u8, u8, u8, u8, u8) {
+        match self {
+            Section1::V3(v) => (
+                u16::from(v.year()),
+                v.month(),
+                v.day(),
+                v.hour(),
+                v.minute(),
+                0,
+            ),
+            Section1::V4(v) => (
+                v.year(),
+                v.month(),
+                v.day(),
+                v.hour(),
+                v.minute(),
+                v.second(),
+            ),
+        }
+    }
+
+    /// BUFR edition matching this variant of the section
+    pub fn edition(&self) -> u8 {
+        match self {
+            Section1::V3(_) => 3,
+            Section1::V4(_) => 4,
+        }
+    }
+
+    pub(crate) fn set_data_category(&mut self, category: u8) {
+        match self {
+            Section1::V3(v) => v.data_category = category,
+            Section1::V4(v) => v.data_category = category,
+        }
+    }
+
+    /// Set the section 2 switch and recompute the length
+    pub(crate) fn prepare(&mut self, optional_section: bool) {
+        match self {
+            Section1::V3(v) => {
+                v.optional_section = optional_section;
+                // Edition 3 sections have an even length
+                v.length = 17 + v.local_use.len();
+                v.length += v.length % 2;
+            }
+            Section1::V4(v) => {
+                v.optional_section = optional_section;
+                v.length = 22 + v.local_use.len();
+            }
+        }
+    }
 }
 
 /// Version 3 variant of the Section 1
-#[derive(CopyGetters, Getters, Debug)]
+#[derive(Clone, CopyGetters, Getters, Debug)]
 pub struct Section1v3 {
     length: usize,
     master_table: u8,
@@ -97,9 +207,69 @@ pub struct Section1v3 {
 }
 
 impl Section1v3 {
-    #[allow(unused_variables)]
-    fn decode(buf: &[u8]) -> Result<Section1v3, Error> {
-        unimplemented!()
+    fn decode(buf: &[u8], offset: usize, checks: &mut Checks) -> Result<Section1v3, Error> {
+        let length = section_length(buf, 1, offset, 17)?;
+        let master_table: u8 = buf[3];
+        let sub_center: u8 = buf[4];
+        let center: u8 = buf[5];
+        let update_version: u8 = buf[6];
+        let optional_section = optional_section_flag(buf, offset, 7, checks)?;
+        let data_category: u8 = buf[8];
+        let data_subcategory: u8 = buf[9];
+        let master_table_version: u8 = buf[10];
+        let local_table_version: u8 = buf[11];
+        let year: u8 = buf[12];
+        let month: u8 = buf[13];
+        let day: u8 = buf[14];
+        let hour: u8 = buf[15];
+        let minute: u8 = buf[16];
+        let local_use = buf[17..length].into();
+
+        Ok(Section1v3 {
+            length,
+            master_table,
+            sub_center,
+            center,
+            update_version,
+            optional_section,
+            data_category,
+            data_subcategory,
+            master_table_version,
+            local_table_version,
+            year,
+            month,
+            day,
+            hour,
+            minute,
+            local_use,
+        })
+    }
+
+    pub fn encode<W: std::io::Write>(&self, wtr: &mut W) -> Result<usize, Error> {
+        wtr.write_u24::<BigEndian>(self.length.try_into().unwrap())?;
+        wtr.write_u8(self.master_table)?;
+        wtr.write_u8(self.sub_center)?;
+        wtr.write_u8(self.center)?;
+        wtr.write_u8(self.update_version)?;
+        if self.optional_section {
+            wtr.write_u8(0b1000_0000)?;
+        } else {
+            wtr.write_u8(0)?;
+        };
+        wtr.write_u8(self.data_category)?;
+        wtr.write_u8(self.data_subcategory)?;
+        wtr.write_u8(self.master_table_version)?;
+        wtr.write_u8(self.local_table_version)?;
+        wtr.write_u8(self.year)?;
+        wtr.write_u8(self.month)?;
+        wtr.write_u8(self.day)?;
+        wtr.write_u8(self.hour)?;
+        wtr.write_u8(self.minute)?;
+        wtr.write_all(&self.local_use)?;
+        let padding = self.length.saturating_sub(17 + self.local_use.len());
+        wtr.write_all(&vec![0; padding])?;
+
+        Ok(17 + self.local_use.len() + padding)
     }
 
     /// Length of the Section 1
@@ -113,9 +283,20 @@ impl Section1v3 {
     }
 }
 
+/// Bit 1 (the most significant) of `buf[i]` flags the presence of section
+/// 2, the other bits are reserved.
+fn optional_section_flag(
+    buf: &[u8],
+    offset: usize,
+    i: usize,
+    checks: &mut Checks,
+) -> Result<bool, Error> {
+    Ok(flags(buf, 1, offset, i, 0b1000_0000, checks)? != 0)
+}
+
 /// Version 4 variant of the Section 1
 #[allow(missing_docs)]
-#[derive(Builder, CopyGetters, Getters, Debug)]
+#[derive(Builder, Clone, CopyGetters, Getters, Debug)]
 pub struct Section1v4 {
     #[builder(setter(skip = true), default = "self.default_length()?")]
     length: usize,
@@ -158,17 +339,13 @@ pub struct Section1v4 {
 }
 
 impl Section1v4 {
-    fn decode(buf: &[u8]) -> Result<Section1v4, Error> {
-        let length = (usize::from(buf[0]) << 16) + (usize::from(buf[1]) << 8) + usize::from(buf[2]);
+    fn decode(buf: &[u8], offset: usize, checks: &mut Checks) -> Result<Section1v4, Error> {
+        let length = section_length(buf, 1, offset, 22)?;
         let master_table: u8 = buf[3];
         let center: u16 = (u16::from(buf[4]) << 8) + u16::from(buf[5]);
         let sub_center: u16 = (u16::from(buf[6]) << 8) + u16::from(buf[7]);
         let update_version: u8 = buf[8];
-        let optional_section: bool = match buf[9] {
-            64 => true,
-            0 => false,
-            _ => todo!("error"),
-        };
+        let optional_section = optional_section_flag(buf, offset, 9, checks)?;
         let data_category: u8 = buf[10];
         let data_subcategory: u8 = buf[11];
         let local_subcategory: u8 = buf[12];
@@ -181,11 +358,7 @@ impl Section1v4 {
         let hour: u8 = buf[19];
         let minute: u8 = buf[20];
         let second: u8 = buf[21];
-        let local_use = if length > 21 {
-            buf[22..length].into()
-        } else {
-            vec![]
-        };
+        let local_use = buf[22..length].into();
 
         Ok(Section1v4 {
             length,
@@ -216,7 +389,7 @@ impl Section1v4 {
         wtr.write_u16::<BigEndian>(self.sub_center)?;
         wtr.write_u8(self.update_version)?;
         if self.optional_section {
-            wtr.write_u8(64)?;
+            wtr.write_u8(0b1000_0000)?;
         } else {
             wtr.write_u8(0)?;
         };
@@ -235,8 +408,7 @@ impl Section1v4 {
             wtr.write_all(&self.local_use)?;
         };
 
-        // FIXME: does wtr have a counter?
-        Ok(8 + 22 + self.local_use.len())
+        Ok(22 + self.local_use.len())
     }
 
     /// Length of the Section 1
@@ -245,6 +417,70 @@ impl Section1v4 {
     }
 }
 
+/// A code figure followed by its meaning, when known
+fn named<T: fmt::Display>(value: T, name: Option<&str>) -> String {
+    match name {
+        Some(name) => format!("{} ({})", value, name),
+        None => value.to_string(),
+    }
+}
+
+impl fmt::Display for Section1v3 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Section 1 (v3)")?;
+        writeln!(f, "    Section length: {}", self.length())?;
+        writeln!(
+            f,
+            "    Master table: {} (version: {})",
+            self.master_table(),
+            self.master_table_version()
+        )?;
+        // Centres of Common Code table C-1 have the figures of C-11
+        writeln!(
+            f,
+            "    Sub-center: {}",
+            named(
+                self.sub_center(),
+                sub_centre_name(self.center().into(), self.sub_center().into())
+            )
+        )?;
+        writeln!(
+            f,
+            "    Center: {}",
+            named(self.center(), centre_name(self.center().into()))
+        )?;
+        writeln!(f, "    Update version: {}", self.update_version())?;
+        writeln!(f, "    Optional section: {}", self.optional_section())?;
+        writeln!(
+            f,
+            "    Data category: {}",
+            named(
+                self.data_category(),
+                data_category_name(self.data_category())
+            )
+        )?;
+        writeln!(
+            f,
+            "    Data sub-category: {}",
+            named(
+                self.data_subcategory(),
+                data_subcategory_name(self.data_category(), self.data_subcategory())
+            )
+        )?;
+        writeln!(f, "    Local table version: {}", self.local_table_version())?;
+        writeln!(
+            f,
+            "    Time: year {} of the century, {}-{}T{}:{}",
+            self.year(),
+            self.month(),
+            self.day(),
+            self.hour(),
+            self.minute()
+        )?;
+        writeln!(f, "    Local use: {:x?}", self.local_use())
+    }
+}
+
 impl fmt::Display for Section1v4 {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         writeln!(f, "Section 1 (v4)")?;
@@ -255,12 +491,37 @@ impl fmt::Display for Section1v4 {
             self.master_table(),
             self.master_table_version()
         )?;
-        writeln!(f, "    Sub-center: {}", self.sub_center())?;
-        writeln!(f, "    Center: {}", self.center())?;
+        writeln!(
+            f,
+            "    Sub-center: {}",
+            named(
+                self.sub_center(),
+                sub_centre_name(self.center(), self.sub_center())
+            )
+        )?;
+        writeln!(
+            f,
+            "    Center: {}",
+            named(self.center(), centre_name(self.center()))
+        )?;
         writeln!(f, "    Update version: {}", self.update_version())?;
         writeln!(f, "    Optional section: {}", self.optional_section())?;
-        writeln!(f, "    Data category: {}", self.data_category())?;
-        writeln!(f, "    Data sub-category: {}", self.data_subcategory())?;
+        writeln!(
+            f,
+            "    Data category: {}",
+            named(
+                self.data_category(),
+                data_category_name(self.data_category())
+            )
+        )?;
+        writeln!(
+            f,
+            "    Data sub-category: {}",
+            named(
+                self.data_subcategory(),
+                data_subcategory_name(self.data_category(), self.data_subcategory())
+            )
+        )?;
         writeln!(f, "    Local sub-category: {}", self.local_subcategory())?;
         writeln!(f, "    Local table version: {}", self.local_table_version())?;
         writeln!(
@@ -318,6 +579,49 @@ mod test_section1 {
     }
 }
 
+#[cfg(test)]
+mod test_section1v3 {
+    use super::Section1v3;
+    use crate::{Checks, Error};
+
+    #[test]
+    fn roundtrip() -> Result<(), Box<dyn std::error::Error>> {
+        let buf = [0, 0, 18, 0, 0, 98, 0, 0, 2, 1, 13, 0, 99, 12, 31, 18, 0, 0];
+        let section = Section1v3::decode(&buf, 8, &mut Checks::new(true))?;
+        assert_eq!(section.length(), 18);
+        assert_eq!(section.center(), 98);
+        assert_eq!(section.data_category(), 2);
+        assert_eq!(section.master_table_version(), 13);
+        assert_eq!(section.year(), 99);
+        assert_eq!(section.local_use(), &[0]);
+
+        let text = section.to_string();
+        assert!(text.contains("Center: 98 (European Centre for Medium-Range Weather Forecasts)"));
+        assert!(text.contains("Data category: 2 (Vertical soundings (other than satellite))"));
+        assert!(text.contains(
+            "Data sub-category: 1 (Upper-wind reports from fixed-land stations (PILOT))"
+        ));
+
+        let mut encoded = vec![];
+        assert_eq!(section.encode(&mut encoded)?, 18);
+        assert_eq!(encoded, buf);
+        Ok(())
+    }
+
+    #[test]
+    fn too_short() {
+        let buf = [0, 0, 16, 0, 0, 98, 0, 0, 2, 1, 13, 0, 99, 12, 31, 18];
+        match Section1v3::decode(&buf, 8, &mut Checks::new(true)) {
+            Err(Error::InvalidSectionLength {
+                section: 1,
+                offset: 8,
+                length: 16,
+            }) => (),
+            _ => panic!(),
+        }
+    }
+}
+
 #[cfg(test)]
 mod test_section1_builder {
     use super::Section1v4Builder;
@@ -350,15 +654,6 @@ mod test_section1_builder {
 
 impl Section1v4Builder {
     fn default_length(&self) -> Result<usize, Section1v4BuilderError> {
-        // Only if local_use is false
-        Ok(22)
-        /*
-        match self.local_use {
-            Some(ref x) => Ok(7 + 2 * x.len()),
-            _ => Err(Section1v4BuilderError::from(
-                derive_builder::UninitializedFieldError::new("local_use"),
-            )),
-        }
-        */
+        Ok(22 + self.local_use.as_ref().map_or(0, |v| v.len()))
     }
 }
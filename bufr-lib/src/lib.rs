@@ -4,54 +4,186 @@
 //!
 //! Module level docs
 
+#[cfg(feature = "async")]
+mod async_reader;
+mod builder;
+mod codes;
+mod common;
+mod data;
+mod eccodes;
 mod error;
+mod expansion;
 mod identification;
+mod ncep;
+mod options;
+#[cfg(feature = "parallel")]
+mod parallel;
+mod partial;
+mod plan;
+mod reader;
+mod scan;
+mod table_messages;
 mod tables;
+mod units;
 
+use std::borrow::Cow;
 use std::convert::TryInto;
 use std::fmt;
 
 use byteorder::{BigEndian, WriteBytesExt};
 use derive_builder::Builder;
-
-pub use crate::error::Error;
-pub use crate::identification::Section1;
-use crate::tables::TABLE_F3;
+use getset::{CopyGetters, Getters};
+
+#[cfg(feature = "async")]
+pub use crate::async_reader::{async_messages, async_messages_with, AsyncMessages};
+pub use crate::builder::MessageBuilder;
+pub use crate::codes::{CodeTable, Meaning};
+pub use crate::common::{centre_name, data_category_name, data_subcategory_name, sub_centre_name};
+pub use crate::data::{Field, Subset, Value};
+pub use crate::error::{Error, Location};
+pub use crate::expansion::{DescriptorNode, ReplicationCount};
+pub use crate::identification::{Section1, Section1v3, Section1v4, Section1v4Builder};
+pub use crate::ncep::{is_dx, ncep_messages, strip_fortran_records, NcepMessages};
+pub use crate::options::{DecodeOptions, MissingValues, UnknownDescriptors};
+#[cfg(feature = "parallel")]
+pub use crate::parallel::{decode_all, decode_all_with};
+pub use crate::partial::{Diagnostic, PartialMessage, Severity};
+pub use crate::plan::{DecodePlan, PlanCache};
+pub use crate::reader::{messages, messages_with, Messages};
+pub use crate::scan::{decode_header, scan, scan_with, Header, Headers};
+pub use crate::table_messages::is_table_message;
+pub use crate::tables::{
+    Layers, MasterTables, TableProvider, TableSet, Tables, EMBEDDED_VERSIONS, RESERVED_CLASSES,
+    TABLES_PATH_VAR,
+};
+pub use crate::units::{BUFRUnit, Dimension, Ratio};
 
 /// A parsed BUFR message
-pub struct Message {
+///
+/// Decoded messages borrow the payloads of sections 2 and 4 from the
+/// buffer they are decoded from, which may be a memory-mapped file. Use
+/// `into_owned` to keep a message longer than the buffer.
+///
+/// The descriptors of section 3 are the exception: they are parsed into a
+/// `Vec<Descriptor>` when decoding, since `Section3::descriptors` and the
+/// compiled plans work on parsed descriptors. They are two bytes each in
+/// the buffer, a small part of a message next to its data.
+pub struct Message<'a> {
     total_length: u32,
     version: u8,
     section1: Section1,
+    section2: Option<Section2<'a>>,
     section3: Section3,
-    section4: Section4,
+    section4: Section4<'a>,
+    options: DecodeOptions,
+    // Values of section 4, when decoded with the message
+    data: Option<Vec<Subset>>,
+    // Problems tolerated while decoding
+    warnings: Vec<Error>,
 }
 
-impl fmt::Display for Message {
+impl fmt::Display for Message<'_> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         writeln!(f, "BUFR version {}", self.version)?;
         writeln!(f, "     total length: {}", self.total_length)?;
         writeln!(f, "\n")?;
         writeln!(f, "{}", self.section1)?;
         writeln!(f, "\n")?;
+        if let Some(section2) = &self.section2 {
+            writeln!(f, "{}", section2)?;
+            writeln!(f, "\n")?;
+        }
         writeln!(f, "{}", self.section3)
     }
 }
 
-impl fmt::Debug for Message {
+impl fmt::Debug for Message<'_> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         writeln!(f, "BUFR version {}", self.version)?;
         writeln!(f, "     total length: {}", self.total_length)?;
         writeln!(f, "\n")?;
         writeln!(f, "{}", self.section1)?;
         writeln!(f, "\n")?;
+        if let Some(section2) = &self.section2 {
+            writeln!(f, "{:?}", section2)?;
+            writeln!(f, "\n")?;
+        }
         writeln!(f, "{}", self.section3)?;
         writeln!(f, "\n")?;
         writeln!(f, "{:?}", self.section4)
     }
 }
 
+/// Optional Section (section 2) of the BUFR format
+///
+/// Its content is defined by the originating centre, so it is kept as raw
+/// bytes.
+#[derive(Debug, Clone)]
+pub struct Section2<'a> {
+    length: usize,
+    data: Cow<'a, [u8]>,
+}
+
+impl fmt::Display for Section2<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Section 2")?;
+        writeln!(f, "length: {:?}", self.length())?;
+        writeln!(f, "data: {:x?}", self.data)
+    }
+}
+
+impl<'a> Section2<'a> {
+    /// Create a section 2 for the given local data
+    pub fn new(data: Vec<u8>) -> Section2<'static> {
+        Section2 {
+            length: 4 + data.len(),
+            data: Cow::Owned(data),
+        }
+    }
+
+    fn decode(buf: &'a [u8], offset: usize, checks: &mut Checks) -> Result<Section2<'a>, Error> {
+        let length = section_length(buf, 2, offset, 4)?;
+        checks.check(reserved_byte(buf, 2, offset, 3))?;
+        let data = Cow::Borrowed(&buf[4..length]);
+
+        Ok(Section2 { length, data })
+    }
+
+    /// Copy the data if it is borrowed
+    pub fn into_owned(self) -> Section2<'static> {
+        Section2 {
+            length: self.length,
+            data: Cow::Owned(self.data.into_owned()),
+        }
+    }
+
+    pub fn encode<W: std::io::Write>(&self, wtr: &mut W) -> Result<usize, Error> {
+        wtr.write_u24::<BigEndian>(self.length.try_into().unwrap())?;
+        wtr.write_u8(0)?;
+        wtr.write_all(&self.data)?;
+        wtr.write_all(&vec![0; self.length.saturating_sub(4 + self.data.len())])?;
+        Ok(self.length)
+    }
+
+    /// Pad the section to an even length, as edition 3 requires
+    fn pad(&mut self) {
+        self.length += self.length % 2;
+    }
+
+    pub fn length(&self) -> usize {
+        self.length
+    }
+
+    /// Local data, as defined by the originating centre
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+}
+
 /// Data description Section (section 3) of the BUFR format
+///
+/// Unlike the payloads of sections 2 and 4, the descriptors are owned, see
+/// `Message`.
 #[derive(Builder, Debug)]
 pub struct Section3 {
     #[builder(setter(skip = true), default = "self.default_length()?")]
@@ -72,46 +204,33 @@ impl fmt::Display for Section3 {
         writeln!(f, "is observed: {:?}", self.is_observed())?;
         writeln!(f, "is compressed: {:?}", self.is_compressed())?;
 
-        let mut ident = String::new();
-        ident.push_str("    ");
-
-        // let mut n = 0;
-        let mut iter = self.descriptors.iter();
-        while let Some(d) = iter.next() {
-            if d.f == 1 {
-                if let Some(dd) = iter.next() {
-                    writeln!(f, "{}{:?} + {:?}", ident, d, dd)?;
+        // Expanded with the embedded tables, as the message isn't known here
+        match self.descriptor_tree(&Tables::embedded()) {
+            Ok(tree) => {
+                for node in tree {
+                    write!(f, "{}", node)?;
                 }
-                // n = if d.y == 0 { d.x + 1 } else { d.x };
-                ident.push_str("    ");
-            } else if d.f == 3 {
-                writeln!(f, "{}{:?}", ident, d)?;
-
-                let child = TABLE_F3
-                    .get(&(d.x, d.y))
-                    .expect("Failed to get item from F3 table");
-                for c in child.iter() {
-                    writeln!(f, "{} |_ {}", ident, c)?;
+            }
+            Err(e) => {
+                for d in &self.descriptors {
+                    writeln!(f, "{}", d)?;
                 }
-            } else {
-                writeln!(f, "{}{:?}", ident, d)?;
-            };
+                writeln!(f, "can't expand the descriptors: {}", e)?;
+            }
         }
         Ok(())
     }
 }
 
 impl Section3 {
-    fn decode(buf: &[u8]) -> Result<Section3, Error> {
-        if buf.len() < 8 {
-            return Err(Error::MessageTooShort);
-        }
-        let length = (usize::from(buf[0]) << 16) + (usize::from(buf[1]) << 8) + usize::from(buf[2]);
-        if buf.len() < length {
-            return Err(Error::TruncatedMessage);
-        }
-        // 4th byte reserved, set to zero
-        assert_eq!(buf[3], 0);
+    fn decode(
+        buf: &[u8],
+        offset: usize,
+        version: u8,
+        checks: &mut Checks,
+    ) -> Result<Section3, Error> {
+        let length = section_length(buf, 3, offset, 9)?;
+        checks.check(reserved_byte(buf, 3, offset, 3))?;
         // number of descriptors
         let n_subsets = (u16::from(buf[4]) << 8) + u16::from(buf[5]);
         /*
@@ -124,22 +243,23 @@ impl Section3 {
         };
         */
 
-        let (is_observed, is_compressed) = match buf[6] {
-            0 => (false, false),
-            0b01_000000 => (false, true),
-            0b10_000000 => (true, false),
-            0b11_000000 => (true, true),
-            _ => todo!("error"),
-        };
-
-        let mut descriptors = vec![];
-        if (length - 7) % 2 != 0 {
-            return Err(Error::InvalidSection3Length(length));
-        }
-        for chunk in buf[7..length].chunks(2) {
-            let descriptor = parse_descriptor(chunk.try_into().unwrap());
-            descriptors.push(descriptor);
+        // Bit 1 flags observed data and bit 2 compression
+        let value = flags(buf, 3, offset, 6, 0b11_000000, checks)?;
+        let is_observed = value & 0b10_000000 != 0;
+        let is_compressed = value & 0b01_000000 != 0;
+
+        // Edition 3 pads the section to an even number of bytes
+        if (length - 7) % 2 != 0 && version != 3 {
+            checks.check(Err(Error::InvalidSectionLength {
+                section: 3,
+                offset,
+                length,
+            }))?;
         }
+        let descriptors = buf[7..length]
+            .chunks_exact(2)
+            .map(|chunk| parse_descriptor([chunk[0], chunk[1]]))
+            .collect();
 
         Ok(Section3 {
             length,
@@ -160,11 +280,14 @@ impl Section3 {
             (true, false) => 0b10_000000,
             (true, true) => 0b11_000000,
         })?;
-        let mut n: usize = 0;
+        let mut n: usize = 7;
         for fxy in &self.descriptors {
             n += fxy.encode(wtr)?;
         }
-        Ok(7 + n)
+        // Edition 3 pads the section to an even number of bytes
+        let padding = self.length.saturating_sub(n);
+        wtr.write_all(&vec![0; padding])?;
+        Ok(n + padding)
     }
 
     pub fn length(&self) -> usize {
@@ -179,8 +302,33 @@ impl Section3 {
         self.is_compressed
     }
 
-    pub fn descriptors(&self) -> Vec<Descriptor> {
-        self.descriptors.clone()
+    pub fn n_subsets(&self) -> u16 {
+        self.n_subsets
+    }
+
+    pub fn descriptors(&self) -> &[Descriptor] {
+        &self.descriptors
+    }
+
+    /// Descriptors with every sequence replaced by its Table D entry
+    ///
+    /// Replication and operator descriptors stay in place, see
+    /// `descriptor_tree` for the groups of replications.
+    pub fn expanded_descriptors(
+        &self,
+        tables: &dyn TableProvider,
+    ) -> Result<Vec<Descriptor>, Error> {
+        expansion::expanded_descriptors(&self.descriptors, tables)
+    }
+
+    /// Descriptors as a tree of sequences and replications
+    ///
+    /// Fails on sequences missing from `tables` or containing themselves.
+    pub fn descriptor_tree(
+        &self,
+        tables: &dyn TableProvider,
+    ) -> Result<Vec<DescriptorNode>, Error> {
+        expansion::descriptor_tree(&self.descriptors, tables)
     }
 }
 
@@ -204,6 +352,20 @@ mod test_section3 {
 
         Ok(())
     }
+
+    #[test]
+    fn display_unknown_sequence() {
+        let section = Section3 {
+            length: 11,
+            n_subsets: 1,
+            is_observed: true,
+            is_compressed: false,
+            descriptors: vec![Descriptor::new(0, 1, 1), Descriptor::new(3, 50, 99)],
+        };
+        let text = section.to_string();
+        assert!(text.contains("0-01-001\n3-50-099\n"), "{}", text);
+        assert!(text.contains("Descriptor 3-50-099 not found"), "{}", text);
+    }
 }
 
 impl Section3Builder {
@@ -234,37 +396,59 @@ mod test_section3_builder {
 }
 
 #[derive(Debug)]
-pub struct Section4 {
+pub struct Section4<'a> {
     length: usize,
-    data: Vec<u8>,
+    data: Cow<'a, [u8]>,
 }
 
-impl Section4 {
+impl<'a> Section4<'a> {
     pub fn length(&self) -> usize {
         self.length
     }
 
-    pub fn data(&self) -> Vec<u8> {
-        self.data.clone()
+    /// Encoded values of the subsets
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    fn decode(buf: &'a [u8], offset: usize, checks: &mut Checks) -> Result<Section4<'a>, Error> {
+        let length = section_length(buf, 4, offset, 4)?;
+        checks.check(reserved_byte(buf, 4, offset, 3))?;
+        let data = Cow::Borrowed(&buf[4..length]);
+
+        Ok(Section4 { length, data })
     }
 
-    fn decode(buf: &[u8]) -> Result<Section4, Error> {
-        if buf.len() < 3 {
-            return Err(Error::MessageTooShort);
+    fn new(data: Vec<u8>) -> Section4<'static> {
+        Section4 {
+            length: 4 + data.len(),
+            data: Cow::Owned(data),
         }
-        let length = (usize::from(buf[0]) << 16) + (usize::from(buf[1]) << 8) + usize::from(buf[2]);
-        if buf.len() < length {
-            return Err(Error::TruncatedMessage);
+    }
+
+    /// Pad the section to an even length, as edition 3 requires
+    fn pad(&mut self) {
+        self.length += self.length % 2;
+    }
+
+    /// Copy the data if it is borrowed
+    pub fn into_owned(self) -> Section4<'static> {
+        Section4 {
+            length: self.length,
+            data: Cow::Owned(self.data.into_owned()),
         }
-        // 4th byte reserved, set to zero
-        assert_eq!(buf[3], 0);
-        let data = buf[4..length].into();
+    }
 
-        Ok(Section4 { length, data })
+    pub fn encode<W: std::io::Write>(&self, wtr: &mut W) -> Result<usize, Error> {
+        wtr.write_u24::<BigEndian>(self.length.try_into().unwrap())?;
+        wtr.write_u8(0)?;
+        wtr.write_all(&self.data)?;
+        wtr.write_all(&vec![0; self.length.saturating_sub(4 + self.data.len())])?;
+        Ok(self.length)
     }
 }
 
-impl Message {
+impl<'a> Message<'a> {
     /// Total length of the message including all sections
     pub fn total_length(&self) -> u32 {
         self.total_length
@@ -280,31 +464,245 @@ impl Message {
         &self.section1
     }
 
+    /// Section 2 of the Message, if present
+    pub fn section2(&self) -> Option<&Section2<'a>> {
+        self.section2.as_ref()
+    }
+
     /// Section 3 of the Message
     pub fn section3(&self) -> &Section3 {
         &self.section3
     }
 
     /// Section 4 of the Message
-    pub fn section4(&self) -> &Section4 {
+    pub fn section4(&self) -> &Section4<'a> {
         &self.section4
     }
 
+    /// Copy whatever the message borrows from the buffer it was decoded
+    /// from
+    pub fn into_owned(self) -> Message<'static> {
+        Message {
+            total_length: self.total_length,
+            version: self.version,
+            section1: self.section1,
+            section2: self.section2.map(Section2::into_owned),
+            section3: self.section3,
+            section4: self.section4.into_owned(),
+            options: self.options,
+            data: self.data,
+            warnings: self.warnings,
+        }
+    }
+
+    /// Problems tolerated while decoding the message
+    ///
+    /// Such as tables of another version than the one asked for, or a
+    /// section check relaxed by lenient options.
+    pub fn warnings(&self) -> &[Error] {
+        &self.warnings
+    }
+
+    /// Position of section 4 in the message
+    fn section4_offset(&self) -> usize {
+        section4_offset(&self.section1, self.section2.as_ref(), &self.section3)
+    }
+
+    /// Decode the values of all subsets in section 4
+    ///
+    /// Values already decoded with the message are returned directly.
+    pub fn subsets(&self) -> Result<Vec<Subset>, Error> {
+        match &self.data {
+            Some(data) => Ok(data.clone()),
+            None => self.decode_data(),
+        }
+    }
+
+    /// Meaning of the value of a field, for code and flag tables
+    ///
+    /// Looked up in the tables the message is decoded with. `None` when the
+    /// table of the element isn't loaded, as few are embedded. Sub-centres
+    /// are those of the originating centre of section 1.
+    pub fn meaning(&self, field: &Field) -> Option<Meaning> {
+        let tables = self
+            .options
+            .table_set()
+            .ok()?
+            .resolve(&self.section1)
+            .ok()?;
+        let centre = self.section1.center();
+        tables::code_meaning(&tables, field.descriptor(), field.value(), Some(centre))
+    }
+
+    /// Descriptors of section 3 expanded with the tables of the message
+    pub fn expanded_descriptors(&self) -> Result<Vec<Descriptor>, Error> {
+        let tables = self.options.table_set()?.resolve(&self.section1)?;
+        self.section3.expanded_descriptors(&tables)
+    }
+
+    /// Tree of the descriptors of section 3, with the tables of the message
+    pub fn descriptor_tree(&self) -> Result<Vec<DescriptorNode>, Error> {
+        let tables = self.options.table_set()?.resolve(&self.section1)?;
+        self.section3.descriptor_tree(&tables)
+    }
+
+    fn decode_data(&self) -> Result<Vec<Subset>, Error> {
+        let start = self.section4_offset();
+        let table_set = self.options.table_set()?;
+        let tables = table_set.resolve(&self.section1)?;
+        let plan = table_set.plan(&self.section1, &self.section3.descriptors)?;
+        data::decode_subsets(
+            &self.section4.data,
+            &self.section3.descriptors,
+            usize::from(self.section3.n_subsets),
+            self.section3.is_compressed,
+            &tables,
+            plan.as_deref(),
+            &self.options,
+        )
+        .map_err(|e| locate_data(e, start))
+    }
+
     pub fn encode<W: std::io::Write>(&self, wtr: &mut W) -> Result<usize, Error> {
         wtr.write_all(b"BUFR")?;
         wtr.write_u24::<BigEndian>(self.total_length)?;
 
         wtr.write_u8(self.version)?;
 
-        self.section1.encode(wtr)?;
+        let mut n = 8;
+        n += self.section1.encode(wtr)?;
+        if let Some(section2) = &self.section2 {
+            n += section2.encode(wtr)?;
+        }
+        n += self.section3.encode(wtr)?;
+        n += self.section4.encode(wtr)?;
+        wtr.write_all(b"7777")?;
 
-        // FIXME proper size
-        Ok(self.total_length as usize)
+        Ok(n + 4)
     }
 }
 
 /// Decode a BUFR message
-pub fn decode(buf: &[u8]) -> Result<Message, Error> {
+///
+/// The message borrows from `buf`.
+pub fn decode(buf: &[u8]) -> Result<Message<'_>, Error> {
+    decode_with(buf, &DecodeOptions::default())
+}
+
+/// Decode a message with explicit options
+pub fn decode_with<'a>(buf: &'a [u8], options: &DecodeOptions) -> Result<Message<'a>, Error> {
+    decode_parts(buf, options, options.decode_data).into_message()
+}
+
+/// Decode as much of a message as possible
+///
+/// Nothing is lost to a single error: the sections before it are kept, and
+/// so are the subsets before the first one that fails. Problems tolerated
+/// by lenient options are reported as warnings.
+///
+/// ```
+/// # let buf = [];
+/// let partial = bufr::decode_partial(&buf, &bufr::DecodeOptions::default().strict(false));
+/// for diagnostic in partial.diagnostics() {
+///     eprintln!("{}", diagnostic);
+/// }
+/// let n_subsets = partial.subsets().len();
+/// ```
+pub fn decode_partial<'a>(buf: &'a [u8], options: &DecodeOptions) -> PartialMessage<'a> {
+    decode_parts(buf, options, true)
+}
+
+fn decode_parts<'a>(buf: &'a [u8], options: &DecodeOptions, with_data: bool) -> PartialMessage<'a> {
+    let mut partial = PartialMessage::new(options);
+    let mut checks = Checks::new(options.strict);
+    let result = decode_sections(buf, &mut checks, &mut partial);
+    for warning in checks.warnings {
+        partial.push(Severity::Warning, warning);
+    }
+    if let Err(e) = result {
+        partial.push(Severity::Error, e);
+    }
+
+    // Problems with the tables, pushed once the sections aren't borrowed
+    let mut found = vec![];
+    if let (Some(section1), Some(section3), Some(section4)) =
+        (&partial.section1, &partial.section3, &partial.section4)
+    {
+        // The tables are only an error when the data is decoded
+        let resolved = options
+            .table_set()
+            .and_then(|table_set| Ok((table_set, table_set.resolve(section1)?)));
+        if let Ok((_, tables)) = &resolved {
+            for warning in table_warnings(section1, tables) {
+                found.push((Severity::Warning, warning));
+            }
+        }
+        if with_data {
+            let start = section4_offset(section1, partial.section2.as_ref(), section3);
+            let planned = resolved.and_then(|(table_set, tables)| {
+                let plan = table_set.plan(section1, &section3.descriptors)?;
+                Ok((tables, plan))
+            });
+            match planned {
+                Ok((tables, plan)) => {
+                    let (subsets, error) = data::decode_subsets_partial(
+                        &section4.data,
+                        &section3.descriptors,
+                        usize::from(section3.n_subsets),
+                        section3.is_compressed,
+                        &tables,
+                        plan.as_deref(),
+                        options,
+                    );
+                    partial.subsets = Some(subsets);
+                    if let Some(e) = error {
+                        let severity = if options.truncates(&e) {
+                            Severity::Warning
+                        } else {
+                            Severity::Error
+                        };
+                        found.push((severity, locate_data(e, start)));
+                    }
+                }
+                Err(e) => found.push((Severity::Error, e)),
+            }
+        }
+    }
+    for (severity, error) in found {
+        partial.push(severity, error);
+    }
+    partial
+}
+
+/// Tables the message is decoded with that aren't the ones it asks for
+fn table_warnings(section1: &Section1, tables: &Layers) -> Vec<Error> {
+    let mut warnings = vec![];
+    let requested = section1.master_table_version();
+    let used = tables.master_version();
+    if used != requested {
+        warnings.push(Error::TableVersion {
+            master_table: section1.master_table(),
+            requested,
+            used,
+        });
+    }
+    let version = section1.local_table_version();
+    if !tables.has_local() && version != 0 && version != 255 {
+        warnings.push(Error::NoLocalTables {
+            center: section1.center(),
+            sub_center: section1.sub_center(),
+            version,
+        });
+    }
+    warnings
+}
+
+/// Decode every section in order, keeping each one as soon as it's decoded
+fn decode_sections<'a>(
+    buf: &'a [u8],
+    checks: &mut Checks,
+    partial: &mut PartialMessage<'a>,
+) -> Result<(), Error> {
     // section 0
     if buf.len() < 8 {
         return Err(Error::MessageTooShort);
@@ -318,53 +716,212 @@ pub fn decode(buf: &[u8]) -> Result<Message, Error> {
     let total_length: u32 =
         (u32::from(buf[4]) << 16) + (u32::from(buf[5]) << 8) + u32::from(buf[6]);
 
-    if (buf.len() as u32) < total_length {
+    if buf.len() < total_length as usize {
         return Err(Error::TruncatedMessage);
     }
+    if total_length < 8 {
+        return Err(Error::InvalidSectionLength {
+            section: 0,
+            offset: 0,
+            length: total_length as usize,
+        });
+    }
+    partial.total_length = Some(total_length);
+    // Sections can't extend beyond the total length
+    let buf = &buf[..total_length as usize];
     // TODO: version [0x04]
     let version: u8 = buf[7];
     match version {
         3 | 4 => (),
         v => return Err(Error::VersionNotSupported(v)),
     };
+    partial.version = Some(version);
 
     let mut offset: usize = 8;
 
-    let section1 = Section1::decode(&buf[offset..], version)?;
+    let section1 = Section1::decode(&buf[offset..], offset, version, checks)?;
     offset += section1.length();
+    let optional_section = section1.optional_section();
+    partial.section1 = Some(section1);
 
-    if section1.optional_section() {
-        //Section2::decode(&buf[offset..]) -> n_consumed
-        unimplemented!()
+    if optional_section {
+        let section2 = Section2::decode(&buf[offset..], offset, checks)?;
+        offset += section2.length();
+        partial.section2 = Some(section2);
     }
 
-    let section3 = Section3::decode(&buf[offset..])?;
+    let section3 = Section3::decode(&buf[offset..], offset, version, checks)?;
     offset += section3.length();
+    partial.section3 = Some(section3);
 
-    let section4 = Section4::decode(&buf[offset..])?;
+    let section4 = Section4::decode(&buf[offset..], offset, checks)?;
     offset += section4.length();
-
-    if &buf[offset..(offset + 4)] != b"7777" {
-        return Err(Error::EndSection);
+    partial.section4 = Some(section4);
+
+    if buf.get(offset..(offset + 4)) != Some(&b"7777"[..]) {
+        // Sections may be followed by padding up to the total length
+        if buf.ends_with(b"7777") {
+            checks.check(Err(Error::EndSection { offset }))?;
+        } else {
+            return Err(Error::EndSection { offset });
+        }
     }
+    Ok(())
+}
 
-    Ok(Message {
-        total_length,
-        version,
-        section1,
-        section3,
-        section4,
+/// Position of section 4 in a message
+fn section4_offset(section1: &Section1, section2: Option<&Section2>, section3: &Section3) -> usize {
+    8 + section1.length() + section2.map_or(0, |s| s.length()) + section3.length()
+}
+
+/// Complete the location of an error in the values of section 4
+fn locate_data(error: Error, section4_offset: usize) -> Error {
+    let start = section4_offset + 4;
+    error.locate(|l| {
+        let offset = start + l.bit_offset().unwrap_or(0) / 8;
+        l.set_section(4, offset)
     })
 }
 
-/// Struct for a descriptor
-#[derive(Debug, Clone, PartialEq)]
+/// Checks that fail only in strict mode, and are otherwise kept as warnings
+pub(crate) struct Checks {
+    strict: bool,
+    warnings: Vec<Error>,
+}
+
+impl Checks {
+    fn new(strict: bool) -> Checks {
+        Checks {
+            strict,
+            warnings: vec![],
+        }
+    }
+
+    fn check(&mut self, result: Result<(), Error>) -> Result<(), Error> {
+        match result {
+            Err(e) if !self.strict => {
+                self.warnings.push(e);
+                Ok(())
+            }
+            result => result,
+        }
+    }
+}
+
+/// Length of a section, from its first 3 bytes
+///
+/// `offset` is the position of the section in the message, used only to
+/// report errors. The length must be at least `min` and fit in `buf`.
+pub(crate) fn section_length(
+    buf: &[u8],
+    section: u8,
+    offset: usize,
+    min: usize,
+) -> Result<usize, Error> {
+    if buf.len() < 3 {
+        return Err(Error::TruncatedSection { section, offset });
+    }
+    let length = (usize::from(buf[0]) << 16) + (usize::from(buf[1]) << 8) + usize::from(buf[2]);
+    if length < min {
+        return Err(Error::InvalidSectionLength {
+            section,
+            offset,
+            length,
+        });
+    }
+    if buf.len() < length {
+        return Err(Error::TruncatedSection { section, offset });
+    }
+    Ok(length)
+}
+
+/// Reserved bytes must be set to zero
+/// Flags in `buf[i]`, with the reserved bits outside `mask` cleared
+///
+/// Reserved bits set are an error unless the checks are lenient.
+pub(crate) fn flags(
+    buf: &[u8],
+    section: u8,
+    offset: usize,
+    i: usize,
+    mask: u8,
+    checks: &mut Checks,
+) -> Result<u8, Error> {
+    let value = buf[i];
+    if value & !mask != 0 {
+        checks.check(Err(Error::InvalidFlags {
+            section,
+            offset: offset + i,
+            value,
+        }))?;
+    }
+    Ok(value & mask)
+}
+
+pub(crate) fn reserved_byte(buf: &[u8], section: u8, offset: usize, i: usize) -> Result<(), Error> {
+    match buf[i] {
+        0 => Ok(()),
+        value => Err(Error::ReservedNotZero {
+            section,
+            offset: offset + i,
+            value,
+        }),
+    }
+}
+
+/// Descriptor, as F, X and Y
+///
+/// F tells the kind of descriptor: 0 for an element of Table B, 1 for a
+/// replication, 2 for an operator and 3 for a sequence of Table D. X and Y
+/// are the class and entry for elements and sequences.
+///
+/// Descriptors are written as "F-XX-YYY", and parsed from that form or from
+/// "FXXYYY":
+///
+/// ```
+/// use bufr::Descriptor;
+///
+/// let descriptor: Descriptor = "301011".parse()?;
+/// assert_eq!(descriptor, "3-01-011".parse()?);
+/// assert_eq!((descriptor.f(), descriptor.x(), descriptor.y()), (3, 1, 11));
+/// assert_eq!(descriptor.to_string(), "3-01-011");
+/// # Ok::<(), bufr::Error>(())
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Descriptor {
     f: u8,
     x: u8,
     y: u8,
 }
 
+impl fmt::Display for Descriptor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}-{:02}-{:03}", self.f, self.x, self.y)
+    }
+}
+
+impl std::str::FromStr for Descriptor {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || Error::InvalidDescriptor(s.to_string());
+        let code = match s.as_bytes() {
+            [_, b'-', _, _, b'-', _, _, _] => s.replace('-', ""),
+            _ => s.to_string(),
+        };
+        if code.len() != 6 || !code.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(invalid());
+        }
+        let f = code[..1].parse().map_err(|_| invalid())?;
+        let x = code[1..3].parse().map_err(|_| invalid())?;
+        let y = code[3..].parse().map_err(|_| invalid())?;
+        if f > 3 || x > 63 {
+            return Err(invalid());
+        }
+        Ok(Descriptor { f, x, y })
+    }
+}
+
 /// Parses a descriptor from a BUFR message.
 ///
 /// A Descriptor has 3 fields:
@@ -380,6 +937,47 @@ fn parse_descriptor(buf: [u8; 2]) -> Descriptor {
 }
 
 impl Descriptor {
+    /// Create a descriptor from its F, X and Y parts
+    pub const fn new(f: u8, x: u8, y: u8) -> Descriptor {
+        Descriptor { f, x, y }
+    }
+
+    /// Kind of descriptor, from 0 to 3
+    pub fn f(&self) -> u8 {
+        self.f
+    }
+
+    /// Class of an element or a sequence, or what a replication or an
+    /// operator applies to
+    pub fn x(&self) -> u8 {
+        self.x
+    }
+
+    /// Entry within the class, or the parameter of a replication or an
+    /// operator
+    pub fn y(&self) -> u8 {
+        self.y
+    }
+
+    /// Table B entry of an element descriptor
+    pub fn element<'t, T: TableProvider + ?Sized>(
+        &self,
+        tables: &'t T,
+    ) -> Option<&'t ElementDescriptor> {
+        match self.f {
+            0 => tables.element(self.x, self.y),
+            _ => None,
+        }
+    }
+
+    /// Descriptors of a sequence descriptor, from Table D
+    pub fn sequence<T: TableProvider + ?Sized>(&self, tables: &T) -> Option<Vec<Descriptor>> {
+        match self.f {
+            3 => tables.sequence(self.x, self.y),
+            _ => None,
+        }
+    }
+
     pub fn encode<W: std::io::Write>(&self, wtr: &mut W) -> Result<usize, Error> {
         wtr.write_u8((self.f << 6) + self.x)?;
         wtr.write_u8(self.y)?;
@@ -389,7 +987,9 @@ impl Descriptor {
 
 #[cfg(test)]
 mod tests {
-    use super::{parse_descriptor, BufferReader, Descriptor};
+    #[cfg(feature = "wmo-35")]
+    use super::Tables;
+    use super::{parse_descriptor, BufferReader, Descriptor, Error};
 
     #[test]
     fn encode_descriptor_1() {
@@ -445,6 +1045,45 @@ mod tests {
         );
     }
 
+    #[test]
+    fn descriptor_from_str() {
+        let expected = Descriptor { f: 3, x: 1, y: 11 };
+        assert_eq!("301011".parse::<Descriptor>().unwrap(), expected);
+        assert_eq!("3-01-011".parse::<Descriptor>().unwrap(), expected);
+        assert_eq!(expected.to_string(), "3-01-011");
+        assert_eq!(
+            "0-63-255".parse::<Descriptor>().unwrap(),
+            Descriptor::new(0, 63, 255)
+        );
+
+        for code in [
+            "", "30101", "3010111", "3-1-011", "401001", "064001", "0-01-0a1",
+        ] {
+            assert!(
+                matches!(code.parse::<Descriptor>(), Err(Error::InvalidDescriptor(_))),
+                "{}",
+                code
+            );
+        }
+    }
+
+    #[cfg(feature = "wmo-35")]
+    #[test]
+    fn descriptor_lookup() {
+        let tables = Tables::embedded();
+        let latitude = Descriptor::new(0, 5, 1);
+        assert_eq!(latitude.element(&tables).unwrap().data_width(), 25);
+        assert!(latitude.sequence(&tables).is_none());
+
+        let sequence = Descriptor::new(3, 1, 2).sequence(&tables).unwrap();
+        assert_eq!(sequence[0], Descriptor::new(0, 1, 3));
+        assert!(Descriptor::new(3, 1, 2).element(&tables).is_none());
+
+        let mut descriptors = vec![sequence[2], latitude, sequence[0]];
+        descriptors.sort();
+        assert_eq!(descriptors, vec![sequence[0], sequence[2], latitude]);
+    }
+
     #[test]
     fn parse_descriptor_1() {
         let test_data = [0, 0];
@@ -477,14 +1116,14 @@ mod tests {
 
     #[test]
     fn test_buffer_reader_zero() {
-        let buffer = vec![];
+        let buffer: [u8; 0] = [];
         let mut reader = BufferReader::new(&buffer[..]);
         reader.consume(0).unwrap();
     }
 
     #[test]
     fn test_buffer_reader_1() {
-        let buffer = vec![0b11110000, 0b00001111];
+        let buffer = [0b11110000, 0b00001111];
         let mut reader = BufferReader::new(&buffer[..]);
         assert_eq!(reader.consume(4).unwrap(), &[0b0000_1111]);
         assert_eq!(reader.consume(8).unwrap(), &[0b0000_0000]);
@@ -497,14 +1136,12 @@ struct BufferReader<'a> {
 }
 
 impl<'a> BufferReader<'a> {
-    #[allow(dead_code)]
     fn new(buffer: &'a [u8]) -> Self {
         Self {
             buffer: bitreader::BitReader::new(buffer),
         }
     }
 
-    #[allow(dead_code)]
     /// offset in bits !!!!
     fn consume(&mut self, width: usize) -> Result<Vec<u8>, Error> {
         let chunks = width / 8;
@@ -512,79 +1149,40 @@ impl<'a> BufferReader<'a> {
 
         self.buffer
             .read_u8_slice(&mut result[..])
-            .expect("Width too large");
+            .map_err(|_| Error::DataTooShort)?;
 
         if width > (chunks * 8) {
             let remainder = self
                 .buffer
                 .read_u8(width as u8 % 8)
-                .expect("Width too large");
+                .map_err(|_| Error::DataTooShort)?;
             result.push(remainder);
         };
 
         Ok(result)
     }
-}
 
-#[allow(dead_code)]
-fn width_value_from_table(x: u8, y: u8) -> usize {
-    match (x, y) {
-        (1, 27) => 80,
-        (5, 2) => 2,
-        (6, 2) => 2,
-        (8, 5) => 4,
-        (19, 5) => 9,
-        (19, 6) => 14,
-        (19, 107) => 4,
-        (19, 108) => 3,
-        (19, 109) => 4,
-        (19, 110) => 4,
-        (19, 111) => 7,
-        (19, 112) => 7,
-        (19, 113) => 4,
-        (19, 114) => 7,
-        (19, 115) => 6,
-        (19, 116) => 7,
-        (19, 117) => 3,
-        (19, 118) => 7,
-        (19, 119) => 3,
-        (19, 150) => 32,
-        _ => unimplemented!(),
+    /// Number of bits read so far
+    fn position(&self) -> usize {
+        self.buffer.position() as usize
+    }
+
+    /// Move past bits without reading them
+    fn skip(&mut self, width: usize) -> Result<(), Error> {
+        self.buffer
+            .skip(width as u64)
+            .map_err(|_| Error::DataTooShort)
     }
-}
 
-#[derive(Debug, PartialEq)]
-enum BUFRUnit {
-    Numeric,
-    CodeTable,
-    FlagTable,
-    CCITTIA5,
-    Year,           // a
-    Month,          // mon
-    Day,            // d
-    Hour,           // h
-    Minute,         // min
-    Second,         // s
-    Degree,         // deg
-    MeterPerSecond, // m/s
-    DegreeTrue,
-    CC1,
-    CC12,
-    CC11,
-    CC14,
-    CodeTableOriginator,
-    Meter,
-    Kelvin,                     // K
-    Hertz,                      // Hz
-    Kilogram,                   // kg
-    KilogramPerLiter,           // kg l-1
-    KilogramPerCubicMeter,      // kg m-3
-    SquareMeter,                // m^2
-    CubicMeter,                 // m^3
-    CubicMeterPerSecond,        // m^3
-    SquareMeterPerSquareSecond, // m2 s-2
-    Pascal,                     // Pa
-    Celsius,                    // C
+    /// Read up to 64 bits as an unsigned integer
+    fn read(&mut self, width: usize) -> Result<u64, Error> {
+        if width > 64 {
+            return Err(Error::UnsupportedDataWidth(width));
+        }
+        self.buffer
+            .read_u64(width as u8)
+            .map_err(|_| Error::DataTooShort)
+    }
 }
 
 /*
@@ -607,14 +1205,15 @@ Next test cases:
 1111_1111_1111_0000
 */
 
-#[cfg(test)]
+#[cfg(all(test, feature = "wmo-35"))]
 mod test_magic {
-    use super::{tables::TABLE_F0, BufferReader};
+    use super::{BufferReader, TableProvider, Tables};
 
     #[test]
     // 0-04-031: width 8, scale 0, reference 0
     fn width8() -> Result<(), Box<dyn std::error::Error>> {
-        let descriptor = TABLE_F0.get(&(4, 31)).expect("No descriptor");
+        let tables = Tables::embedded();
+        let descriptor = tables.element(4, 31).expect("No descriptor");
         let buffer = [0b0000_1000];
 
         let mut reader = BufferReader::new(&buffer);
@@ -630,7 +1229,8 @@ mod test_magic {
     #[test]
     // 0-04-002: width 4, scale 0, reference 0
     fn width4() -> Result<(), Box<dyn std::error::Error>> {
-        let descriptor = TABLE_F0.get(&(4, 2)).expect("No descriptor");
+        let tables = Tables::embedded();
+        let descriptor = tables.element(4, 2).expect("No descriptor");
         let buffer = [0b0000_1000];
 
         let mut reader = BufferReader::new(&buffer);
@@ -646,7 +1246,8 @@ mod test_magic {
     #[test]
     // 0-04-001: width 12, scale 0, reference 0
     fn width12() -> Result<(), Box<dyn std::error::Error>> {
-        let descriptor = TABLE_F0.get(&(4, 1)).expect("No descriptor");
+        let tables = Tables::embedded();
+        let descriptor = tables.element(4, 1).expect("No descriptor");
         let buffer = [0b0000_1111, 0b0000_1111];
 
         let mut reader = BufferReader::new(&buffer);
@@ -659,7 +1260,8 @@ mod test_magic {
     #[test]
     // 0-04-004: width 5, scale 0, reference 0
     fn width_5x2() -> Result<(), Box<dyn std::error::Error>> {
-        let descriptor = TABLE_F0.get(&(4, 4)).expect("No descriptor");
+        let tables = Tables::embedded();
+        let descriptor = tables.element(4, 4).expect("No descriptor");
         let buffer = [0b0000_1000, 0b0100_1111];
         let mut reader = BufferReader::new(&buffer);
 
@@ -675,15 +1277,28 @@ mod test_magic {
 
 // scale: The power of 10 by which the element has been multiplied prior to encoding.
 // reference: A number to be subtracted from the element, after scaling (if any), and prior to encoding.
-#[derive(Debug, PartialEq)]
-struct ElementDescriptor {
-    name: String,
+/// Table B entry of an element descriptor (F=0)
+#[derive(Clone, CopyGetters, Getters, Debug, PartialEq)]
+pub struct ElementDescriptor {
+    /// Borrowed for the tables embedded in the crate
+    name: Cow<'static, str>,
+    #[getset(get = "pub")]
     unit: BUFRUnit,
+    #[getset(get_copy = "pub")]
     scale: i32,
+    #[getset(get_copy = "pub")]
     reference_value: i32,
+    /// In bits
+    #[getset(get_copy = "pub")]
     data_width: u16,
 }
 
+impl ElementDescriptor {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
 // Testing WIP
 // Expand a sequence descriptor, i.e. F=3.
 // Note that it can contain another F=3
@@ -0,0 +1,371 @@
+//! NCEP PrepBUFR and data dump files
+//!
+//! These files are written by NCEPLIBS-bufr as Fortran unformatted
+//! sequential files: every message is a record between two 4-byte markers
+//! holding its length, in the byte order of the machine that wrote it.
+//!
+//! Their first messages, of data category 11, hold the local tables (the
+//! "DX tables") needed by the following ones. Section 4 of these messages
+//! lists, as ASCII text:
+//!
+//! - 1 byte with the number of Table A entries, then 67 characters for
+//!   each: a 3-digit number, the mnemonic, a blank and the description
+//! - 1 byte with the number of Table B entries, then 112 characters for
+//!   each: the FXY, the 8-character mnemonic, a blank, 55 characters of
+//!   description, 24 of unit, then the scale, reference value and data
+//!   width in 4, 11 and 3 characters
+//! - 1 byte with the number of Table D entries, then for each 70
+//!   characters like Table B, 1 byte with the number of descriptors and
+//!   the FXY of each descriptor, in 6 characters
+
+use std::borrow::Cow;
+use std::convert::TryInto;
+
+use crate::reader::{messages_with, Messages};
+use crate::table_messages::TABLES_CATEGORY;
+use crate::tables::{parse_descriptor, parse_fxy, TableF0, TableF3, F3};
+use crate::{decode_with, BUFRUnit, DecodeOptions, ElementDescriptor, Error, Message, Tables};
+
+/// Originating centre of NCEP files
+const NCEP: u16 = 7;
+
+const TABLE_A_LEN: usize = 67;
+const TABLE_B_LEN: usize = 112;
+const TABLE_D_LEN: usize = 70;
+const FXY_LEN: usize = 6;
+
+/// Strip the Fortran record markers around the messages of a file
+///
+/// Files without markers are returned as they are. Records are expected
+/// to start with a message, as written by NCEPLIBS-bufr.
+pub fn strip_fortran_records(buf: &[u8]) -> Result<Cow<'_, [u8]>, Error> {
+    if buf.get(4..8) != Some(b"BUFR") {
+        return Ok(Cow::Borrowed(buf));
+    }
+    let big_endian = record_length(buf, 0, true)
+        .and_then(|n| buf.get(4 + n..8 + n))
+        .map(|end| end == &buf[..4])
+        == Some(true);
+
+    let mut unblocked = Vec::with_capacity(buf.len());
+    let mut offset = 0;
+    while offset < buf.len() {
+        let length = record_length(buf, offset, big_endian).ok_or(Error::InvalidRecord(offset))?;
+        let end = offset + 4 + length;
+        match buf.get(end..end + 4) {
+            Some(marker) if marker == &buf[offset..offset + 4] => {}
+            _ => return Err(Error::InvalidRecord(end)),
+        }
+        unblocked.extend_from_slice(&buf[offset + 4..end]);
+        offset = end + 4;
+    }
+    Ok(Cow::Owned(unblocked))
+}
+
+/// Length of the record whose marker starts at `offset`
+fn record_length(buf: &[u8], offset: usize, big_endian: bool) -> Option<usize> {
+    let marker: [u8; 4] = buf.get(offset..offset + 4)?.try_into().ok()?;
+    let length = if big_endian {
+        u32::from_be_bytes(marker)
+    } else {
+        u32::from_le_bytes(marker)
+    };
+    Some(length as usize)
+}
+
+/// Whether a message holds NCEP DX tables rather than data
+pub fn is_dx(message: &Message) -> bool {
+    message.section1().center() == NCEP && message.section1().data_category() == TABLES_CATEGORY
+}
+
+/// Section 4 of a DX table message, read as text
+struct DxReader<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> DxReader<'a> {
+    fn count(&mut self) -> Result<usize, Error> {
+        let n = *self.data.get(self.offset).ok_or_else(truncated)?;
+        self.offset += 1;
+        Ok(usize::from(n))
+    }
+
+    fn text(&mut self, length: usize) -> Result<&'a str, Error> {
+        let text = self
+            .data
+            .get(self.offset..self.offset + length)
+            .ok_or_else(truncated)?;
+        self.offset += length;
+        std::str::from_utf8(text)
+            .ok()
+            .filter(|text| text.is_ascii())
+            .ok_or_else(|| Error::InvalidTable("DX table is not ASCII".to_string()))
+    }
+}
+
+fn truncated() -> Error {
+    Error::InvalidTable("DX table message ends early".to_string())
+}
+
+/// Number in a DX table field, where the sign may be apart from the digits
+fn number<T: std::str::FromStr>(field: &str) -> Result<T, Error> {
+    let digits: String = field.split_whitespace().collect();
+    digits
+        .parse()
+        .map_err(|_| Error::InvalidTable(format!("Invalid number in DX table: {}", field)))
+}
+
+/// Description of a DX table entry, or its mnemonic when there is none
+fn description(entry: &str) -> String {
+    match entry[15..70].trim() {
+        "" => entry[6..14].trim().to_string(),
+        description => description.to_string(),
+    }
+}
+
+/// Parses section 4 of a DX table message
+pub(crate) fn parse_dx(data: &[u8]) -> Result<(TableF0, TableF3), Error> {
+    let mut reader = DxReader { data, offset: 0 };
+    let mut f0 = TableF0::default();
+    let mut f3 = TableF3::default();
+
+    // Table A only names the sequences that messages start with
+    for _ in 0..reader.count()? {
+        reader.text(TABLE_A_LEN)?;
+    }
+
+    for _ in 0..reader.count()? {
+        let entry = reader.text(TABLE_B_LEN)?;
+        let (_, x, y) = parse_fxy(&entry[..6])?;
+        let element = ElementDescriptor {
+            name: description(entry).into(),
+            unit: BUFRUnit::from(&entry[70..94]),
+            scale: number(&entry[94..98])?,
+            reference_value: number(&entry[98..109])?,
+            data_width: number(&entry[109..112])?,
+        };
+        f0.insert((x, y), element);
+    }
+
+    for _ in 0..reader.count()? {
+        let entry = reader.text(TABLE_D_LEN)?;
+        let (_, x, y) = parse_fxy(&entry[..6])?;
+        let descriptors = (0..reader.count()?)
+            .map(|_| parse_descriptor(reader.text(FXY_LEN)?))
+            .collect::<Result<_, _>>()?;
+        let sequence = F3 {
+            descriptors,
+            title: Some(description(entry)),
+        };
+        f3.insert((x, y), sequence);
+    }
+    Ok((f0, f3))
+}
+
+/// Iterator over the data messages of an NCEP file
+///
+/// DX table messages aren't returned: the tables of each run of them are
+/// used as local tables for the following messages, until the next run.
+/// The buffer must not have Fortran record markers, see
+/// `strip_fortran_records`.
+pub struct NcepMessages<'a> {
+    buf: &'a [u8],
+    messages: Messages<'a>,
+    options: DecodeOptions,
+    // Options with the tables of the last run of DX messages
+    current: DecodeOptions,
+    dx: Tables,
+    in_dx: bool,
+}
+
+/// Iterate over the data messages in `buf`, the content of an NCEP file
+/// without its Fortran record markers
+pub fn ncep_messages<'a>(buf: &'a [u8], options: &DecodeOptions) -> NcepMessages<'a> {
+    NcepMessages {
+        buf,
+        messages: messages_with(buf, &options.clone().decode_data(false)),
+        options: options.clone(),
+        current: options.clone(),
+        dx: Tables::default(),
+        in_dx: false,
+    }
+}
+
+impl<'a> NcepMessages<'a> {
+    /// Index of the last message returned, DX table messages included
+    pub fn index(&self) -> usize {
+        self.messages.index()
+    }
+
+    /// Position in the buffer of the last message returned
+    pub fn offset(&self) -> usize {
+        self.messages.offset()
+    }
+
+    /// Tables of the last run of DX table messages
+    pub fn dx_tables(&self) -> &Tables {
+        &self.dx
+    }
+}
+
+impl<'a> Iterator for NcepMessages<'a> {
+    type Item = Result<Message<'a>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let mut message = match self.messages.next()? {
+                Ok(message) => message,
+                Err(e) => return Some(Err(e)),
+            };
+            let (index, offset) = (self.messages.index(), self.messages.offset());
+
+            if is_dx(&message) {
+                if !self.in_dx {
+                    self.dx = Tables::default();
+                    self.in_dx = true;
+                }
+                if let Err(e) = self.dx.add_dx(&message) {
+                    return Some(Err(e.with_message(index, offset)));
+                }
+                continue;
+            }
+
+            if self.in_dx {
+                self.in_dx = false;
+                let mut tables = match self.options.table_set() {
+                    Ok(tables) => tables.clone(),
+                    Err(e) => return Some(Err(e.with_message(index, offset))),
+                };
+                tables.set_default_local(self.dx.clone());
+                self.current = self.options.clone().tables(tables);
+            }
+            if self.current.decode_data {
+                return Some(
+                    decode_with(&self.buf[offset..], &self.current)
+                        .map_err(|e| e.with_message(index, offset)),
+                );
+            }
+            message.options = self.current.clone();
+            return Some(Ok(message));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_ncep {
+    use super::strip_fortran_records;
+    use crate::Error;
+    #[cfg(feature = "wmo-35")]
+    use {
+        super::ncep_messages,
+        crate::{decode, TableProvider, Value},
+    };
+
+    // Edition 4 message with one subset of the descriptor `fxy`
+    fn message(category: u8, fxy: [u8; 2], data: &[u8]) -> Vec<u8> {
+        let total = 8 + 22 + 9 + 4 + data.len() + 4;
+        let mut buf = b"BUFR".to_vec();
+        buf.extend_from_slice(&(total as u32).to_be_bytes()[1..]);
+        buf.push(4);
+        buf.extend_from_slice(&[
+            0, 0, 22, 0, 0, 7, 0, 0, 0, 0, category, 0, 0, 0, 0, 7, 228, 1, 1, 0, 0, 0,
+        ]);
+        buf.extend_from_slice(&[0, 0, 9, 0, 0, 1, 128, fxy[0], fxy[1]]);
+        buf.extend_from_slice(&((4 + data.len()) as u32).to_be_bytes()[1..]);
+        buf.push(0);
+        buf.extend_from_slice(data);
+        buf.extend_from_slice(b"7777");
+        buf
+    }
+
+    fn dx_message() -> Vec<u8> {
+        let mut data = vec![1];
+        data.extend(format!("{:3}{:8} {:55}", "001", "NC000001", "TEST MESSAGES").bytes());
+        data.push(2);
+        for (fxy, mnemonic, name, unit, scale, width) in &[
+            ("063001", "POB", "PRESSURE", "MB", 1, 14),
+            ("063002", "TYP", "REPORT TYPE", "CODE TABLE", 0, 9),
+        ] {
+            let entry = format!(
+                "{}{:8} {:55}{:24}{:>4}{:>11}{:>3}",
+                fxy, mnemonic, name, unit, scale, 0, width
+            );
+            data.extend(entry.bytes());
+        }
+        data.push(1);
+        data.extend(format!("{}{:8} {:55}", "363001", "NC000001", "").bytes());
+        data.push(2);
+        data.extend(b"063001063002");
+        message(11, [0, 0], &data)
+    }
+
+    fn data_message() -> Vec<u8> {
+        // POB = 1013.2 in 14 bits, then TYP = 120 in 9 bits
+        message(0, [255, 1], &[0x9e, 0x50, 0xf0])
+    }
+
+    #[test]
+    fn fortran_records() {
+        let dx = dx_message();
+        let data = data_message();
+        let mut expected = dx.clone();
+        expected.extend(&data);
+
+        for big_endian in &[true, false] {
+            let mut buf = vec![];
+            for record in &[&dx, &data] {
+                let n = record.len() as u32;
+                let marker = if *big_endian {
+                    n.to_be_bytes()
+                } else {
+                    n.to_le_bytes()
+                };
+                buf.extend_from_slice(&marker);
+                buf.extend_from_slice(record);
+                buf.extend_from_slice(&marker);
+            }
+            assert_eq!(strip_fortran_records(&buf).unwrap().as_ref(), &expected[..]);
+
+            buf.pop();
+            assert!(matches!(
+                strip_fortran_records(&buf),
+                Err(Error::InvalidRecord(_))
+            ));
+        }
+        assert_eq!(
+            strip_fortran_records(&expected).unwrap().as_ref(),
+            &expected[..]
+        );
+    }
+
+    #[cfg(feature = "wmo-35")]
+    #[test]
+    fn dx_tables() {
+        let mut buf = dx_message();
+        buf.extend(data_message());
+        buf.extend(data_message());
+
+        // Without the DX tables the local descriptors are unknown
+        assert!(decode(&data_message()).unwrap().subsets().is_err());
+
+        let options = crate::DecodeOptions::default();
+        let all: Vec<_> = ncep_messages(&buf, &options)
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(all.len(), 2);
+        for message in &all {
+            let subsets = message.subsets().unwrap();
+            let values: Vec<_> = subsets[0].fields().iter().map(|f| f.value()).collect();
+            assert_eq!(values, vec![&Value::Float(1013.2), &Value::Integer(120)]);
+        }
+
+        let options = options.decode_data(true);
+        let mut messages = ncep_messages(&buf, &options);
+        assert!(messages.next().unwrap().is_ok());
+        assert_eq!(messages.index(), 1);
+        let pob = messages.dx_tables().element(63, 1).unwrap();
+        assert_eq!(pob.name(), "PRESSURE");
+        assert_eq!(pob.data_width(), 14);
+    }
+}
@@ -0,0 +1,210 @@
+//! Options controlling how messages are decoded
+
+use std::sync::Arc;
+
+use crate::error::Error;
+use crate::tables::{default_table_set, TableSet};
+use crate::units::BUFRUnit;
+use crate::Descriptor;
+
+/// What to do with values set to missing (all bits set to 1)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MissingValues {
+    /// Keep them as `Value::Missing`, so every descriptor has a value
+    Keep,
+    /// Leave them out of the subsets
+    Skip,
+}
+
+/// What to do with descriptors not found in the tables
+///
+/// Their data width is unknown, so nothing after them can be decoded.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UnknownDescriptors {
+    /// Fail to decode section 4
+    Error,
+    /// Keep the values and subsets decoded before the unknown descriptor
+    Truncate,
+}
+
+/// Options for `decode_with`
+///
+/// The default is what `decode` does: strict checks, values decoded only
+/// when asked for with `Message::subsets` using the embedded tables, missing
+/// values kept and an error on unknown descriptors.
+///
+/// ```
+/// use bufr::{DecodeOptions, MissingValues};
+///
+/// let options = DecodeOptions::default()
+///     .strict(false)
+///     .max_replication(1000)
+///     .missing_values(MissingValues::Skip);
+/// ```
+#[derive(Debug, Clone)]
+pub struct DecodeOptions {
+    pub(crate) strict: bool,
+    pub(crate) max_replication: usize,
+    pub(crate) max_depth: usize,
+    pub(crate) max_expanded_descriptors: usize,
+    pub(crate) decode_data: bool,
+    pub(crate) missing_values: MissingValues,
+    pub(crate) unknown_descriptors: UnknownDescriptors,
+    pub(crate) tables: Option<Arc<TableSet>>,
+    output_units: Vec<(BUFRUnit, BUFRUnit)>,
+    selection: Option<Vec<Descriptor>>,
+}
+
+impl Default for DecodeOptions {
+    fn default() -> Self {
+        DecodeOptions {
+            strict: true,
+            max_replication: usize::from(u16::MAX),
+            max_depth: 32,
+            max_expanded_descriptors: 1 << 22,
+            decode_data: false,
+            missing_values: MissingValues::Keep,
+            unknown_descriptors: UnknownDescriptors::Error,
+            tables: None,
+            output_units: vec![],
+            selection: None,
+        }
+    }
+}
+
+impl DecodeOptions {
+    /// Reject non-zero reserved bytes and sections whose lengths disagree
+    ///
+    /// When false, reserved bytes are ignored, edition 4 descriptors may be
+    /// padded like edition 3, and the end section is also accepted at the
+    /// end of the total length.
+    pub fn strict(mut self, value: bool) -> Self {
+        self.strict = value;
+        self
+    }
+
+    /// Largest number of repetitions for a single replication
+    pub fn max_replication(mut self, value: usize) -> Self {
+        self.max_replication = value;
+        self
+    }
+
+    /// Largest nesting of sequences and replications
+    pub fn max_depth(mut self, value: usize) -> Self {
+        self.max_depth = value;
+        self
+    }
+
+    /// Largest number of descriptors walked to decode a data section, over
+    /// all its subsets and every repetition of replications
+    ///
+    /// Nested replications multiply their counts, so each one staying
+    /// within `max_replication` doesn't bound the work.
+    pub fn max_expanded_descriptors(mut self, value: usize) -> Self {
+        self.max_expanded_descriptors = value;
+        self
+    }
+
+    /// Decode the values of section 4 with the rest of the message
+    ///
+    /// Errors in the data are then reported by `decode_with` rather than by
+    /// `Message::subsets`.
+    pub fn decode_data(mut self, value: bool) -> Self {
+        self.decode_data = value;
+        self
+    }
+
+    /// Keep or skip missing values
+    pub fn missing_values(mut self, value: MissingValues) -> Self {
+        self.missing_values = value;
+        self
+    }
+
+    /// Fail or stop on unknown descriptors
+    pub fn unknown_descriptors(mut self, value: UnknownDescriptors) -> Self {
+        self.unknown_descriptors = value;
+        self
+    }
+
+    /// Tables to choose from, instead of the embedded ones
+    pub fn tables(mut self, value: TableSet) -> Self {
+        self.tables = Some(Arc::new(value));
+        self
+    }
+
+    /// Convert the values of elements in one unit to another unit
+    ///
+    /// Fails if the units don't have the same dimension. Converted values
+    /// are always `Value::Float`.
+    ///
+    /// ```
+    /// use bufr::{BUFRUnit, DecodeOptions};
+    ///
+    /// let options = DecodeOptions::default()
+    ///     .output_unit(BUFRUnit::Kelvin, BUFRUnit::Celsius)?
+    ///     .output_unit(BUFRUnit::Pascal, BUFRUnit::Hectopascal)?;
+    /// assert!(options.output_unit(BUFRUnit::Pascal, BUFRUnit::Knot).is_err());
+    /// # Ok::<(), bufr::Error>(())
+    /// ```
+    pub fn output_unit(mut self, from: BUFRUnit, to: BUFRUnit) -> Result<Self, Error> {
+        from.convert(0.0, &to)?;
+        self.output_units.retain(|(unit, _)| *unit != from);
+        self.output_units.push((from, to));
+        Ok(self)
+    }
+
+    /// Decode only the values of these descriptors
+    ///
+    /// The bits of all other values are skipped without decoding them, so
+    /// the subsets only have fields for the selected descriptors. Counts of
+    /// delayed replications are still read to find where values are.
+    ///
+    /// ```
+    /// use bufr::{DecodeOptions, Descriptor};
+    ///
+    /// // Latitude, longitude, time and sea temperature
+    /// let options = DecodeOptions::default().select(
+    ///     ["005001", "006001", "004004", "004005", "022043"]
+    ///         .iter()
+    ///         .map(|code| code.parse::<Descriptor>())
+    ///         .collect::<Result<Vec<_>, _>>()?,
+    /// );
+    /// # Ok::<(), bufr::Error>(())
+    /// ```
+    pub fn select<I: IntoIterator<Item = Descriptor>>(mut self, descriptors: I) -> Self {
+        let mut selection: Vec<_> = descriptors.into_iter().collect();
+        selection.sort();
+        selection.dedup();
+        self.selection = Some(selection);
+        self
+    }
+
+    pub(crate) fn table_set(&self) -> Result<&TableSet, Error> {
+        match self.tables.as_deref() {
+            Some(tables) => Ok(tables),
+            None => default_table_set(),
+        }
+    }
+
+    /// Unit requested for the values of elements in `unit`
+    pub(crate) fn output_unit_for(&self, unit: &BUFRUnit) -> Option<&BUFRUnit> {
+        self.output_units
+            .iter()
+            .find(|(from, _)| from == unit)
+            .map(|(_, to)| to)
+    }
+
+    /// Whether the values of a descriptor are kept in the subsets
+    pub(crate) fn selects(&self, descriptor: &Descriptor) -> bool {
+        match &self.selection {
+            Some(selection) => selection.binary_search(descriptor).is_ok(),
+            None => true,
+        }
+    }
+
+    /// Whether an error in section 4 only ends the data early
+    pub(crate) fn truncates(&self, error: &Error) -> bool {
+        self.unknown_descriptors == UnknownDescriptors::Truncate
+            && matches!(error.kind(), Error::UnknownDescriptor(..))
+    }
+}
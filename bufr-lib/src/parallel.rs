@@ -0,0 +1,198 @@
+//! Decoding the messages of a buffer on several threads
+
+use rayon::prelude::*;
+
+use crate::error::Error;
+use crate::reader::Framing;
+use crate::{decode_with, DecodeOptions, Message};
+
+/// Decode all the messages in `buf` in parallel
+///
+/// See `decode_all_with`.
+pub fn decode_all(buf: &[u8]) -> Vec<Result<Message<'_>, Error>> {
+    decode_all_with(buf, &DecodeOptions::default())
+}
+
+/// Decode all the messages in `buf` in parallel, with `options`
+///
+/// The buffer is first split into messages by the total length in their
+/// section 0, then each message is decoded on the rayon thread pool. The
+/// results are the same as those of `messages_with`, in the same order and
+/// with the same indices in their errors: after a message that can't be
+/// decoded, the search for the next one restarts right after its "BUFR"
+/// marker, and any message found that way is decoded on the calling thread.
+/// The tables and decode plans of the options are shared by all threads.
+///
+/// Values are only decoded on the threads with `DecodeOptions::decode_data`.
+///
+/// ```
+/// # let buf = [];
+/// let options = bufr::DecodeOptions::default().decode_data(true);
+/// for message in bufr::decode_all_with(&buf, &options) {
+///     let message = message?;
+/// }
+/// # Ok::<(), bufr::Error>(())
+/// ```
+pub fn decode_all_with<'a>(
+    buf: &'a [u8],
+    options: &DecodeOptions,
+) -> Vec<Result<Message<'a>, Error>> {
+    // Section 0 gives the length of the whole message, trusted only when
+    // it ends with the end section
+    let mut starts = vec![];
+    let mut framing = Framing::default();
+    while let Some(start) = framing.start(buf) {
+        let length = match buf.get(start + 4..start + 7) {
+            Some(&[a, b, c]) => (usize::from(a) << 16) | (usize::from(b) << 8) | usize::from(c),
+            _ => 0,
+        };
+        let end = start + length;
+        let intact = length >= 8 && buf.get(end - 4..end) == Some(&b"7777"[..]);
+        framing.advance(start, if intact { Some(length) } else { None });
+        starts.push(start);
+    }
+    let mut decoded: Vec<_> = starts
+        .par_iter()
+        .map(|&start| Some(decode_with(&buf[start..], options)))
+        .collect();
+
+    // Framed again by the results, which only differs from the lengths
+    // after messages that can't be decoded
+    let mut results = vec![];
+    let mut framing = Framing::default();
+    while let Some(start) = framing.start(buf) {
+        let result = starts
+            .binary_search(&start)
+            .ok()
+            .and_then(|i| decoded[i].take())
+            .unwrap_or_else(|| decode_with(&buf[start..], options));
+        let length = result.as_ref().ok().map(|m| m.total_length() as usize);
+        framing.advance(start, length);
+        let index = results.len();
+        results.push(result.map_err(|e| e.with_message(index, start)));
+    }
+    results
+}
+
+#[cfg(all(test, feature = "wmo-35"))]
+mod test_parallel {
+    use super::decode_all_with;
+    use crate::identification::{Section1, Section1v4Builder};
+    use crate::{messages_with, DecodeOptions, Descriptor, MessageBuilder, Value};
+
+    fn message(day: u8) -> Vec<u8> {
+        let section1 = Section1v4Builder::default()
+            .master_table(0)
+            .sub_center(0)
+            .center(0)
+            .update_version(0)
+            .optional_section(false)
+            .data_category(0)
+            .data_subcategory(0)
+            .local_subcategory(0)
+            .master_table_version(35)
+            .local_table_version(0)
+            .year(2020)
+            .month(10)
+            .day(day)
+            .hour(0)
+            .minute(0)
+            .second(0)
+            .build()
+            .unwrap();
+        let message = MessageBuilder::default()
+            .section1(Section1::V4(section1))
+            .descriptors(vec![Descriptor::new(3, 1, 11)])
+            .subset(vec![
+                Value::Integer(2020),
+                Value::Integer(10),
+                Value::Integer(i64::from(day)),
+            ])
+            .build()
+            .unwrap();
+        let mut buf = vec![];
+        message.encode(&mut buf).unwrap();
+        buf
+    }
+
+    #[test]
+    fn same_as_sequential() {
+        let mut buf = vec![];
+        for day in 1..=28 {
+            buf.extend(message(day));
+            buf.extend_from_slice(b"\n");
+        }
+        // Corrupt the end section of the 5th message
+        let n = message(1).len() + 1;
+        buf[5 * n - 2] = b'0';
+
+        let options = DecodeOptions::default().decode_data(true);
+        let parallel = decode_all_with(&buf, &options);
+        let sequential: Vec<_> = messages_with(&buf, &options).collect();
+        assert_eq!(parallel.len(), 28);
+        assert_eq!(parallel.len(), sequential.len());
+        for (p, s) in parallel.iter().zip(&sequential) {
+            match (p, s) {
+                (Ok(p), Ok(s)) => assert_eq!(p.subsets().unwrap(), s.subsets().unwrap()),
+                (Err(p), Err(s)) => assert_eq!(p.to_string(), s.to_string()),
+                _ => panic!("{:?} and {:?}", p, s),
+            }
+        }
+        assert!(parallel[4].is_err());
+        let subsets = parallel[27].as_ref().unwrap().subsets().unwrap();
+        assert_eq!(subsets[0].fields()[2].value(), &Value::Integer(28));
+    }
+
+    #[test]
+    fn inner_marker() {
+        let mut buf = vec![];
+        for day in 1..=28 {
+            buf.extend(message(day));
+        }
+        // The 5th message keeps its length and end section, but its
+        // section 1 is replaced by a "BUFR" marker
+        let n = message(1).len();
+        buf[4 * n + 8..4 * n + 12].copy_from_slice(b"BUFR");
+
+        let options = DecodeOptions::default().decode_data(true);
+        let parallel = decode_all_with(&buf, &options);
+        let sequential: Vec<_> = messages_with(&buf, &options).collect();
+        assert_eq!(parallel.len(), 29);
+        assert_eq!(parallel.len(), sequential.len());
+        for (p, s) in parallel.iter().zip(&sequential) {
+            match (p, s) {
+                (Ok(p), Ok(s)) => assert_eq!(p.subsets().unwrap(), s.subsets().unwrap()),
+                (Err(p), Err(s)) => {
+                    assert_eq!(p.to_string(), s.to_string());
+                    let (p, s) = (p.location().unwrap(), s.location().unwrap());
+                    assert_eq!(p.message(), s.message());
+                    assert_eq!(p.message_offset(), s.message_offset());
+                }
+                _ => panic!("{:?} and {:?}", p, s),
+            }
+        }
+        let inner = parallel[5].as_ref().unwrap_err().location().unwrap();
+        assert_eq!(inner.message(), Some(5));
+        assert_eq!(inner.message_offset(), Some(4 * n + 8));
+        let subsets = parallel[28].as_ref().unwrap().subsets().unwrap();
+        assert_eq!(subsets[0].fields()[2].value(), &Value::Integer(28));
+    }
+
+    #[test]
+    fn truncated() {
+        // The second message is cut, so its length overlaps the third one
+        let mut buf = message(1);
+        let cut = message(2);
+        buf.extend_from_slice(&cut[..cut.len() - 10]);
+        buf.extend(message(3));
+
+        let options = DecodeOptions::default().decode_data(true);
+        let parallel = decode_all_with(&buf, &options);
+        let sequential: Vec<_> = messages_with(&buf, &options).collect();
+        assert_eq!(parallel.len(), 3);
+        assert_eq!(parallel.len(), sequential.len());
+        assert!(parallel[1].is_err());
+        let subsets = parallel[2].as_ref().unwrap().subsets().unwrap();
+        assert_eq!(subsets[0].fields()[2].value(), &Value::Integer(3));
+    }
+}
@@ -0,0 +1,226 @@
+//! Tolerant decoding, keeping whatever could be decoded
+
+use std::fmt;
+
+use getset::{CopyGetters, Getters};
+
+use crate::data::Subset;
+use crate::error::Error;
+use crate::identification::Section1;
+use crate::options::DecodeOptions;
+use crate::{Message, Section2, Section3, Section4};
+
+/// How bad a problem is
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    /// Tolerated, the decoded content is still complete
+    Warning,
+    /// Part of the message could not be decoded
+    Error,
+}
+
+/// A problem found while decoding a message
+#[derive(CopyGetters, Getters, Debug)]
+pub struct Diagnostic {
+    #[getset(get_copy = "pub")]
+    severity: Severity,
+    #[getset(get = "pub")]
+    error: Error,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.severity {
+            Severity::Warning => write!(f, "warning: {}", self.error),
+            Severity::Error => write!(f, "error: {}", self.error),
+        }
+    }
+}
+
+/// Everything that could be decoded from a message, and what went wrong
+///
+/// Sections are decoded in order up to the first one that fails. Subsets
+/// are decoded up to the first one that fails, even when the end section
+/// is wrong.
+#[derive(Debug)]
+pub struct PartialMessage<'a> {
+    pub(crate) total_length: Option<u32>,
+    pub(crate) version: Option<u8>,
+    pub(crate) section1: Option<Section1>,
+    pub(crate) section2: Option<Section2<'a>>,
+    pub(crate) section3: Option<Section3>,
+    pub(crate) section4: Option<Section4<'a>>,
+    pub(crate) subsets: Option<Vec<Subset>>,
+    pub(crate) diagnostics: Vec<Diagnostic>,
+    pub(crate) options: DecodeOptions,
+}
+
+impl<'a> PartialMessage<'a> {
+    pub(crate) fn new(options: &DecodeOptions) -> Self {
+        PartialMessage {
+            total_length: None,
+            version: None,
+            section1: None,
+            section2: None,
+            section3: None,
+            section4: None,
+            subsets: None,
+            diagnostics: vec![],
+            options: options.clone(),
+        }
+    }
+
+    pub(crate) fn push(&mut self, severity: Severity, error: Error) {
+        self.diagnostics.push(Diagnostic { severity, error });
+    }
+
+    /// Total length from section 0
+    pub fn total_length(&self) -> Option<u32> {
+        self.total_length
+    }
+
+    /// BUFR edition from section 0
+    pub fn version(&self) -> Option<u8> {
+        self.version
+    }
+
+    pub fn section1(&self) -> Option<&Section1> {
+        self.section1.as_ref()
+    }
+
+    pub fn section2(&self) -> Option<&Section2<'a>> {
+        self.section2.as_ref()
+    }
+
+    pub fn section3(&self) -> Option<&Section3> {
+        self.section3.as_ref()
+    }
+
+    pub fn section4(&self) -> Option<&Section4<'a>> {
+        self.section4.as_ref()
+    }
+
+    /// Subsets decoded before the first failing one
+    pub fn subsets(&self) -> &[Subset] {
+        self.subsets.as_deref().unwrap_or(&[])
+    }
+
+    /// Every problem found, in the order they were found
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    /// Problems that were tolerated
+    pub fn warnings(&self) -> impl Iterator<Item = &Error> {
+        self.with_severity(Severity::Warning)
+    }
+
+    /// Problems that stopped part of the decoding
+    pub fn errors(&self) -> impl Iterator<Item = &Error> {
+        self.with_severity(Severity::Error)
+    }
+
+    fn with_severity(&self, severity: Severity) -> impl Iterator<Item = &Error> {
+        self.diagnostics
+            .iter()
+            .filter(move |d| d.severity == severity)
+            .map(|d| &d.error)
+    }
+
+    /// Whether the whole message was decoded, maybe with warnings
+    pub fn is_complete(&self) -> bool {
+        self.errors().next().is_none()
+    }
+
+    /// The complete message, or the first error
+    pub fn into_message(self) -> Result<Message<'a>, Error> {
+        let mut warnings = vec![];
+        for diagnostic in self.diagnostics {
+            match diagnostic.severity {
+                Severity::Warning => warnings.push(diagnostic.error),
+                Severity::Error => return Err(diagnostic.error),
+            }
+        }
+        match (
+            self.total_length,
+            self.version,
+            self.section1,
+            self.section3,
+            self.section4,
+        ) {
+            (Some(total_length), Some(version), Some(section1), Some(section3), Some(section4)) => {
+                Ok(Message {
+                    total_length,
+                    version,
+                    section1,
+                    section2: self.section2,
+                    section3,
+                    section4,
+                    options: self.options,
+                    data: self.subsets,
+                    warnings,
+                })
+            }
+            _ => Err(Error::TruncatedMessage),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "wmo-35"))]
+mod test_partial {
+    use crate::identification::{Section1, Section1v4Builder};
+    use crate::{decode_partial, DecodeOptions, Descriptor, Error, MessageBuilder, Value};
+
+    #[test]
+    fn keep_subsets_before_error() -> Result<(), Box<dyn std::error::Error>> {
+        let section1 = Section1v4Builder::default()
+            .master_table(0)
+            .sub_center(0)
+            .center(0)
+            .update_version(0)
+            .optional_section(false)
+            .data_category(31)
+            .data_subcategory(0)
+            .local_subcategory(0)
+            .master_table_version(35)
+            .local_table_version(0)
+            .year(2020)
+            .month(10)
+            .day(6)
+            .hour(19)
+            .minute(24)
+            .second(0)
+            .build()?;
+        // Replication factor and one station name of 32 bytes per subset
+        let subset = vec![Value::Integer(1), Value::String("Cabot".to_string())];
+        let message = MessageBuilder::default()
+            .section1(Section1::V4(section1))
+            .descriptors(vec![
+                Descriptor::new(1, 1, 0),
+                Descriptor::new(0, 31, 1),
+                Descriptor::new(0, 1, 19),
+            ])
+            .subsets(vec![subset; 3])
+            .build()?;
+        let mut buf = vec![];
+        message.encode(&mut buf)?;
+
+        // Replication factor of the second subset
+        let start = 8 + 22 + 13 + 4;
+        buf[start + 33] = 255;
+
+        let partial = decode_partial(&buf, &DecodeOptions::default());
+        assert!(!partial.is_complete());
+        assert!(partial.section1().is_some());
+        assert_eq!(partial.section3().unwrap().n_subsets(), 3);
+        assert_eq!(partial.subsets().len(), 1);
+
+        let errors: Vec<_> = partial.errors().collect();
+        assert_eq!(errors.len(), 1);
+        assert!(matches!(errors[0].kind(), Error::DataTooShort));
+        assert_eq!(errors[0].location().unwrap().subset(), Some(1));
+
+        assert!(partial.into_message().is_err());
+        Ok(())
+    }
+}
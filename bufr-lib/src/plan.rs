@@ -0,0 +1,454 @@
+//! Decode plans compiled from the descriptors of section 3
+//!
+//! Walking the descriptors expands every sequence and applies every
+//! operator again for each message, and for each subset of uncompressed
+//! messages. A plan does that once: it holds the spec of each value, with
+//! its width, scale and reference value after the operators, so decoding
+//! only has to read the bits. Plans are cached in the `TableSet` they were
+//! compiled with, by descriptors and selected tables.
+//!
+//! Descriptors that can't be planned, because of an unknown descriptor or
+//! an unsupported operator for example, are walked as before, which also
+//! keeps the values decoded up to the problem.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, PoisonError, RwLock};
+
+use crate::data::{Operators, Spec};
+use crate::{DecodeOptions, Descriptor, Error, TableProvider};
+
+/// Most plans kept by a cache before it is emptied
+const MAX_PLANS: usize = 1024;
+
+/// One step of a plan
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Step {
+    /// Element, or value inserted by an operator
+    Value { descriptor: Descriptor, spec: Spec },
+    /// Expanded sequence
+    Sequence {
+        descriptor: Descriptor,
+        steps: Vec<Step>,
+    },
+    /// Replicated steps, with those of the first repetition apart when
+    /// operators within it change the following ones
+    Replication {
+        descriptor: Descriptor,
+        count: Count,
+        first: Vec<Step>,
+        rest: Option<Vec<Step>>,
+    },
+}
+
+/// Number of repetitions of a replication
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Count {
+    Fixed(usize),
+    /// Read from the data, with the descriptor and spec of the factor
+    Delayed(Descriptor, Spec),
+}
+
+impl Step {
+    pub(crate) fn descriptor(&self) -> &Descriptor {
+        match self {
+            Step::Value { descriptor, .. }
+            | Step::Sequence { descriptor, .. }
+            | Step::Replication { descriptor, .. } => descriptor,
+        }
+    }
+
+    /// Whether the step has a value of a descriptor matching `f`
+    pub(crate) fn any_value(&self, f: &dyn Fn(&Descriptor) -> bool) -> bool {
+        match self {
+            Step::Value { descriptor, .. } => f(descriptor),
+            Step::Sequence { steps, .. } => steps.iter().any(|s| s.any_value(f)),
+            Step::Replication { first, rest, .. } => {
+                let rest = rest.as_deref().unwrap_or(&[]);
+                first.iter().chain(rest).any(|s| s.any_value(f))
+            }
+        }
+    }
+
+    /// Bits taken by the step, unless it depends on the data or doesn't
+    /// fit in a `usize`
+    pub(crate) fn bit_width(&self) -> Option<usize> {
+        match self {
+            Step::Value { spec, .. } => Some(spec.width()),
+            Step::Sequence { steps, .. } => bit_width(steps),
+            Step::Replication {
+                count: Count::Fixed(n),
+                first,
+                rest,
+                ..
+            } => {
+                let rest = bit_width(rest.as_deref().unwrap_or(first))?;
+                match n.checked_sub(1) {
+                    Some(n) => bit_width(first)?.checked_add(n.checked_mul(rest)?),
+                    None => Some(0),
+                }
+            }
+            Step::Replication { .. } => None,
+        }
+    }
+}
+
+fn bit_width(steps: &[Step]) -> Option<usize> {
+    steps
+        .iter()
+        .try_fold(0usize, |width, step| width.checked_add(step.bit_width()?))
+}
+
+/// Descriptors of section 3 compiled against a set of tables
+///
+/// ```
+/// use bufr::{DecodePlan, Descriptor, MasterTables};
+///
+/// # #[cfg(feature = "wmo-35")]
+/// # {
+/// let master = MasterTables::wmo();
+/// let (_, tables) = master.select(0, 35)?;
+/// // Year, month and day
+/// let plan = DecodePlan::compile(&[Descriptor::new(3, 1, 11)], tables)?;
+/// assert_eq!(plan.bit_width(), Some(12 + 4 + 6));
+/// # }
+/// # Ok::<(), bufr::Error>(())
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecodePlan {
+    descriptors: Vec<Descriptor>,
+    steps: Vec<Step>,
+}
+
+impl DecodePlan {
+    /// Expand the descriptors and work out the spec of every value
+    ///
+    /// Fails on descriptors missing from the tables, unsupported operators,
+    /// delayed replications whose operators change what follows depending
+    /// on their count, and nesting deeper than the default
+    /// `DecodeOptions::max_depth`.
+    pub fn compile(
+        descriptors: &[Descriptor],
+        tables: &dyn TableProvider,
+    ) -> Result<DecodePlan, Error> {
+        let mut compiler = Compiler {
+            tables,
+            operators: Operators::default(),
+            sequences: vec![],
+            depth: 0,
+            max_depth: DecodeOptions::default().max_depth,
+        };
+        Ok(DecodePlan {
+            descriptors: descriptors.to_vec(),
+            steps: compiler.compile(descriptors)?,
+        })
+    }
+
+    /// Descriptors the plan was compiled from
+    pub fn descriptors(&self) -> &[Descriptor] {
+        &self.descriptors
+    }
+
+    /// Bits taken by one subset of an uncompressed message, unless it
+    /// depends on delayed replications
+    pub fn bit_width(&self) -> Option<usize> {
+        bit_width(&self.steps)
+    }
+
+    pub(crate) fn steps(&self) -> &[Step] {
+        &self.steps
+    }
+}
+
+struct Compiler<'t> {
+    tables: &'t dyn TableProvider,
+    operators: Operators,
+    // Sequences being expanded, to find those containing themselves
+    sequences: Vec<Descriptor>,
+    // Nesting of the sequences and replications being compiled
+    depth: usize,
+    max_depth: usize,
+}
+
+impl<'t> Compiler<'t> {
+    /// Compile nested descriptors, like `Walker::nested` walks them
+    fn nested(&mut self, descriptors: &[Descriptor]) -> Result<Vec<Step>, Error> {
+        if self.depth >= self.max_depth {
+            return Err(Error::DepthLimit(self.max_depth));
+        }
+        self.depth += 1;
+        let steps = self.compile(descriptors);
+        self.depth -= 1;
+        steps
+    }
+
+    fn compile(&mut self, descriptors: &[Descriptor]) -> Result<Vec<Step>, Error> {
+        let mut steps = vec![];
+        let mut i = 0;
+        while i < descriptors.len() {
+            let descriptor = descriptors[i];
+            i += 1;
+            match descriptor.f() {
+                0 => {
+                    let spec = self.operators.element(self.tables, &descriptor)?;
+                    steps.push(Step::Value { descriptor, spec });
+                }
+                1 => {
+                    let step = self.replication(descriptor, descriptors, &mut i)?;
+                    steps.push(step);
+                }
+                2 => {
+                    if let Some(spec) = self.operators.operate(&descriptor)? {
+                        steps.push(Step::Value { descriptor, spec });
+                    }
+                }
+                _ => {
+                    let sequence = self.sequence(descriptor)?;
+                    steps.push(Step::Sequence {
+                        descriptor,
+                        steps: sequence,
+                    });
+                }
+            }
+        }
+        Ok(steps)
+    }
+
+    fn sequence(&mut self, descriptor: Descriptor) -> Result<Vec<Step>, Error> {
+        if self.sequences.contains(&descriptor) {
+            return Err(Error::CyclicSequence(descriptor.x(), descriptor.y()));
+        }
+        let descriptors = descriptor
+            .sequence(self.tables)
+            .ok_or(Error::UnknownDescriptor(
+                descriptor.f(),
+                descriptor.x(),
+                descriptor.y(),
+            ))?;
+        self.sequences.push(descriptor);
+        let steps = self.nested(&descriptors);
+        self.sequences.pop();
+        steps
+    }
+
+    /// Replication of the descriptors following `descriptors[*i - 1]`
+    fn replication(
+        &mut self,
+        descriptor: Descriptor,
+        descriptors: &[Descriptor],
+        i: &mut usize,
+    ) -> Result<Step, Error> {
+        let (x, y) = (descriptor.x(), descriptor.y());
+        let incomplete = || Error::IncompleteReplication(x, y);
+        let count = if y == 0 {
+            let factor = *descriptors.get(*i).ok_or_else(incomplete)?;
+            *i += 1;
+            Count::Delayed(factor, self.operators.element(self.tables, &factor)?)
+        } else {
+            Count::Fixed(usize::from(y))
+        };
+        let n = usize::from(x);
+        let group = descriptors.get(*i..*i + n).ok_or_else(incomplete)?;
+        *i += n;
+
+        // Operators only set values, so the second repetition starts like
+        // all the following ones
+        let before = self.operators.clone();
+        let first = self.nested(group)?;
+        let after = self.operators.clone();
+        let rest = if after == before {
+            None
+        } else if matches!(count, Count::Delayed(..)) {
+            return Err(Error::UnplannableReplication(x, y));
+        } else {
+            Some(self.nested(group)?)
+        };
+        if self.operators != after {
+            return Err(Error::UnplannableReplication(x, y));
+        }
+        Ok(Step::Replication {
+            descriptor,
+            count,
+            first,
+            rest,
+        })
+    }
+}
+
+/// Master table, selected version and local tables a plan was compiled
+/// with
+pub(crate) type TablesKey = (u8, u8, Option<(u16, u16, u8)>);
+
+/// Plans by descriptors, or `None` for those that can't be planned
+type Plans = HashMap<Vec<Descriptor>, Option<Arc<DecodePlan>>>;
+
+/// Decode plans shared by the threads decoding with the same tables
+///
+/// Descriptors that couldn't be planned are remembered too, so they aren't
+/// compiled again.
+#[derive(Default)]
+pub struct PlanCache {
+    plans: RwLock<HashMap<TablesKey, Plans>>,
+}
+
+impl PlanCache {
+    /// Number of descriptor lists with a plan, or known not to have one
+    pub fn len(&self) -> usize {
+        let plans = self.plans.read().unwrap_or_else(PoisonError::into_inner);
+        plans.values().map(HashMap::len).sum()
+    }
+
+    /// True if no plan was compiled yet
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Forget every plan
+    pub fn clear(&self) {
+        self.plans
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clear();
+    }
+
+    /// Plan of the descriptors, compiled on first use
+    pub(crate) fn get_or_compile(
+        &self,
+        key: TablesKey,
+        descriptors: &[Descriptor],
+        tables: &dyn TableProvider,
+    ) -> Option<Arc<DecodePlan>> {
+        {
+            let plans = self.plans.read().unwrap_or_else(PoisonError::into_inner);
+            if let Some(plan) = plans.get(&key).and_then(|p| p.get(descriptors)) {
+                return plan.clone();
+            }
+        }
+        let plan = DecodePlan::compile(descriptors, tables).ok().map(Arc::new);
+        let mut plans = self.plans.write().unwrap_or_else(PoisonError::into_inner);
+        if plans.values().map(HashMap::len).sum::<usize>() >= MAX_PLANS {
+            plans.clear();
+        }
+        plans
+            .entry(key)
+            .or_default()
+            .insert(descriptors.to_vec(), plan.clone());
+        plan
+    }
+}
+
+/// Copies start empty, as their tables may change
+impl Clone for PlanCache {
+    fn clone(&self) -> Self {
+        PlanCache::default()
+    }
+}
+
+impl fmt::Debug for PlanCache {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PlanCache")
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+#[cfg(all(test, feature = "wmo-35"))]
+mod test_plan {
+    use super::{DecodePlan, PlanCache};
+    use crate::data::decode_subsets;
+    use crate::{DecodeOptions, Descriptor, Error, Tables, Value};
+
+    fn d(code: &str) -> Descriptor {
+        code.parse().unwrap()
+    }
+
+    #[test]
+    fn operators() {
+        let tables = Tables::embedded();
+        // Year with 2 more bits, twice more as usual, then a 2 characters
+        // string
+        let descriptors = [
+            d("201130"),
+            d("004001"),
+            d("201000"),
+            d("101000"),
+            d("031001"),
+            d("004001"),
+            d("205002"),
+        ];
+        let plan = DecodePlan::compile(&descriptors, &tables).unwrap();
+        assert_eq!(plan.descriptors(), &descriptors);
+        assert_eq!(plan.bit_width(), None);
+
+        // A year of 14 bits, the delayed factor, 2 years and the string
+        let data = [0x1f, 0x90, 0x09, 0xf9, 0x1f, 0x91, 0x05, 0x08];
+        let options = DecodeOptions::default();
+        let walked = decode_subsets(&data, &descriptors, 1, false, &tables, None, &options);
+        let planned = decode_subsets(
+            &data,
+            &descriptors,
+            1,
+            false,
+            &tables,
+            Some(&plan),
+            &options,
+        );
+        let walked = walked.unwrap();
+        assert_eq!(planned.unwrap(), walked);
+        let values: Vec<_> = walked[0].values().cloned().collect();
+        assert_eq!(values[0], Value::Integer(2020));
+        assert_eq!(values[3], Value::Integer(2020));
+        assert_eq!(values[4], Value::String("AB".to_string()));
+    }
+
+    #[test]
+    fn replications() {
+        let tables = Tables::embedded();
+        // Fixed replication resetting the width it changes
+        let descriptors = [d("103002"), d("201130"), d("004001"), d("201000")];
+        let plan = DecodePlan::compile(&descriptors, &tables).unwrap();
+        assert_eq!(plan.bit_width(), Some(2 * 14));
+
+        // The first repetition changes the width of the following ones
+        let descriptors = [d("102003"), d("004001"), d("201130")];
+        let plan = DecodePlan::compile(&descriptors, &tables).unwrap();
+        assert_eq!(plan.bit_width(), Some(12 + 2 * 14));
+
+        // Same, but with a count read from the data
+        let descriptors = [d("102000"), d("031001"), d("004001"), d("201130")];
+        let err = DecodePlan::compile(&descriptors, &tables).unwrap_err();
+        assert!(matches!(err, Error::UnplannableReplication(2, 0)));
+
+        let err = DecodePlan::compile(&[d("063255")], &tables).unwrap_err();
+        assert!(matches!(err, Error::UnknownDescriptor(0, 63, 255)));
+
+        // Widths that don't fit in a usize are unknown
+        let mut descriptors: Vec<_> = (1..=9).rev().map(|x| Descriptor::new(1, x, 255)).collect();
+        descriptors.push(d("001019"));
+        let plan = DecodePlan::compile(&descriptors, &tables).unwrap();
+        assert_eq!(plan.bit_width(), None);
+
+        // Nesting deeper than the default limit
+        let mut descriptors: Vec<_> = (1..=40).rev().map(|x| Descriptor::new(1, x, 2)).collect();
+        descriptors.push(d("004001"));
+        let err = DecodePlan::compile(&descriptors, &tables).unwrap_err();
+        assert!(matches!(err, Error::DepthLimit(32)));
+    }
+
+    #[test]
+    fn cache() {
+        let tables = Tables::embedded();
+        let cache = PlanCache::default();
+        let key = (0, 35, None);
+
+        let first = cache.get_or_compile(key, &[d("301011")], &tables).unwrap();
+        let again = cache.get_or_compile(key, &[d("301011")], &tables).unwrap();
+        assert!(std::sync::Arc::ptr_eq(&first, &again));
+        assert!(cache.get_or_compile(key, &[d("363255")], &tables).is_none());
+        assert_eq!(cache.len(), 2);
+
+        assert!(cache.clone().is_empty());
+        cache.clear();
+        assert!(cache.is_empty());
+    }
+}
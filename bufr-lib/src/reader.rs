@@ -0,0 +1,147 @@
+//! Files with many messages
+
+use crate::error::Error;
+use crate::{decode_with, DecodeOptions, Message};
+
+/// Iterator over all the messages in a buffer
+///
+/// Anything between messages is skipped. When a message can't be decoded
+/// the search for the next one restarts right after its "BUFR" marker, so
+/// one corrupted message doesn't hide the following ones. Errors carry the
+/// index and position of the message.
+pub struct Messages<'a> {
+    buf: &'a [u8],
+    options: DecodeOptions,
+    framing: Framing,
+    index: usize,
+    // Start of the last message returned
+    offset: usize,
+}
+
+/// Iterate over the messages in `buf`, such as the content of a file
+pub fn messages(buf: &[u8]) -> Messages<'_> {
+    messages_with(buf, &DecodeOptions::default())
+}
+
+/// Iterate over the messages in `buf`, decoding them with `options`
+pub fn messages_with<'a>(buf: &'a [u8], options: &DecodeOptions) -> Messages<'a> {
+    Messages {
+        buf,
+        options: options.clone(),
+        framing: Framing::default(),
+        index: 0,
+        offset: 0,
+    }
+}
+
+/// Position of the next "BUFR" marker in `buf`
+pub(crate) fn find_start(buf: &[u8]) -> Option<usize> {
+    buf.windows(4).position(|w| w == b"BUFR")
+}
+
+/// Splitting of a buffer into messages
+///
+/// Shared by `Messages` and `decode_all_with`, so that both find the same
+/// messages at the same positions.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Framing {
+    // Where to look for the next message
+    next: usize,
+}
+
+impl Framing {
+    /// Position of the next message in `buf`
+    pub(crate) fn start(&self, buf: &[u8]) -> Option<usize> {
+        Some(self.next + find_start(buf.get(self.next..)?)?)
+    }
+
+    /// Move past the message at `start`
+    ///
+    /// A message of known `length` is skipped as a whole, otherwise the
+    /// search restarts right after its "BUFR" marker.
+    pub(crate) fn advance(&mut self, start: usize, length: Option<usize>) {
+        self.next = start + length.unwrap_or(4);
+    }
+}
+
+impl<'a> Messages<'a> {
+    /// Index of the last message returned
+    pub fn index(&self) -> usize {
+        self.index.saturating_sub(1)
+    }
+
+    /// Position in the buffer of the last message returned
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+}
+
+impl<'a> Iterator for Messages<'a> {
+    type Item = Result<Message<'a>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let start = self.framing.start(self.buf)?;
+        let index = self.index;
+        self.index += 1;
+        self.offset = start;
+
+        let result = decode_with(&self.buf[start..], &self.options);
+        let length = result.as_ref().ok().map(|m| m.total_length() as usize);
+        self.framing.advance(start, length);
+        Some(result.map_err(|e| e.with_message(index, start)))
+    }
+}
+
+#[cfg(test)]
+mod test_messages {
+    use super::messages;
+    use crate::Error;
+
+    // Smallest valid message: no descriptors can be expanded from it, but
+    // the structure is complete.
+    fn message() -> Vec<u8> {
+        let mut buf = b"BUFR".to_vec();
+        buf.extend_from_slice(&[0, 0, 49, 4]);
+        buf.extend_from_slice(&[
+            0, 0, 22, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 7, 228, 1, 1, 0, 0, 0,
+        ]);
+        buf.extend_from_slice(&[0, 0, 9, 0, 0, 1, 128, 64, 1]);
+        buf.extend_from_slice(&[0, 0, 6, 0, 7, 228]);
+        buf.extend_from_slice(b"7777");
+        buf
+    }
+
+    #[test]
+    fn skip_garbage() {
+        let mut buf = b"garbage".to_vec();
+        buf.extend(message());
+        buf.extend_from_slice(b"\n\n");
+        buf.extend(message());
+
+        let all: Vec<_> = messages(&buf).collect();
+        assert_eq!(all.len(), 2);
+        assert!(all.iter().all(|m| m.is_ok()));
+    }
+
+    #[test]
+    fn resynchronise() {
+        let mut corrupted = message();
+        let n = corrupted.len();
+        corrupted[n - 1] = b'0';
+        let mut buf = corrupted;
+        buf.extend(message());
+
+        let all: Vec<_> = messages(&buf).collect();
+        assert_eq!(all.len(), 2);
+        match &all[0] {
+            Err(e) => {
+                assert!(matches!(e.kind(), Error::EndSection { offset: 45 }));
+                let location = e.location().unwrap();
+                assert_eq!(location.message(), Some(0));
+                assert_eq!(location.message_offset(), Some(0));
+            }
+            Ok(_) => panic!(),
+        }
+        assert!(all[1].is_ok());
+    }
+}
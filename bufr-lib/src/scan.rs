@@ -0,0 +1,213 @@
+//! Inventory of files with many messages
+
+use crate::error::Error;
+use crate::identification::Section1;
+use crate::reader::find_start;
+use crate::{decode_parts, DecodeOptions, Section3};
+
+/// Metadata of a message, without its data
+///
+/// Section 4 is only skipped by its length, so its values are neither
+/// read nor checked.
+#[derive(Debug)]
+pub struct Header {
+    offset: usize,
+    total_length: u32,
+    edition: u8,
+    section1: Section1,
+    section3: Section3,
+}
+
+impl Header {
+    /// Position of the message in the buffer it was found in
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Total length of the message including all sections
+    pub fn total_length(&self) -> u32 {
+        self.total_length
+    }
+
+    /// BUFR edition
+    pub fn edition(&self) -> u8 {
+        self.edition
+    }
+
+    /// Identification section: centre, data category and typical time
+    pub fn section1(&self) -> &Section1 {
+        &self.section1
+    }
+
+    /// Data description section: number of subsets and unexpanded
+    /// descriptors
+    pub fn section3(&self) -> &Section3 {
+        &self.section3
+    }
+}
+
+/// Decode the header of the message at the start of `buf`
+pub fn decode_header(buf: &[u8], options: &DecodeOptions) -> Result<Header, Error> {
+    let message = decode_parts(buf, options, false).into_message()?;
+    Ok(Header {
+        offset: 0,
+        total_length: message.total_length,
+        edition: message.version,
+        section1: message.section1,
+        section3: message.section3,
+    })
+}
+
+/// Iterator over the headers of all the messages in a buffer
+///
+/// Messages are found like `Messages` does, skipping anything between them
+/// and resynchronising after a corrupted one.
+pub struct Headers<'a> {
+    buf: &'a [u8],
+    options: DecodeOptions,
+    // Where to look for the next message
+    next: usize,
+    index: usize,
+}
+
+/// Iterate over the headers of the messages in `buf`
+pub fn scan(buf: &[u8]) -> Headers<'_> {
+    scan_with(buf, &DecodeOptions::default())
+}
+
+/// Iterate over the headers of the messages in `buf`, checked with
+/// `options`
+pub fn scan_with<'a>(buf: &'a [u8], options: &DecodeOptions) -> Headers<'a> {
+    Headers {
+        buf,
+        options: options.clone().decode_data(false),
+        next: 0,
+        index: 0,
+    }
+}
+
+impl<'a> Headers<'a> {
+    /// Index of the last header returned
+    pub fn index(&self) -> usize {
+        self.index.saturating_sub(1)
+    }
+}
+
+impl<'a> Iterator for Headers<'a> {
+    type Item = Result<Header, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let start = self.next + find_start(self.buf.get(self.next..)?)?;
+        let index = self.index;
+        self.index += 1;
+
+        match decode_header(&self.buf[start..], &self.options) {
+            Ok(mut header) => {
+                header.offset = start;
+                self.next = start + header.total_length as usize;
+                Some(Ok(header))
+            }
+            Err(e) => {
+                self.next = start + 4;
+                Some(Err(e.with_message(index, start)))
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "wmo-35"))]
+mod test_scan {
+    use super::scan;
+    use crate::identification::{Section1, Section1v4Builder};
+    use crate::{Descriptor, MessageBuilder, Value};
+
+    fn message(day: u8) -> Vec<u8> {
+        let section1 = Section1v4Builder::default()
+            .master_table(0)
+            .sub_center(0)
+            .center(98)
+            .update_version(0)
+            .optional_section(false)
+            .data_category(1)
+            .data_subcategory(0)
+            .local_subcategory(0)
+            .master_table_version(35)
+            .local_table_version(0)
+            .year(2020)
+            .month(10)
+            .day(day)
+            .hour(12)
+            .minute(0)
+            .second(0)
+            .build()
+            .unwrap();
+        let message = MessageBuilder::default()
+            .section1(Section1::V4(section1))
+            .descriptors(vec![Descriptor::new(3, 1, 11)])
+            .subset(vec![
+                Value::Integer(2020),
+                Value::Integer(10),
+                Value::Integer(6),
+            ])
+            .subset(vec![
+                Value::Integer(2020),
+                Value::Integer(10),
+                Value::Integer(7),
+            ])
+            .build()
+            .unwrap();
+        let mut buf = vec![];
+        message.encode(&mut buf).unwrap();
+        buf
+    }
+
+    #[test]
+    fn headers() {
+        let mut buf = b"garbage".to_vec();
+        buf.extend(message(6));
+        let second = buf.len();
+        buf.extend(message(7));
+        // The data of section 4 isn't checked
+        let n = buf.len();
+        buf[n - 6] = 0xff;
+
+        let headers: Vec<_> = scan(&buf).map(Result::unwrap).collect();
+        assert_eq!(headers.len(), 2);
+        assert_eq!(headers[0].offset(), 7);
+        assert_eq!(headers[1].offset(), second);
+        assert_eq!(headers[1].total_length() as usize, n - second);
+        assert_eq!(headers[1].edition(), 4);
+        assert_eq!(headers[1].section1().center(), 98);
+        assert_eq!(
+            headers[1].section1().typical_time(),
+            (2020, 10, 7, 12, 0, 0)
+        );
+        assert_eq!(headers[1].section3().n_subsets(), 2);
+        assert_eq!(
+            headers[1].section3().descriptors(),
+            &[Descriptor::new(3, 1, 11)]
+        );
+    }
+
+    #[test]
+    fn resynchronise() {
+        let mut corrupted = message(6);
+        let n = corrupted.len();
+        corrupted[n - 1] = b'0';
+        corrupted.extend(message(7));
+
+        let headers: Vec<_> = scan(&corrupted).collect();
+        assert_eq!(headers.len(), 2);
+        assert_eq!(
+            headers[0]
+                .as_ref()
+                .unwrap_err()
+                .location()
+                .unwrap()
+                .message(),
+            Some(0)
+        );
+        let header = headers[1].as_ref().unwrap();
+        assert_eq!(header.section1().typical_time().2, 7);
+    }
+}
@@ -0,0 +1,378 @@
+//! Table B and Table D entries carried by BUFR messages
+//!
+//! Messages of data category 11 define new entries with the descriptors
+//! of class 00: 3-00-004 for each Table B entry and 3-00-010 for each
+//! Table D entry, each repeated with a delayed replication.
+
+use crate::identification::Section1;
+use crate::tables::{parse_descriptor, TableF0, TableF3, F3};
+use crate::{
+    BUFRUnit, Descriptor, ElementDescriptor, Error, Message, MessageBuilder, Subset, Value,
+};
+
+/// Data category of BUFR table messages
+pub(crate) const TABLES_CATEGORY: u8 = 11;
+
+/// Entries of each replication, limited by the 8 bits of 0-31-001
+const MAX_ENTRIES: usize = 255;
+
+/// Characters of each line of an element name, 0-00-013 and 0-00-014
+const NAME_LINE: usize = 32;
+
+/// Entry being read, from its 0-00-010 to the next one
+#[derive(Default)]
+struct Entry {
+    f: String,
+    x: String,
+    y: String,
+    // Values of 0-00-013 to 0-00-020, by Y
+    text: [Option<String>; 21],
+    sequence: Vec<Descriptor>,
+}
+
+impl Entry {
+    fn text(&self, y: usize) -> &str {
+        self.text[y].as_deref().unwrap_or("").trim()
+    }
+
+    fn number(&self, sign: usize, digits: usize) -> Result<i32, Error> {
+        let text = format!("{}{}", self.text(sign), self.text(digits));
+        text.parse().map_err(|_| invalid(&text))
+    }
+
+    fn name(&self) -> String {
+        let line1 = self.text[13].as_deref().unwrap_or("");
+        let line2 = self.text(14);
+        if line1.len() == NAME_LINE || line2.is_empty() {
+            format!("{}{}", line1, line2).trim().to_string()
+        } else {
+            format!("{} {}", line1.trim(), line2)
+        }
+    }
+
+    /// Add the entry to the tables
+    fn finish(self, f0: &mut TableF0, f3: &mut TableF3) -> Result<(), Error> {
+        let x = self.x.parse().map_err(|_| invalid(&self.x))?;
+        let y = self.y.parse().map_err(|_| invalid(&self.y))?;
+        match self.f.as_str() {
+            "0" => {
+                let element = ElementDescriptor {
+                    name: self.name().into(),
+                    unit: BUFRUnit::from(self.text(15)),
+                    scale: self.number(16, 17)?,
+                    reference_value: self.number(18, 19)?,
+                    data_width: self.text(20).parse().map_err(|_| invalid(self.text(20)))?,
+                };
+                f0.insert((x, y), element);
+            }
+            "3" => {
+                let sequence = F3 {
+                    descriptors: self.sequence,
+                    title: None,
+                };
+                f3.insert((x, y), sequence);
+            }
+            f => return Err(invalid(f)),
+        }
+        Ok(())
+    }
+}
+
+fn invalid(text: &str) -> Error {
+    Error::InvalidTable(format!("Invalid value in table message: {}", text))
+}
+
+/// Table B and Table D entries defined by the subsets of a table message
+pub(crate) fn parse_table_message(subsets: &[Subset]) -> Result<(TableF0, TableF3), Error> {
+    let mut f0 = TableF0::default();
+    let mut f3 = TableF3::default();
+
+    for subset in subsets {
+        let mut entry: Option<Entry> = None;
+        for field in subset.fields() {
+            let d = field.descriptor();
+            let text = match (d.f, d.x, field.value()) {
+                (0, 0, Value::String(text)) => text.clone(),
+                (0, 0, Value::Missing) => String::new(),
+                _ => continue,
+            };
+            if d.y == 10 {
+                if let Some(entry) = entry.take() {
+                    entry.finish(&mut f0, &mut f3)?;
+                }
+                entry = Some(Entry {
+                    f: text.trim().to_string(),
+                    ..Entry::default()
+                });
+                continue;
+            }
+            let entry = match entry.as_mut() {
+                Some(entry) => entry,
+                None => continue,
+            };
+            match d.y {
+                11 => entry.x = text.trim().to_string(),
+                12 => entry.y = text.trim().to_string(),
+                13..=20 => entry.text[usize::from(d.y)] = Some(text),
+                30 => entry.sequence.push(parse_descriptor(text.trim())?),
+                _ => {}
+            }
+        }
+        if let Some(entry) = entry {
+            entry.finish(&mut f0, &mut f3)?;
+        }
+    }
+    Ok((f0, f3))
+}
+
+fn text(s: impl ToString) -> Value {
+    Value::String(s.to_string())
+}
+
+/// Element name split over 0-00-013 and 0-00-014, at a blank if possible
+///
+/// Longer names are cut at the end of the second line.
+fn name_lines(name: &str) -> (&str, &str) {
+    if name.len() <= NAME_LINE || !name.is_ascii() {
+        return (name, "");
+    }
+    // A line shorter than 32 characters tells the blank was dropped
+    let (line1, line2) = match name[..NAME_LINE].rfind(' ') {
+        Some(i) if i > 0 => (&name[..i], &name[i + 1..]),
+        _ => name.split_at(NAME_LINE),
+    };
+    (line1, &line2[..line2.len().min(NAME_LINE)])
+}
+
+/// Unit name that fits in the 24 characters of 0-00-015
+fn unit_name(unit: &BUFRUnit) -> String {
+    match unit {
+        BUFRUnit::CodeTableOriginator => BUFRUnit::CodeTable.to_string(),
+        unit => unit.to_string(),
+    }
+}
+
+fn sign(value: i32) -> Value {
+    text(if value < 0 { "-" } else { "+" })
+}
+
+/// Values of 3-00-004 for a Table B entry
+fn element_values(x: u8, y: u8, e: &ElementDescriptor) -> Vec<Value> {
+    let (line1, line2) = name_lines(&e.name);
+    vec![
+        text("0"),
+        text(format!("{:02}", x)),
+        text(format!("{:03}", y)),
+        text(line1),
+        text(line2),
+        text(unit_name(&e.unit)),
+        sign(e.scale),
+        text(format!("{:03}", e.scale.unsigned_abs())),
+        sign(e.reference_value),
+        text(format!("{:010}", e.reference_value.unsigned_abs())),
+        text(format!("{:03}", e.data_width)),
+    ]
+}
+
+/// Values of 3-00-010 for a Table D entry
+fn sequence_values(x: u8, y: u8, sequence: &F3) -> Result<Vec<Value>, Error> {
+    let n = sequence.descriptors.len();
+    if n > MAX_ENTRIES {
+        return Err(Error::InvalidValue(3, x, y, format!("{} descriptors", n)));
+    }
+    let mut values = vec![
+        text("3"),
+        text(format!("{:02}", x)),
+        text(format!("{:03}", y)),
+        Value::Integer(n as i64),
+    ];
+    for d in sequence.iter() {
+        values.push(text(format!("{}{:02}{:03}", d.f, d.x, d.y)));
+    }
+    Ok(values)
+}
+
+/// Table messages carrying the entries of Table B and Table D
+///
+/// Section 1 is used for every message, with its data category set to 11.
+pub(crate) fn table_messages(
+    f0: &TableF0,
+    f3: &TableF3,
+    section1: Section1,
+) -> Result<Vec<Message<'static>>, Error> {
+    let mut section1 = section1;
+    section1.set_data_category(TABLES_CATEGORY);
+
+    let mut elements: Vec<_> = f0.iter().collect();
+    elements.sort_by_key(|(key, _)| **key);
+    let mut sequences: Vec<_> = f3.iter().collect();
+    sequences.sort_by_key(|(key, _)| **key);
+
+    let descriptors = vec![
+        Descriptor::new(1, 1, 0),
+        Descriptor::new(0, 31, 1),
+        Descriptor::new(3, 0, 4),
+        Descriptor::new(1, 1, 0),
+        Descriptor::new(0, 31, 1),
+        Descriptor::new(3, 0, 10),
+    ];
+    let elements: Vec<_> = elements.chunks(MAX_ENTRIES).collect();
+    let sequences: Vec<_> = sequences.chunks(MAX_ENTRIES).collect();
+    let mut messages = vec![];
+    for i in 0..usize::max(elements.len(), sequences.len()) {
+        let elements = elements.get(i).copied().unwrap_or_default();
+        let sequences = sequences.get(i).copied().unwrap_or_default();
+
+        let mut values = vec![Value::Integer(elements.len() as i64)];
+        for (&(x, y), e) in elements {
+            values.extend(element_values(x, y, e));
+        }
+        values.push(Value::Integer(sequences.len() as i64));
+        for (&(x, y), sequence) in sequences {
+            values.extend(sequence_values(x, y, sequence)?);
+        }
+
+        let message = MessageBuilder::default()
+            .section1(section1.clone())
+            .is_observed(false)
+            .descriptors(descriptors.clone())
+            .subset(values)
+            .build()?;
+        messages.push(message);
+    }
+    Ok(messages)
+}
+
+/// Whether a message carries table entries rather than data
+///
+/// NCEP DX table messages use the same data category, see `is_dx`.
+pub fn is_table_message(message: &Message) -> bool {
+    message.section1().data_category() == TABLES_CATEGORY
+}
+
+#[cfg(all(test, feature = "wmo-35"))]
+mod test_table_messages {
+    use super::name_lines;
+    use crate::identification::{Section1, Section1v4Builder};
+    use crate::{TableProvider, TableSet, Tables};
+
+    const TABLE_B: &str = "ClassNo,ClassName_en,FXY,ElementName_en,BUFR_Unit,BUFR_Scale,BUFR_ReferenceValue,BUFR_DataWidth_Bits,CREX_Unit,CREX_Scale,CREX_DataWidth_Char,Note_en,noteIDs,Status
+48,Local,048001,Temperature of the water below the surface at the buoy,K,2,-1000,16,C,2,4,,,Operational
+48,Local,048002,Buoy type,Code table,0,0,6,Code table,0,2,,,Operational
+";
+    const TABLE_D: &str = "Category,CategoryOfSequences_en,FXY1,Title_en,SubTitle_en,FXY2,ElementName_en,ElementDescription_en,Note_en,noteIDs,Status
+48,Local,348001,Buoy,,048002,Buoy type,,,,Operational
+48,Local,348001,Buoy,,048001,Temperature,,,,Operational
+48,Local,348001,Buoy,,101001,Replication,,,,Operational
+48,Local,348001,Buoy,,012101,Temperature,,,,Operational
+";
+
+    fn section1(local_table_version: u8) -> Section1 {
+        let section1 = Section1v4Builder::default()
+            .master_table(0)
+            .sub_center(0)
+            .center(98)
+            .update_version(0)
+            .optional_section(false)
+            .data_category(0)
+            .data_subcategory(0)
+            .local_subcategory(0)
+            .master_table_version(35)
+            .local_table_version(local_table_version)
+            .year(2024)
+            .month(5)
+            .day(1)
+            .hour(0)
+            .minute(0)
+            .second(0)
+            .build()
+            .unwrap();
+        Section1::V4(section1)
+    }
+
+    fn local_tables() -> Tables {
+        let mut tables = Tables::default();
+        tables.add_table_b(TABLE_B.as_bytes()).unwrap();
+        tables.add_table_d(TABLE_D.as_bytes()).unwrap();
+        tables
+    }
+
+    #[test]
+    fn round_trip() {
+        let tables = local_tables();
+        let messages = tables.table_messages(section1(1)).unwrap();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].section1().data_category(), 11);
+
+        let mut buf = vec![];
+        messages[0].encode(&mut buf).unwrap();
+        let message = crate::decode(&buf).unwrap();
+        let mut decoded = Tables::default();
+        decoded.add_table_message(&message).unwrap();
+
+        for &(x, y) in &[(48, 1), (48, 2)] {
+            assert_eq!(decoded.element(x, y), tables.element(x, y));
+        }
+        assert_eq!(decoded.sequence(48, 1), tables.sequence(48, 1));
+    }
+
+    #[test]
+    fn merge_local() {
+        let messages = local_tables().table_messages(section1(1)).unwrap();
+        let mut set = TableSet::wmo();
+        for message in &messages {
+            set.add_table_message(message).unwrap();
+        }
+        let tables = set.resolve(&section1(1)).unwrap();
+        assert!(tables.has_local());
+        assert_eq!(tables.element(48, 2).unwrap().data_width(), 6);
+        assert!(set.resolve(&section1(2)).unwrap().element(48, 2).is_none());
+
+        // Without a local table version, entries go to the master tables
+        let messages = local_tables().table_messages(section1(0)).unwrap();
+        let mut set = TableSet::wmo();
+        set.add_table_message(&messages[0]).unwrap();
+        let tables = set.resolve(&section1(2)).unwrap();
+        assert!(!tables.has_local());
+        assert!(tables.element(48, 1).is_some());
+        assert!(tables.element(1, 1).is_some());
+    }
+
+    #[test]
+    fn many_entries() {
+        let mut csv = TABLE_B.lines().next().unwrap().to_string();
+        for y in 0..=255 {
+            csv.push_str(&format!(
+                "\n50,Local,050{:03},Element {},m,0,0,8,m,0,3,,,Operational",
+                y, y
+            ));
+        }
+        let mut tables = Tables::default();
+        tables.add_table_b(csv.as_bytes()).unwrap();
+        let messages = tables.table_messages(section1(1)).unwrap();
+        assert_eq!(messages.len(), 2);
+
+        let mut decoded = Tables::default();
+        for message in &messages {
+            decoded.add_table_message(message).unwrap();
+        }
+        assert_eq!(decoded.element(50, 255).unwrap().name(), "Element 255");
+    }
+
+    #[test]
+    fn names() {
+        let name = "Temperature of the water below the surface at the buoy";
+        assert_eq!(
+            name_lines(name),
+            ("Temperature of the water below", "the surface at the buoy")
+        );
+        let name = "Temperature of the water below the surface of the sea at the buoy";
+        assert_eq!(name_lines(name).1, "the surface of the sea at the bu");
+        let name = "Abcdefghijklmnopqrstuvwxyzabcdefghij";
+        assert_eq!(
+            name_lines(name),
+            ("Abcdefghijklmnopqrstuvwxyzabcdef", "ghij")
+        );
+        assert_eq!(name_lines("Year"), ("Year", ""));
+    }
+}
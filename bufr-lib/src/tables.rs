@@ -1,352 +1,81 @@
-use std::collections::HashMap;
-use std::convert::TryFrom;
-use std::fmt;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::convert::{TryFrom, TryInto};
 use std::fs::File;
-use std::io::BufReader;
+use std::io::{BufRead, BufReader, BufWriter, Write};
 use std::path::Path;
+use std::sync::Arc;
 // use std::path::PathBuf;
 // use std::str::FromStr;
 
 use once_cell::sync::Lazy;
 use serde::Deserialize;
 
-use crate::ElementDescriptor;
+use crate::codes::{load_code_flag, parse_code_flag, CodeTable, Meaning, TableCodeFlag};
+use crate::common::{centre_name, sub_centre_name};
+use crate::eccodes::{
+    parse_element_table, parse_sequence_def, write_element_table, write_sequence_def,
+};
+use crate::identification::Section1;
+use crate::ncep::parse_dx;
+use crate::plan::{DecodePlan, PlanCache};
+use crate::table_messages::{is_table_message, parse_table_message, table_messages};
+use crate::{BUFRUnit, Descriptor, ElementDescriptor, Error, Value};
 
 pub(crate) type TableF0 = HashMap<(u8, u8), ElementDescriptor>;
 pub(crate) type TableF3 = HashMap<(u8, u8), F3>;
 
-pub(crate) static TABLE_F0: Lazy<TableF0> = Lazy::new(|| {
-    let mut table = TableF0::default();
-
-    let data = include_bytes!("../tables/BUFRCREX_TableB_en_00.csv");
-    let new_table = parse_table_f0(&data[..]);
-    table.extend(new_table);
-
-    let data = include_bytes!("../tables/BUFRCREX_TableB_en_01.csv");
-    let new_table = parse_table_f0(&data[..]);
-    table.extend(new_table);
-
-    /*
-    let data = include_bytes!("../tables/BUFRCREX_TableB_en_02.csv");
-    let new_table = parse_table_f0(&data[..]);
-    table.extend(new_table);
-
-    let data = include_bytes!("../tables/BUFRCREX_TableB_en_03.csv");
-    let new_table = parse_table_f0(&data[..]);
-    table.extend(new_table);
-    */
-
-    let data = include_bytes!("../tables/BUFRCREX_TableB_en_04.csv");
-    let new_table = parse_table_f0(&data[..]);
-    table.extend(new_table);
-
-    let data = include_bytes!("../tables/BUFRCREX_TableB_en_05.csv");
-    let new_table = parse_table_f0(&data[..]);
-    table.extend(new_table);
-
-    /*
-    let data = include_bytes!("../tables/BUFRCREX_TableB_en_06.csv");
-    let new_table = parse_table_f0(&data[..]);
-    table.extend(new_table);
-
-    let data = include_bytes!("../tables/BUFRCREX_TableB_en_07.csv");
-    let new_table = parse_table_f0(&data[..]);
-    table.extend(new_table);
-    */
-
-    let data = include_bytes!("../tables/BUFRCREX_TableB_en_08.csv");
-    let new_table = parse_table_f0(&data[..]);
-    table.extend(new_table);
-
-    /*
-    let data = include_bytes!("../tables/BUFRCREX_TableB_en_10.csv");
-    let new_table = parse_table_f0(&data[..]);
-    table.extend(new_table);
-
-    let data = include_bytes!("../tables/BUFRCREX_TableB_en_11.csv");
-    let new_table = parse_table_f0(&data[..]);
-    table.extend(new_table);
-
-    let data = include_bytes!("../tables/BUFRCREX_TableB_en_12.csv");
-    let new_table = parse_table_f0(&data[..]);
-    table.extend(new_table);
-
-    let data = include_bytes!("../tables/BUFRCREX_TableB_en_13.csv");
-    let new_table = parse_table_f0(&data[..]);
-    table.extend(new_table);
-
-    let data = include_bytes!("../tables/BUFRCREX_TableB_en_14.csv");
-    let new_table = parse_table_f0(&data[..]);
-    table.extend(new_table);
-
-    let data = include_bytes!("../tables/BUFRCREX_TableB_en_15.csv");
-    let new_table = parse_table_f0(&data[..]);
-    table.extend(new_table);
-    */
-
-    let data = include_bytes!("../tables/BUFRCREX_TableB_en_19.csv");
-    let new_table = parse_table_f0(&data[..]);
-    table.extend(new_table);
-
-    /*
-    let data = include_bytes!("../tables/BUFRCREX_TableB_en_20.csv");
-    let new_table = parse_table_f0(&data[..]);
-    table.extend(new_table);
-
-    let data = include_bytes!("../tables/BUFRCREX_TableB_en_21.csv");
-    let new_table = parse_table_f0(&data[..]);
-    table.extend(new_table);
-
-    let data = include_bytes!("../tables/BUFRCREX_TableB_en_22.csv");
-    let new_table = parse_table_f0(&data[..]);
-    table.extend(new_table);
-    */
-
-    let data = include_bytes!("../tables/BUFRCREX_TableB_en_23.csv");
-    let new_table = parse_table_f0(&data[..]);
-    table.extend(new_table);
-
-    /*
-    let data = include_bytes!("../tables/BUFRCREX_TableB_en_24.csv");
-    let new_table = parse_table_f0(&data[..]);
-    table.extend(new_table);
-
-    let data = include_bytes!("../tables/BUFRCREX_TableB_en_25.csv");
-    let new_table = parse_table_f0(&data[..]);
-    table.extend(new_table);
-    */
-
-    let data = include_bytes!("../tables/BUFRCREX_TableB_en_26.csv");
-    let new_table = parse_table_f0(&data[..]);
-    table.extend(new_table);
-
-    let data = include_bytes!("../tables/BUFRCREX_TableB_en_27.csv");
-    let new_table = parse_table_f0(&data[..]);
-    table.extend(new_table);
-
-    let data = include_bytes!("../tables/BUFRCREX_TableB_en_28.csv");
-    let new_table = parse_table_f0(&data[..]);
-    table.extend(new_table);
-
-    let data = include_bytes!("../tables/BUFRCREX_TableB_en_29.csv");
-    let new_table = parse_table_f0(&data[..]);
-    table.extend(new_table);
-
-    let data = include_bytes!("../tables/BUFRCREX_TableB_en_30.csv");
-    let new_table = parse_table_f0(&data[..]);
-    table.extend(new_table);
-
-    let data = include_bytes!("../tables/BUFRCREX_TableB_en_31.csv");
-    let new_table = parse_table_f0(&data[..]);
-    table.extend(new_table);
-
-    /*
-    let data = include_bytes!("../tables/BUFRCREX_TableB_en_33.csv");
-    let new_table = parse_table_f0(&data[..]);
-    table.extend(new_table);
-    */
-
-    let data = include_bytes!("../tables/BUFRCREX_TableB_en_35.csv");
-    let new_table = parse_table_f0(&data[..]);
-    table.extend(new_table);
-
-    /*
-    let data = include_bytes!("../tables/BUFRCREX_TableB_en_40.csv");
-    let new_table = parse_table_f0(&data[..]);
-    table.extend(new_table);
-
-    let data = include_bytes!("../tables/BUFRCREX_TableB_en_41.csv");
-    let new_table = parse_table_f0(&data[..]);
-    table.extend(new_table);
-    */
-
-    let data = include_bytes!("../tables/BUFRCREX_TableB_en_42.csv");
-    let new_table = parse_table_f0(&data[..]);
-    table.extend(new_table);
-
-    table
-});
-
-pub(crate) static TABLE_F3: Lazy<TableF3> = Lazy::new(|| {
-    let mut table = TableF3::default();
-
-    // FIXME: 18, 21, 22, 40
-    //    for n in 0..16 {
-    let data = include_bytes!("../tables/BUFR_TableD_en_00.csv");
-    let new_table = parse_table_f3(&data[..]);
-    table.extend(new_table);
-
-    let data = include_bytes!("../tables/BUFR_TableD_en_01.csv");
-    let new_table = parse_table_f3(&data[..]);
-    table.extend(new_table);
-
-    let data = include_bytes!("../tables/BUFR_TableD_en_02.csv");
-    let new_table = parse_table_f3(&data[..]);
-    table.extend(new_table);
-
-    let data = include_bytes!("../tables/BUFR_TableD_en_03.csv");
-    let new_table = parse_table_f3(&data[..]);
-    table.extend(new_table);
-
-    let data = include_bytes!("../tables/BUFR_TableD_en_04.csv");
-    let new_table = parse_table_f3(&data[..]);
-    table.extend(new_table);
-
-    let data = include_bytes!("../tables/BUFR_TableD_en_05.csv");
-    let new_table = parse_table_f3(&data[..]);
-    table.extend(new_table);
-
-    let data = include_bytes!("../tables/BUFR_TableD_en_06.csv");
-    let new_table = parse_table_f3(&data[..]);
-    table.extend(new_table);
-
-    let data = include_bytes!("../tables/BUFR_TableD_en_07.csv");
-    let new_table = parse_table_f3(&data[..]);
-    table.extend(new_table);
-
-    let data = include_bytes!("../tables/BUFR_TableD_en_08.csv");
-    let new_table = parse_table_f3(&data[..]);
-    table.extend(new_table);
-
-    let data = include_bytes!("../tables/BUFR_TableD_en_09.csv");
-    let new_table = parse_table_f3(&data[..]);
-    table.extend(new_table);
-
-    let data = include_bytes!("../tables/BUFR_TableD_en_10.csv");
-    let new_table = parse_table_f3(&data[..]);
-    table.extend(new_table);
-
-    let data = include_bytes!("../tables/BUFR_TableD_en_11.csv");
-    let new_table = parse_table_f3(&data[..]);
-    table.extend(new_table);
-
-    let data = include_bytes!("../tables/BUFR_TableD_en_12.csv");
-    let new_table = parse_table_f3(&data[..]);
-    table.extend(new_table);
-
-    let data = include_bytes!("../tables/BUFR_TableD_en_13.csv");
-    let new_table = parse_table_f3(&data[..]);
-    table.extend(new_table);
-
-    let data = include_bytes!("../tables/BUFR_TableD_en_14.csv");
-    let new_table = parse_table_f3(&data[..]);
-    table.extend(new_table);
-
-    let data = include_bytes!("../tables/BUFR_TableD_en_15.csv");
-    let new_table = parse_table_f3(&data[..]);
-    table.extend(new_table);
-
-    let data = include_bytes!("../tables/BUFR_TableD_en_16.csv");
-    let new_table = parse_table_f3(&data[..]);
-    table.extend(new_table);
-
-    let data = include_bytes!("../tables/BUFR_TableD_en_17.csv");
-    let new_table = parse_table_f3(&data[..]);
-    table.extend(new_table);
-
-    let data = include_bytes!("../tables/BUFR_TableD_en_18.csv");
-    let new_table = parse_table_f3(&data[..]);
-    table.extend(new_table);
-
-    let data = include_bytes!("../tables/BUFR_TableD_en_19.csv");
-    let new_table = parse_table_f3(&data[..]);
-    table.extend(new_table);
-
-    let data = include_bytes!("../tables/BUFR_TableD_en_20.csv");
-    let new_table = parse_table_f3(&data[..]);
-    table.extend(new_table);
-
-    let data = include_bytes!("../tables/BUFR_TableD_en_21.csv");
-    let new_table = parse_table_f3(&data[..]);
-    table.extend(new_table);
-
-    let data = include_bytes!("../tables/BUFR_TableD_en_22.csv");
-    let new_table = parse_table_f3(&data[..]);
-    table.extend(new_table);
-
-    let data = include_bytes!("../tables/BUFR_TableD_en_23.csv");
-    let new_table = parse_table_f3(&data[..]);
-    table.extend(new_table);
-
-    let data = include_bytes!("../tables/BUFR_TableD_en_24.csv");
-    let new_table = parse_table_f3(&data[..]);
-    table.extend(new_table);
-
-    let data = include_bytes!("../tables/BUFR_TableD_en_25.csv");
-    let new_table = parse_table_f3(&data[..]);
-    table.extend(new_table);
-
-    let data = include_bytes!("../tables/BUFR_TableD_en_26.csv");
-    let new_table = parse_table_f3(&data[..]);
-    table.extend(new_table);
-
-    let data = include_bytes!("../tables/BUFR_TableD_en_27.csv");
-    let new_table = parse_table_f3(&data[..]);
-    table.extend(new_table);
-
-    let data = include_bytes!("../tables/BUFR_TableD_en_28.csv");
-    let new_table = parse_table_f3(&data[..]);
-    table.extend(new_table);
-
-    let data = include_bytes!("../tables/BUFR_TableD_en_29.csv");
-    let new_table = parse_table_f3(&data[..]);
-    table.extend(new_table);
-
-    let data = include_bytes!("../tables/BUFR_TableD_en_30.csv");
-    let new_table = parse_table_f3(&data[..]);
-    table.extend(new_table);
+/// Table D entry embedded in the crate: title and descriptors
+type EmbeddedSequence = ((u8, u8), Option<&'static str>, &'static [Descriptor]);
+
+/// Tables of one version of the WMO master table, compiled in by build.rs
+///
+/// Each table is sorted by descriptor, for binary searches.
+#[derive(Debug)]
+struct EmbeddedVersion {
+    version: u8,
+    table_b: &'static [((u8, u8), ElementDescriptor)],
+    table_d: &'static [EmbeddedSequence],
+    code_flag: &'static [((u8, u8), CodeTable)],
+}
 
-    let data = include_bytes!("../tables/BUFR_TableD_en_31.csv");
-    let new_table = parse_table_f3(&data[..]);
-    table.extend(new_table);
+// EMBEDDED and EMBEDDED_VERSIONS, generated by build.rs from the CSV files
+// of tables/ for the versions enabled by the `wmo-*` features
+include!(concat!(env!("OUT_DIR"), "/embedded_tables.rs"));
 
-    let data = include_bytes!("../tables/BUFR_TableD_en_32.csv");
-    let new_table = parse_table_f3(&data[..]);
-    table.extend(new_table);
+impl EmbeddedVersion {
+    fn element(&self, x: u8, y: u8) -> Option<&'static ElementDescriptor> {
+        let table = self.table_b;
+        let i = table.binary_search_by_key(&(x, y), |e| e.0).ok()?;
+        Some(&table[i].1)
+    }
 
-    let data = include_bytes!("../tables/BUFR_TableD_en_33.csv");
-    let new_table = parse_table_f3(&data[..]);
-    table.extend(new_table);
+    fn sequence(&self, x: u8, y: u8) -> Option<&'static [Descriptor]> {
+        let table = self.table_d;
+        let i = table.binary_search_by_key(&(x, y), |s| s.0).ok()?;
+        Some(table[i].2)
+    }
 
-    let data = include_bytes!("../tables/BUFR_TableD_en_34.csv");
-    let new_table = parse_table_f3(&data[..]);
-    table.extend(new_table);
+    fn code_table(&self, x: u8, y: u8) -> Option<&'static CodeTable> {
+        let table = self.code_flag;
+        let i = table.binary_search_by_key(&(x, y), |c| c.0).ok()?;
+        Some(&table[i].1)
+    }
+}
 
-    let data = include_bytes!("../tables/BUFR_TableD_en_35.csv");
-    let new_table = parse_table_f3(&data[..]);
-    table.extend(new_table);
+/// Environment variable naming a directory of tables to load at runtime
+pub const TABLES_PATH_VAR: &str = "BUFR_TABLES_PATH";
 
-    let data = include_bytes!("../tables/glider.csv");
-    let new_table = parse_table_f3(&data[..]);
-    table.extend(new_table);
+/// Classes of the WMO Table B, from 0 to 47; classes 48 to 63 are local
+const WMO_CLASSES: u8 = 48;
 
-    table
-});
-
-#[derive(PartialEq, Debug)]
-pub enum Descriptor {
-    Element(u8, u8),     // F0
-    Replication(u8, u8), // F1
-    Operator(u8, u8),    // F2
-    Sequence(u8, u8),    // F3
-}
-
-impl fmt::Display for Descriptor {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let (fd, x, y) = match *self {
-            Descriptor::Element(x, y) => (0, x, y),
-            Descriptor::Replication(x, y) => (1, x, y),
-            Descriptor::Operator(x, y) => (2, x, y),
-            Descriptor::Sequence(x, y) => (3, x, y),
-        };
-        write!(f, "Descriptor {{ f: {}, x: {}, y: {} }}", fd, x, y)
-    }
-}
+/// Classes of the WMO Table B reserved for future use, without any element
+pub const RESERVED_CLASSES: &[u8] = &[9, 16, 17, 18, 32, 34, 36, 37, 38, 39, 43, 44, 45, 46, 47];
 
-#[derive(PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug)]
 pub(crate) struct F3 {
-    descriptors: Vec<Descriptor>,
-    title: Option<String>,
+    pub(crate) descriptors: Vec<Descriptor>,
+    pub(crate) title: Option<String>,
 }
 
 impl F3 {
@@ -369,15 +98,16 @@ impl TryFrom<Descriptor> for &ElementDescriptor {
     type Error = crate::Error;
 
     fn try_from(value: Descriptor) -> Result<Self, Self::Error> {
-        match value {
-            Descriptor::Element(x, y) => Ok(&TABLE_F0[&(x, y)]),
-            _ => unimplemented!(),
-        }
+        let Descriptor { f: 0, x, y } = value else {
+            return Err(Error::NotAnElement(value.f, value.x, value.y));
+        };
+        EMBEDDED
+            .last()
+            .and_then(|embedded| embedded.element(x, y))
+            .ok_or(Error::UnknownDescriptor(0, x, y))
     }
 }
 
-//Descriptor::Element()
-
 #[allow(dead_code, non_snake_case)]
 #[derive(Debug, Deserialize)]
 pub struct RecordF0 {
@@ -418,104 +148,94 @@ impl F3 {
     }
 }
 
-use crate::BUFRUnit;
-
-impl std::str::FromStr for BUFRUnit {
-    type Err = crate::Error;
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        Ok(match s {
-            "Code table" => BUFRUnit::CodeTable,
-            "Flag table" => BUFRUnit::FlagTable,
-            "Numeric" => BUFRUnit::Numeric,
-            "a" => BUFRUnit::Year,
-            "d" => BUFRUnit::Day,
-            "m/s" => BUFRUnit::MeterPerSecond,
-            "m s-1" => BUFRUnit::MeterPerSecond,
-            "CCITT IA5" => BUFRUnit::CCITTIA5,
-            "degree true" => BUFRUnit::DegreeTrue,
-            "Code table defined by originating/generating centre" => BUFRUnit::CodeTableOriginator,
-            "h" => BUFRUnit::Hour,
-            "min" => BUFRUnit::Minute,
-            "m" => BUFRUnit::Meter,
-            "mon" => BUFRUnit::Month,
-            "s" => BUFRUnit::Second,
-            "deg" => BUFRUnit::Degree,
-            "Common Code table C-1" => BUFRUnit::CC1,
-            "Common Code table C-12" => BUFRUnit::CC12,
-            "Common Code table C-11" => BUFRUnit::CC11,
-            "Common Code table C-14" => BUFRUnit::CC14,
-            "K" => BUFRUnit::Kelvin,
-            "C" => BUFRUnit::Celsius,
-            "Hz" => BUFRUnit::Hertz,
-            "kg" => BUFRUnit::Kilogram,
-            "kg l-1" => BUFRUnit::KilogramPerLiter,
-            "kg m-3" => BUFRUnit::KilogramPerCubicMeter,
-            "m2" => BUFRUnit::SquareMeter,
-            "m3" => BUFRUnit::CubicMeter,
-            "m3/s" => BUFRUnit::CubicMeterPerSecond,
-            "m3 s-1" => BUFRUnit::CubicMeterPerSecond,
-            "m2 s-2" => BUFRUnit::SquareMeterPerSquareSecond,
-            "Pa" => BUFRUnit::Pascal,
-            _ => unimplemented!("Unrecognized unit: {}", s),
-        })
-    }
-}
+impl TryFrom<RecordF0> for ElementDescriptor {
+    type Error = crate::Error;
 
-impl From<RecordF0> for ElementDescriptor {
-    fn from(v: RecordF0) -> Self {
-        let unit = v.BUFR_Unit.parse().expect("Unknown unit");
+    fn try_from(v: RecordF0) -> Result<Self, Self::Error> {
+        let unit = BUFRUnit::from(v.BUFR_Unit.as_str());
 
-        Self {
-            name: v.ElementName_en,
+        Ok(Self {
+            name: v.ElementName_en.into(),
             unit,
             scale: v.BUFR_Scale,
             reference_value: v.BUFR_ReferenceValue,
             data_width: v.BUFR_DataWidth_Bits,
-        }
+        })
     }
 }
 
-#[allow(dead_code)]
-fn load_table_f0<P: AsRef<Path>>(filename: P) -> TableF0 {
-    let path = filename.as_ref();
-    let file = File::open(path).unwrap_or_else(|_| panic!("Error loading file: {:?}", path));
+/// Splits a "FXXYYY" code in its parts
+pub(crate) fn parse_fxy(code: &str) -> Result<(u8, u8, u8), Error> {
+    let invalid = || Error::InvalidTable(format!("Invalid descriptor code: {}", code));
+    if code.len() != 6 {
+        return Err(invalid());
+    }
+    let f = code
+        .get(0..1)
+        .ok_or_else(invalid)?
+        .parse()
+        .map_err(|_| invalid())?;
+    let x = code
+        .get(1..3)
+        .ok_or_else(invalid)?
+        .parse()
+        .map_err(|_| invalid())?;
+    let y = code
+        .get(3..)
+        .ok_or_else(invalid)?
+        .parse()
+        .map_err(|_| invalid())?;
+    Ok((f, x, y))
+}
+
+/// Parses a "FXXYYY" code into a descriptor
+pub(crate) fn parse_descriptor(code: &str) -> Result<Descriptor, Error> {
+    match parse_fxy(code)? {
+        (f, x, y) if f <= 3 && x <= 63 => Ok(Descriptor::new(f, x, y)),
+        (f, _, _) => Err(Error::InvalidTable(format!("Unknown f: {}", f))),
+    }
+}
+
+fn load_table_f0<P: AsRef<Path>>(filename: P) -> Result<TableF0, Error> {
+    let file = File::open(filename)?;
     let reader = BufReader::new(file);
     parse_table_f0(reader)
 }
 
-fn parse_table_f0<R: std::io::Read>(reader: R) -> TableF0 {
+fn parse_table_f0<R: std::io::Read>(reader: R) -> Result<TableF0, Error> {
     let mut table = TableF0::default();
 
     let mut rdr = csv::Reader::from_reader(reader);
     for result in rdr.deserialize() {
-        let record: RecordF0 = result.expect("Error leading record");
-        //let f: u8 = record.FXY.get(0..1).expect("").parse().expect("");
-        let x: u8 = record.FXY.get(1..=2).expect("").parse().expect("");
-        let y: u8 = record.FXY.get(3..).expect("").parse().expect("");
-        table.insert((x, y), record.into());
+        let record: RecordF0 = result.map_err(|e| Error::InvalidTable(e.to_string()))?;
+        let (_, x, y) = parse_fxy(&record.FXY)?;
+        table.insert((x, y), record.try_into()?);
     }
-    table
+    Ok(table)
 }
 
-#[allow(dead_code)]
-fn load_table_f3<P: AsRef<Path>>(filename: P) -> TableF3 {
-    let path = filename.as_ref();
-    let file = File::open(path).unwrap_or_else(|_| panic!("Error loading file: {:?}", path));
+fn load_table_f3<P: AsRef<Path>>(filename: P) -> Result<TableF3, Error> {
+    let file = File::open(filename)?;
     let reader = BufReader::new(file);
     parse_table_f3(reader)
 }
 
-fn parse_table_f3<R: std::io::Read>(reader: R) -> TableF3 {
+fn parse_table_f3<R: std::io::Read>(reader: R) -> Result<TableF3, Error> {
     let mut table = TableF3::default();
 
     let mut rdr = csv::Reader::from_reader(reader);
     for result in rdr.deserialize() {
-        let record: RecordF3 = result.expect("Error leading record");
-        let f: u8 = record.FXY1.get(0..1).expect("").parse().expect("");
-        assert_eq!(f, 3);
-        let x: u8 = record.FXY1.get(1..=2).expect("").parse().expect("");
-        let y: u8 = record.FXY1.get(3..).expect("").parse().expect("");
+        let record: RecordF3 = result.map_err(|e| Error::InvalidTable(e.to_string()))?;
+        let (x, y) = match parse_fxy(&record.FXY1)? {
+            (3, x, y) => (x, y),
+            _ => {
+                return Err(Error::InvalidTable(format!(
+                    "Not a sequence: {}",
+                    record.FXY1
+                )))
+            }
+        };
+        let descriptor = Descriptor::try_from(&record)?;
         table
             .entry((x, y))
             .and_modify(|v| {
@@ -523,38 +243,661 @@ fn parse_table_f3<R: std::io::Read>(reader: R) -> TableF3 {
                 // - verify that v.title is a superset of record.Title_en
                 // - only warn, not assert
                 //assert_eq!(record.Title_en, v.title);
-                v.descriptors.push(record.clone().into())
+                v.descriptors.push(descriptor)
             })
-            .or_insert({
-                let title = record.Title_en.clone();
-                F3 {
-                    descriptors: vec![record.into()],
-                    title,
-                }
+            .or_insert(F3 {
+                descriptors: vec![descriptor],
+                title: record.Title_en,
             });
     }
-    table
+    Ok(table)
+}
+
+/// Source of Table B and Table D entries
+///
+/// Decoding and encoding only go through this trait, so tables can come
+/// from anywhere.
+pub trait TableProvider {
+    /// Table B entry of the element descriptor 0-X-Y
+    fn element(&self, x: u8, y: u8) -> Option<&ElementDescriptor>;
+
+    /// Table D expansion of the sequence descriptor 3-X-Y
+    fn sequence(&self, x: u8, y: u8) -> Option<Vec<Descriptor>>;
+
+    /// Code or flag table of the element descriptor 0-X-Y
+    fn code_table(&self, x: u8, y: u8) -> Option<&CodeTable> {
+        let _ = (x, y);
+        None
+    }
+
+    /// Meaning of a value of an element with a code or flag table
+    ///
+    /// Sub-centres, from Common Code table C-12, need their centre, so
+    /// they are only named by the code tables of the element.
+    fn meaning(&self, descriptor: &Descriptor, value: &Value) -> Option<Meaning> {
+        code_meaning(self, descriptor, value, None)
+    }
+}
+
+/// Meaning of a value of an element with a code or flag table, with the
+/// centre sub-centres belong to
+///
+/// The code tables of the element come first, then the Common Code tables
+/// embedded in the crate.
+pub(crate) fn code_meaning<T: TableProvider + ?Sized>(
+    tables: &T,
+    descriptor: &Descriptor,
+    value: &Value,
+    centre: Option<u16>,
+) -> Option<Meaning> {
+    let (x, y) = match descriptor {
+        Descriptor { f: 0, x, y } => (*x, *y),
+        _ => return None,
+    };
+    let value = match value {
+        Value::Integer(v) => u64::try_from(*v).ok()?,
+        _ => return None,
+    };
+    let element = tables.element(x, y)?;
+    let code_table = tables.code_table(x, y);
+    match element.unit {
+        BUFRUnit::FlagTable => {
+            let flags = code_table?.flags(value, element.data_width);
+            let flags = flags.into_iter().map(|(b, m)| (b, m.to_string()));
+            Some(Meaning::Flags(flags.collect()))
+        }
+        ref unit if unit.is_code() => {
+            let name = match (code_table.and_then(|t| t.entry(value)), unit) {
+                (Some(name), _) => name,
+                (None, BUFRUnit::CC1 | BUFRUnit::CC11) => centre_name(u16::try_from(value).ok()?)?,
+                (None, BUFRUnit::CC12) => sub_centre_name(centre?, u16::try_from(value).ok()?)?,
+                _ => return None,
+            };
+            Some(Meaning::Code(name.to_string()))
+        }
+        _ => None,
+    }
+}
+
+/// Table B, Table D and the code and flag tables of one version of a
+/// master table
+///
+/// Entries added at runtime are searched before those embedded in the
+/// crate, which are never copied.
+#[derive(Clone, Debug, Default)]
+pub struct Tables {
+    embedded: Option<&'static EmbeddedVersion>,
+    f0: TableF0,
+    f3: TableF3,
+    codes: TableCodeFlag,
+}
+
+impl Tables {
+    /// Tables embedded in the crate for the most recent version, empty
+    /// when no version is embedded
+    pub(crate) fn embedded() -> Tables {
+        EMBEDDED.last().map(Tables::shared).unwrap_or_default()
+    }
+
+    /// Tables made of the embedded ones of a version
+    fn shared(embedded: &'static EmbeddedVersion) -> Tables {
+        Tables {
+            embedded: Some(embedded),
+            ..Tables::default()
+        }
+    }
+
+    /// Table B, with the embedded entries copied under those added
+    fn table_b(&self) -> Cow<'_, TableF0> {
+        let Some(embedded) = self.embedded else {
+            return Cow::Borrowed(&self.f0);
+        };
+        let mut table: TableF0 = embedded.table_b.iter().cloned().collect();
+        table.extend(self.f0.iter().map(|(&k, v)| (k, v.clone())));
+        Cow::Owned(table)
+    }
+
+    /// Table D, with the embedded entries copied under those added
+    fn table_d(&self) -> Cow<'_, TableF3> {
+        let Some(embedded) = self.embedded else {
+            return Cow::Borrowed(&self.f3);
+        };
+        let mut table: TableF3 = embedded
+            .table_d
+            .iter()
+            .map(|&(key, title, descriptors)| {
+                let sequence = F3 {
+                    descriptors: descriptors.to_vec(),
+                    title: title.map(str::to_string),
+                };
+                (key, sequence)
+            })
+            .collect();
+        table.extend(self.f3.iter().map(|(&k, v)| (k, v.clone())));
+        Cow::Owned(table)
+    }
+
+    /// Classes of the WMO Table B without any element in these tables
+    ///
+    /// Reserved classes aren't missing, as WMO defines no element in them.
+    pub fn missing_classes(&self) -> Vec<u8> {
+        let embedded = self.embedded.map_or(&[][..], |e| e.table_b);
+        let classes: BTreeSet<u8> = (self.f0.keys().chain(embedded.iter().map(|(k, _)| k)))
+            .map(|&(x, _)| x)
+            .collect();
+        (0..WMO_CLASSES)
+            .filter(|x| !RESERVED_CLASSES.contains(x) && !classes.contains(x))
+            .collect()
+    }
+
+    /// Elements in code or flag table units without a code or flag table
+    ///
+    /// The values of these elements aren't named. Common code tables and
+    /// those defined by the originating centre aren't listed.
+    pub fn missing_code_tables(&self) -> Vec<Descriptor> {
+        let mut missing: Vec<Descriptor> = self
+            .table_b()
+            .iter()
+            .filter(|(_, e)| matches!(e.unit, BUFRUnit::CodeTable | BUFRUnit::FlagTable))
+            .filter(|(&(x, y), _)| self.code_table(x, y).is_none())
+            .map(|(&(x, y), _)| Descriptor::new(0, x, y))
+            .collect();
+        missing.sort_unstable_by_key(|d| (d.x, d.y));
+        missing
+    }
+
+    /// Add the entries of a Table B CSV file, in the WMO format
+    pub fn add_table_b<R: std::io::Read>(&mut self, reader: R) -> Result<(), Error> {
+        let table = parse_table_f0(reader)?;
+        self.f0.extend(table);
+        Ok(())
+    }
+
+    /// Add the entries of a Table D CSV file, in the WMO format
+    pub fn add_table_d<R: std::io::Read>(&mut self, reader: R) -> Result<(), Error> {
+        let table = parse_table_f3(reader)?;
+        self.f3.extend(table);
+        Ok(())
+    }
+
+    /// Add the entries of a code and flag table CSV file, in the WMO format
+    ///
+    /// Tables of descriptors already known are replaced.
+    pub fn add_code_flag<R: std::io::Read>(&mut self, reader: R) -> Result<(), Error> {
+        let table = parse_code_flag(reader)?;
+        self.codes.extend(table);
+        Ok(())
+    }
+
+    /// Load a Table B CSV file, in the WMO format
+    pub fn load_table_b<P: AsRef<Path>>(&mut self, filename: P) -> Result<(), Error> {
+        let table = load_table_f0(filename)?;
+        self.f0.extend(table);
+        Ok(())
+    }
+
+    /// Load a Table D CSV file, in the WMO format
+    pub fn load_table_d<P: AsRef<Path>>(&mut self, filename: P) -> Result<(), Error> {
+        let table = load_table_f3(filename)?;
+        self.f3.extend(table);
+        Ok(())
+    }
+
+    /// Load a code and flag table CSV file, in the WMO format
+    pub fn load_code_flag<P: AsRef<Path>>(&mut self, filename: P) -> Result<(), Error> {
+        let table = load_code_flag(filename)?;
+        self.codes.extend(table);
+        Ok(())
+    }
+
+    /// Load a Table B, Table D or code and flag table file, told apart by
+    /// its header
+    pub fn load<P: AsRef<Path>>(&mut self, filename: P) -> Result<(), Error> {
+        let mut header = String::new();
+        BufReader::new(File::open(&filename)?).read_line(&mut header)?;
+        if header.contains("BUFR_DataWidth_Bits") {
+            self.load_table_b(filename)
+        } else if header.contains("FXY2") {
+            self.load_table_d(filename)
+        } else if header.contains("CodeFigure") {
+            self.load_code_flag(filename)
+        } else {
+            Err(Error::InvalidTable(format!(
+                "Neither a Table B, a Table D nor a code table: {}",
+                filename.as_ref().display()
+            )))
+        }
+    }
+
+    /// Add the entries of an ecCodes `element.table`
+    pub fn add_eccodes_elements<R: std::io::Read>(&mut self, reader: R) -> Result<(), Error> {
+        let table = parse_element_table(reader)?;
+        self.f0.extend(table);
+        Ok(())
+    }
+
+    /// Add the entries of an ecCodes `sequence.def`
+    pub fn add_eccodes_sequences<R: std::io::Read>(&mut self, reader: R) -> Result<(), Error> {
+        let table = parse_sequence_def(reader)?;
+        self.f3.extend(table);
+        Ok(())
+    }
+
+    /// Add Table B and Table D of an NCEP DX table message
+    pub fn add_dx(&mut self, message: &crate::Message) -> Result<(), Error> {
+        let (f0, f3) = parse_dx(&message.section4.data)?;
+        self.f0.extend(f0);
+        self.f3.extend(f3);
+        Ok(())
+    }
+
+    /// Add the Table B and Table D entries of a table message, of data
+    /// category 11
+    pub fn add_table_message(&mut self, message: &crate::Message) -> Result<(), Error> {
+        if !is_table_message(message) {
+            return Err(Error::InvalidTable(format!(
+                "Data category {} is not a table message",
+                message.section1().data_category()
+            )));
+        }
+        let (f0, f3) = parse_table_message(&message.subsets()?)?;
+        self.f0.extend(f0);
+        self.f3.extend(f3);
+        Ok(())
+    }
+
+    /// Table messages carrying all the Table B and Table D entries
+    ///
+    /// Every message has the given section 1, with data category 11. Each
+    /// holds up to 255 entries of each table.
+    pub fn table_messages(
+        &self,
+        section1: Section1,
+    ) -> Result<Vec<crate::Message<'static>>, Error> {
+        table_messages(&self.table_b(), &self.table_d(), section1)
+    }
+
+    /// Load the `element.table` and `sequence.def` of an ecCodes table
+    /// directory, like `definitions/bufr/tables/0/local/1/98/0`
+    ///
+    /// Either file may be missing, but not both.
+    pub fn load_eccodes<P: AsRef<Path>>(&mut self, dir: P) -> Result<(), Error> {
+        let dir = dir.as_ref();
+        let elements = dir.join("element.table");
+        let sequences = dir.join("sequence.def");
+        if !elements.exists() && !sequences.exists() {
+            return Err(Error::InvalidTable(format!(
+                "No element.table nor sequence.def in {}",
+                dir.display()
+            )));
+        }
+        if elements.exists() {
+            self.add_eccodes_elements(File::open(elements)?)?;
+        }
+        if sequences.exists() {
+            self.add_eccodes_sequences(File::open(sequences)?)?;
+        }
+        Ok(())
+    }
+
+    /// Write Table B as an ecCodes `element.table`
+    pub fn write_eccodes_elements<W: std::io::Write>(&self, writer: W) -> Result<(), Error> {
+        write_element_table(&self.table_b(), writer)
+    }
+
+    /// Write Table D as an ecCodes `sequence.def`
+    pub fn write_eccodes_sequences<W: std::io::Write>(&self, writer: W) -> Result<(), Error> {
+        write_sequence_def(&self.table_d(), writer)
+    }
+
+    /// Write `element.table` and `sequence.def` to a directory, created if
+    /// needed, for ecCodes to use
+    pub fn save_eccodes<P: AsRef<Path>>(&self, dir: P) -> Result<(), Error> {
+        let dir = dir.as_ref();
+        std::fs::create_dir_all(dir)?;
+        let mut writer = BufWriter::new(File::create(dir.join("element.table"))?);
+        self.write_eccodes_elements(&mut writer)?;
+        writer.flush()?;
+        let mut writer = BufWriter::new(File::create(dir.join("sequence.def"))?);
+        self.write_eccodes_sequences(&mut writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Load every CSV file in a directory, in name order
+    ///
+    /// Entries from later files replace those from earlier ones.
+    pub fn from_dir<P: AsRef<Path>>(dir: P) -> Result<Tables, Error> {
+        let mut filenames = vec![];
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension() == Some(std::ffi::OsStr::new("csv")) {
+                filenames.push(path);
+            }
+        }
+        filenames.sort();
+
+        let mut tables = Tables::default();
+        for filename in filenames {
+            tables.load(filename)?;
+        }
+        Ok(tables)
+    }
+}
+
+impl TableProvider for Tables {
+    fn element(&self, x: u8, y: u8) -> Option<&ElementDescriptor> {
+        self.f0
+            .get(&(x, y))
+            .or_else(|| self.embedded?.element(x, y))
+    }
+
+    fn sequence(&self, x: u8, y: u8) -> Option<Vec<Descriptor>> {
+        match self.f3.get(&(x, y)) {
+            Some(sequence) => Some(sequence.descriptors.clone()),
+            None => self.embedded?.sequence(x, y).map(<[_]>::to_vec),
+        }
+    }
+
+    fn code_table(&self, x: u8, y: u8) -> Option<&CodeTable> {
+        self.codes
+            .get(&(x, y))
+            .or_else(|| self.embedded?.code_table(x, y))
+    }
+}
+
+/// Versions of the master tables available for decoding
+///
+/// Messages are decoded with the tables of the master table and version in
+/// their section 1. When that version isn't available, the nearest one is
+/// used instead: preferably the oldest newer version, since WMO only adds
+/// entries, otherwise the most recent older one.
+#[derive(Clone, Debug, Default)]
+pub struct MasterTables {
+    versions: BTreeMap<(u8, u8), Tables>,
+}
+
+impl MasterTables {
+    /// WMO tables embedded in the crate
+    ///
+    /// Which versions are embedded depends on the `wmo-*` features, see
+    /// `EMBEDDED_VERSIONS`.
+    pub fn wmo() -> MasterTables {
+        let mut tables = MasterTables::default();
+        for embedded in EMBEDDED {
+            tables.insert(0, embedded.version, Tables::shared(embedded));
+        }
+        tables
+    }
+
+    /// Embedded WMO tables, with those of the directory in the
+    /// `BUFR_TABLES_PATH` environment variable on top when it is set
+    pub fn from_env() -> Result<MasterTables, Error> {
+        let mut tables = MasterTables::wmo();
+        if let Some(dir) = std::env::var_os(TABLES_PATH_VAR) {
+            tables.load_dir(dir)?;
+        }
+        Ok(tables)
+    }
+
+    /// Load the versions of the WMO master table in a directory
+    ///
+    /// Each subdirectory named after a version number, like `35/`, holds
+    /// the CSV files of that version, which replace any tables already
+    /// available for it. Other entries are ignored.
+    pub fn load_dir<P: AsRef<Path>>(&mut self, dir: P) -> Result<(), Error> {
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            let version = path
+                .file_name()
+                .and_then(|name| name.to_str()?.parse().ok());
+            if let (Some(version), true) = (version, path.is_dir()) {
+                self.insert(0, version, Tables::from_dir(&path)?);
+            }
+        }
+        Ok(())
+    }
+
+    /// Add or replace the tables of one version
+    pub fn insert(&mut self, master_table: u8, version: u8, tables: Tables) {
+        self.versions.insert((master_table, version), tables);
+    }
+
+    /// Available master tables and versions
+    pub fn versions(&self) -> impl Iterator<Item = (u8, u8)> + '_ {
+        self.versions.keys().copied()
+    }
+
+    /// Tables for a version, or the nearest available one
+    ///
+    /// The version actually selected is returned with the tables.
+    pub fn select(&self, master_table: u8, version: u8) -> Result<(u8, &Tables), Error> {
+        let newer = self
+            .versions
+            .range((master_table, version)..=(master_table, u8::MAX))
+            .next();
+        let older = self
+            .versions
+            .range((master_table, 0)..(master_table, version))
+            .next_back();
+        newer
+            .or(older)
+            .map(|(&(_, v), tables)| (v, tables))
+            .ok_or(Error::NoTables(master_table))
+    }
 }
 
-impl From<RecordF3> for Descriptor {
-    fn from(record: RecordF3) -> Self {
-        let f: u8 = record.FXY2.get(0..1).expect("").parse().expect("");
-        let x: u8 = record.FXY2.get(1..=2).expect("").parse().expect("");
-        let y: u8 = record.FXY2.get(3..).expect("").parse().expect("");
+/// Local tables on top of the master tables
+///
+/// Local tables are chosen by the centre, sub-centre and local table
+/// version of section 1, and are searched before the master tables.
+///
+/// The plans compiled to decode messages with the tables are kept in the
+/// set, and forgotten whenever the tables change.
+#[derive(Clone, Debug, Default)]
+pub struct TableSet {
+    master: MasterTables,
+    local: HashMap<(u16, u16, u8), Tables>,
+    default_local: Option<Tables>,
+    plans: PlanCache,
+}
 
-        match f {
-            0 => Descriptor::Element(x, y),
-            1 => Descriptor::Replication(x, y),
-            2 => Descriptor::Operator(x, y),
-            3 => Descriptor::Sequence(x, y),
-            _ => unimplemented!("Unknown f: {}", f),
+impl From<MasterTables> for TableSet {
+    fn from(master: MasterTables) -> Self {
+        TableSet {
+            master,
+            local: HashMap::default(),
+            default_local: None,
+            plans: PlanCache::default(),
         }
     }
 }
 
+impl TableSet {
+    /// Embedded WMO tables, without local tables
+    pub fn wmo() -> TableSet {
+        TableSet::from(MasterTables::wmo())
+    }
+
+    /// Embedded WMO tables and those of `BUFR_TABLES_PATH`, without local
+    /// tables
+    ///
+    /// This is what messages are decoded with unless other tables are
+    /// given.
+    pub fn from_env() -> Result<TableSet, Error> {
+        MasterTables::from_env().map(TableSet::from)
+    }
+
+    /// Add or replace the local tables of a centre
+    pub fn insert_local(&mut self, centre: u16, sub_centre: u16, version: u8, tables: Tables) {
+        self.local.insert((centre, sub_centre, version), tables);
+        self.plans.clear();
+    }
+
+    /// Local tables for every message without local tables of its own
+    ///
+    /// NCEP files carry such tables in their first messages.
+    pub fn set_default_local(&mut self, tables: Tables) {
+        self.default_local = Some(tables);
+        self.plans.clear();
+    }
+
+    /// Merge the entries of a table message
+    ///
+    /// They go to the local tables of the centre when section 1 has a local
+    /// table version, otherwise to the master tables of its version, which
+    /// start as a copy of the nearest version available.
+    pub fn add_table_message(&mut self, message: &crate::Message) -> Result<(), Error> {
+        self.plans.clear();
+        let section1 = message.section1();
+        let tables = match section1.local_table_version() {
+            0 | 255 => {
+                let key = (section1.master_table(), section1.master_table_version());
+                if !self.master.versions.contains_key(&key) {
+                    let (_, nearest) = self.master.select(key.0, key.1)?;
+                    let nearest = nearest.clone();
+                    self.master.insert(key.0, key.1, nearest);
+                }
+                self.master.versions.get_mut(&key).expect("Inserted above")
+            }
+            version => self
+                .local
+                .entry((section1.center(), section1.sub_center(), version))
+                .or_default(),
+        };
+        tables.add_table_message(message)
+    }
+
+    /// Load the local tables of a centre from a directory of CSV files
+    pub fn load_local<P: AsRef<Path>>(
+        &mut self,
+        centre: u16,
+        sub_centre: u16,
+        version: u8,
+        dir: P,
+    ) -> Result<(), Error> {
+        let tables = Tables::from_dir(dir)?;
+        self.insert_local(centre, sub_centre, version, tables);
+        Ok(())
+    }
+
+    pub fn master(&self) -> &MasterTables {
+        &self.master
+    }
+
+    pub fn master_mut(&mut self) -> &mut MasterTables {
+        self.plans.clear();
+        &mut self.master
+    }
+
+    /// Plans compiled to decode messages with the tables
+    pub fn plans(&self) -> &PlanCache {
+        &self.plans
+    }
+
+    /// Plan decoding the descriptors of a message, or `None` when they have
+    /// to be walked
+    pub(crate) fn plan(
+        &self,
+        section1: &Section1,
+        descriptors: &[Descriptor],
+    ) -> Result<Option<Arc<DecodePlan>>, Error> {
+        let layers = self.resolve(section1)?;
+        // Every message without local tables of its own uses the same ones
+        let local = match section1.local_table_version() {
+            0 | 255 => None,
+            version => Some((section1.center(), section1.sub_center(), version))
+                .filter(|key| self.local.contains_key(key)),
+        };
+        let key = (section1.master_table(), layers.master_version(), local);
+        Ok(self.plans.get_or_compile(key, descriptors, &layers))
+    }
+
+    /// Tables for the message described by a section 1
+    pub fn resolve(&self, section1: &Section1) -> Result<Layers<'_>, Error> {
+        let (version, master) = self
+            .master
+            .select(section1.master_table(), section1.master_table_version())?;
+        // 0 means no local tables, 255 is missing
+        let local = match section1.local_table_version() {
+            0 | 255 => None,
+            version => self
+                .local
+                .get(&(section1.center(), section1.sub_center(), version)),
+        }
+        .or(self.default_local.as_ref());
+        Ok(Layers {
+            local,
+            master,
+            version,
+        })
+    }
+}
+
+/// Tables resolved for one message: local first, then master
+#[derive(Clone, Copy, Debug)]
+pub struct Layers<'a> {
+    local: Option<&'a Tables>,
+    master: &'a Tables,
+    version: u8,
+}
+
+impl<'a> Layers<'a> {
+    /// Whether local tables were found for the message
+    pub fn has_local(&self) -> bool {
+        self.local.is_some()
+    }
+
+    /// Version of the master tables in use
+    pub fn master_version(&self) -> u8 {
+        self.version
+    }
+}
+
+impl<'a> TableProvider for Layers<'a> {
+    fn element(&self, x: u8, y: u8) -> Option<&ElementDescriptor> {
+        self.local
+            .and_then(|t| t.element(x, y))
+            .or_else(|| self.master.element(x, y))
+    }
+
+    fn sequence(&self, x: u8, y: u8) -> Option<Vec<Descriptor>> {
+        self.local
+            .and_then(|t| t.sequence(x, y))
+            .or_else(|| self.master.sequence(x, y))
+    }
+
+    fn code_table(&self, x: u8, y: u8) -> Option<&CodeTable> {
+        self.local
+            .and_then(|t| t.code_table(x, y))
+            .or_else(|| self.master.code_table(x, y))
+    }
+}
+
+/// Default tables, or why they couldn't be loaded
+static TABLE_SET: Lazy<Result<TableSet, String>> =
+    Lazy::new(|| TableSet::from_env().map_err(|e| e.to_string()));
+
+/// Tables used when none are given
+pub(crate) fn default_table_set() -> Result<&'static TableSet, Error> {
+    TABLE_SET.as_ref().map_err(|e| Error::TablesPath(e.clone()))
+}
+
+impl TryFrom<&RecordF3> for Descriptor {
+    type Error = crate::Error;
+
+    fn try_from(record: &RecordF3) -> Result<Self, Self::Error> {
+        parse_descriptor(&record.FXY2)
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{load_table_f0, load_table_f3, Descriptor, TABLE_F0, TABLE_F3};
+    use super::{load_table_f0, load_table_f3, Descriptor, MasterTables, TableProvider, Tables};
+    #[cfg(feature = "wmo-35")]
+    use super::{TableSet, RESERVED_CLASSES, WMO_CLASSES};
+    #[cfg(feature = "wmo-35")]
+    use crate::identification::{Section1, Section1v4Builder};
     use crate::{BUFRUnit, ElementDescriptor, Error};
 
     use std::path::PathBuf;
@@ -562,9 +905,9 @@ mod tests {
     #[test]
     fn validate_load_f0() {
         let mut filename = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
-        filename.push("tables/BUFRCREX_TableB_en_01.csv");
+        filename.push("tables/35/BUFRCREX_TableB_en_01.csv");
 
-        let table = load_table_f0(filename);
+        let table = load_table_f0(filename).unwrap();
         for ((x, y), v) in table.into_iter() {
             if let Ok(ans) = element_descriptor_f0(x, y) {
                 assert_eq!(v, ans);
@@ -574,170 +917,382 @@ mod tests {
 
     #[test]
     fn validate_static_f0() {
-        for ((x, y), v) in TABLE_F0.iter() {
+        for ((x, y), v) in Tables::embedded().table_b().iter() {
             if let Ok(ans) = element_descriptor_f0(*x, *y) {
                 assert_eq!(v, &ans);
             }
         }
     }
 
+    #[cfg(feature = "wmo-35")]
+    #[test]
+    fn all_classes() {
+        let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tables/35");
+        let mut classes = vec![];
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            let name = path.file_name().unwrap().to_str().unwrap();
+            if let Some(class) = name.strip_prefix("BUFRCREX_TableB_en_") {
+                let table = load_table_f0(&path).unwrap();
+                assert!(!table.is_empty(), "{}", name);
+                for element in table.values() {
+                    assert!(!matches!(element.unit, BUFRUnit::Other(_)), "{}", name);
+                }
+                classes.push(class.trim_end_matches(".csv").parse::<u8>().unwrap());
+            }
+        }
+        classes.sort_unstable();
+        let expected: Vec<u8> = (0..WMO_CLASSES)
+            .filter(|x| !RESERVED_CLASSES.contains(x))
+            .collect();
+        assert_eq!(classes, expected);
+        assert!(Tables::embedded().missing_classes().is_empty());
+
+        let tables = Tables::embedded();
+        let temperature = tables.element(12, 101).unwrap();
+        assert_eq!(temperature.unit, BUFRUnit::Kelvin);
+        let radiance = tables.element(14, 43).unwrap();
+        assert_eq!(radiance.unit, BUFRUnit::RadiancePerWavelength);
+        // "Code table " in the WMO table
+        let code = tables.element(40, 56).unwrap();
+        assert_eq!(code.unit, BUFRUnit::CodeTable);
+    }
+
+    #[cfg(feature = "wmo-35")]
+    #[test]
+    fn missing_code_tables() {
+        let mut tables = Tables::embedded();
+        let missing = tables.missing_code_tables();
+        assert!(missing.contains(&Descriptor::new(0, 20, 3)));
+        assert!(missing.contains(&Descriptor::new(0, 40, 56)));
+        assert!(!missing.contains(&Descriptor::new(0, 2, 1)));
+        assert!(!missing.contains(&Descriptor::new(0, 8, 1)));
+        // Common code tables
+        assert!(!missing.contains(&Descriptor::new(0, 1, 33)));
+
+        let csv = "FXY,ElementName_en,CodeFigure,EntryName_en,EntryName_sub1_en,EntryName_sub2_en,Note_en,noteIDs,Status
+020003,Present weather,0,Cloud development not observed or not observable,,,,,Operational
+";
+        tables.add_code_flag(csv.as_bytes()).unwrap();
+        let after = tables.missing_code_tables();
+        assert_eq!(after.len(), missing.len() - 1);
+        assert!(!after.contains(&Descriptor::new(0, 20, 3)));
+    }
+
     #[test]
     fn validate_load_f3() {
         let mut filename = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
-        filename.push("tables/BUFR_TableD_en_01.csv");
+        filename.push("tables/35/BUFR_TableD_en_01.csv");
 
-        let table = load_table_f3(filename);
+        let table = load_table_f3(filename).unwrap();
         let record = table.get(&(1, 2)).unwrap();
         assert_eq!(record.len(), 3);
         assert_eq!(
             record.descriptors,
             vec![
-                Descriptor::Element(1, 3),
-                Descriptor::Element(1, 4),
-                Descriptor::Element(1, 5),
+                Descriptor::new(0, 1, 3),
+                Descriptor::new(0, 1, 4),
+                Descriptor::new(0, 1, 5),
             ]
         );
     }
 
+    #[cfg(feature = "wmo-35")]
     #[test]
     fn validate_static_f3() {
-        let record = TABLE_F3.get(&(1, 2)).unwrap();
+        let tables = Tables::embedded();
+        let table = tables.table_d();
+        let record = table.get(&(1, 2)).unwrap();
         assert_eq!(record.len(), 3);
         assert_eq!(
             record.descriptors,
             vec![
-                Descriptor::Element(1, 3),
-                Descriptor::Element(1, 4),
-                Descriptor::Element(1, 5),
+                Descriptor::new(0, 1, 3),
+                Descriptor::new(0, 1, 4),
+                Descriptor::new(0, 1, 5),
             ]
         );
     }
 
+    #[cfg(feature = "wmo-35")]
+    #[test]
+    fn select_version() {
+        let year = "04,Location and appearance,004001,Year,a,0,0,10,a,0,4,,,Operational\n";
+        let mut old = Tables::default();
+        old.add_table_b(format!("{}{}", TABLE_B_HEADER, year).as_bytes())
+            .unwrap();
+
+        let mut tables = MasterTables::wmo();
+        tables.insert(0, 12, old);
+        assert_eq!(tables.versions().collect::<Vec<_>>(), [(0, 12), (0, 35)]);
+
+        let (version, old) = tables.select(0, 12).unwrap();
+        assert_eq!(version, 12);
+        assert_eq!(old.element(4, 1).unwrap().data_width, 10);
+        // Newer tables are preferred
+        assert_eq!(tables.select(0, 13).unwrap().0, 35);
+        assert_eq!(tables.select(0, 11).unwrap().0, 12);
+        assert_eq!(tables.select(0, 40).unwrap().0, 35);
+        let (_, new) = tables.select(0, 35).unwrap();
+        assert_eq!(new.element(4, 1).unwrap().data_width, 12);
+
+        assert!(matches!(tables.select(10, 1), Err(Error::NoTables(10))));
+    }
+
+    const TABLE_B_HEADER: &str = "ClassNo,ClassName_en,FXY,ElementName_en,BUFR_Unit,BUFR_Scale,BUFR_ReferenceValue,BUFR_DataWidth_Bits,CREX_Unit,CREX_Scale,CREX_DataWidth_Char,Note_en,noteIDs,Status\n";
+    const TABLE_D_HEADER: &str = "Category,CategoryOfSequences_en,FXY1,Title_en,SubTitle_en,FXY2,ElementName_en,ElementDescription_en,Note_en,Status\n";
+
+    #[cfg(feature = "wmo-35")]
+    fn section1(center: u16, local_table_version: u8) -> Section1 {
+        Section1::V4(
+            Section1v4Builder::default()
+                .master_table(0)
+                .sub_center(0)
+                .center(center)
+                .update_version(0)
+                .optional_section(false)
+                .data_category(31)
+                .data_subcategory(0)
+                .local_subcategory(0)
+                .master_table_version(35)
+                .local_table_version(local_table_version)
+                .year(2020)
+                .month(10)
+                .day(6)
+                .hour(19)
+                .minute(24)
+                .second(0)
+                .build()
+                .unwrap(),
+        )
+    }
+
+    #[cfg(feature = "wmo-35")]
+    #[test]
+    fn layers() {
+        let mut local = Tables::default();
+        let year = "04,Location and appearance,004001,Year,a,0,0,10,a,0,4,,,Operational\n";
+        local
+            .add_table_b(format!("{}{}", TABLE_B_HEADER, year).as_bytes())
+            .unwrap();
+        let sequence = "50,Local,350001,Local,,004001,Year,,,Operational\n";
+        local
+            .add_table_d(format!("{}{}", TABLE_D_HEADER, sequence).as_bytes())
+            .unwrap();
+        let missing = local.missing_classes();
+        assert!(!missing.contains(&4));
+        assert!(missing.contains(&12));
+        assert!(!missing.contains(&9));
+
+        let mut set = TableSet::wmo();
+        set.insert_local(98, 0, 1, local);
+
+        let tables = set.resolve(&section1(98, 1)).unwrap();
+        assert!(tables.has_local());
+        assert_eq!(tables.element(4, 1).unwrap().data_width, 10);
+        assert_eq!(tables.element(4, 2).unwrap().data_width, 4);
+        assert_eq!(tables.sequence(50, 1).unwrap().len(), 1);
+        assert!(tables.sequence(1, 2).is_some());
+
+        // Another centre or version only sees the master tables
+        for section1 in &[section1(7, 1), section1(98, 2)] {
+            let tables = set.resolve(section1).unwrap();
+            assert!(!tables.has_local());
+            assert_eq!(tables.element(4, 1).unwrap().data_width, 12);
+            assert!(tables.sequence(50, 1).is_none());
+        }
+    }
+
+    #[test]
+    fn from_dir() {
+        let dir = std::env::temp_dir().join(format!("bufr-tables-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let year = "04,Location and appearance,004001,Year,a,0,0,10,a,0,4,,,Operational\n";
+        std::fs::write(dir.join("b.csv"), format!("{}{}", TABLE_B_HEADER, year)).unwrap();
+        let sequence = "50,Local,350001,Local,,004001,Year,,,Operational\n";
+        std::fs::write(dir.join("d.csv"), format!("{}{}", TABLE_D_HEADER, sequence)).unwrap();
+        std::fs::write(dir.join("notes.txt"), "Not a table").unwrap();
+
+        let tables = Tables::from_dir(&dir);
+        std::fs::write(dir.join("z.csv"), "Not a table").unwrap();
+        let invalid = Tables::from_dir(&dir);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let tables = tables.unwrap();
+        assert_eq!(tables.element(4, 1).unwrap().data_width, 10);
+        assert!(tables.sequence(50, 1).is_some());
+        assert!(matches!(invalid, Err(Error::InvalidTable(_))));
+    }
+
+    #[test]
+    fn load_dir() {
+        let dir = std::env::temp_dir().join(format!("bufr-versions-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("36")).unwrap();
+        std::fs::create_dir_all(dir.join("local")).unwrap();
+        let year = "04,Location and appearance,004001,Year,a,0,0,10,a,0,4,,,Operational\n";
+        let table_b = format!("{}{}", TABLE_B_HEADER, year);
+        std::fs::write(dir.join("36").join("b.csv"), &table_b).unwrap();
+        std::fs::write(dir.join("local").join("b.csv"), &table_b).unwrap();
+
+        let mut master = MasterTables::default();
+        let loaded = master.load_dir(&dir);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        loaded.unwrap();
+        assert_eq!(master.versions().collect::<Vec<_>>(), vec![(0, 36)]);
+        let (_, tables) = master.select(0, 36).unwrap();
+        assert_eq!(tables.element(4, 1).unwrap().data_width, 10);
+    }
+
+    #[test]
+    fn eccodes_dir() {
+        let dir = std::env::temp_dir().join(format!("bufr-eccodes-{}", std::process::id()));
+        let mut tables = Tables::default();
+        tables
+            .add_eccodes_elements("004001|year|long|YEAR|a|0|0|10|a|0|4\n".as_bytes())
+            .unwrap();
+        tables
+            .add_eccodes_sequences("\"350001\" = [  004001 ]\n".as_bytes())
+            .unwrap();
+        tables.save_eccodes(&dir).unwrap();
+
+        let mut loaded = Tables::default();
+        let result = loaded.load_eccodes(&dir);
+        std::fs::remove_dir_all(&dir).unwrap();
+        let missing = Tables::default().load_eccodes(&dir);
+
+        result.unwrap();
+        assert_eq!(loaded.element(4, 1), tables.element(4, 1));
+        assert_eq!(loaded.element(4, 1).unwrap().unit, BUFRUnit::Year);
+        assert_eq!(loaded.sequence(50, 1), tables.sequence(50, 1));
+        assert!(matches!(missing, Err(Error::InvalidTable(_))));
+    }
+
     // Testing WIP
     // F=0
     fn element_descriptor_f0(x: u8, y: u8) -> Result<ElementDescriptor, Error> {
         let element = match (x, y) {
             (1, 19) => ElementDescriptor {
-                name: String::from("Long station or site name"),
+                name: "Long station or site name".into(),
                 unit: BUFRUnit::CCITTIA5,
                 scale: 0,
                 reference_value: 0,
                 data_width: 256,
             },
             (1, 36) => ElementDescriptor {
-                name: String::from("Agency in charge of operating the observing platform"),
+                name: "Agency in charge of operating the observing platform".into(),
                 unit: BUFRUnit::CodeTable,
                 scale: 0,
                 reference_value: 0,
                 data_width: 20,
             },
             (1, 85) => ElementDescriptor {
-                name: String::from("Observing platform manufacturer's model"),
+                name: "Observing platform manufacturer's model".into(),
                 unit: BUFRUnit::CCITTIA5,
                 scale: 0,
                 reference_value: 0,
                 data_width: 160,
             },
             (1, 86) => ElementDescriptor {
-                name: String::from("Observing platform manufacturer's serial number"),
+                name: "Observing platform manufacturer's serial number".into(),
                 unit: BUFRUnit::CCITTIA5,
                 scale: 0,
                 reference_value: 0,
                 data_width: 256,
             },
             (1, 87) => ElementDescriptor {
-                name: String::from("WMO marine observing platform extended identifier"),
+                name: "WMO marine observing platform extended identifier".into(),
                 unit: BUFRUnit::Numeric,
                 scale: 0,
                 reference_value: 0,
                 data_width: 23,
             },
             (1, 125) => ElementDescriptor {
-                name: String::from("WIGOS identifier series"),
+                name: "WIGOS identifier series".into(),
                 unit: BUFRUnit::Numeric,
                 scale: 0,
                 reference_value: 0,
                 data_width: 4,
             },
             (1, 126) => ElementDescriptor {
-                name: String::from("WIGOS issuer of identifier"),
+                name: "WIGOS issuer of identifier".into(),
                 unit: BUFRUnit::Numeric,
                 scale: 0,
                 reference_value: 0,
                 data_width: 16,
             },
             (1, 127) => ElementDescriptor {
-                name: String::from("WIGOS issue number"),
+                name: "WIGOS issue number".into(),
                 unit: BUFRUnit::Numeric,
                 scale: 0,
                 reference_value: 0,
                 data_width: 16,
             },
             (1, 128) => ElementDescriptor {
-                name: String::from("WIGOS local identifier (character)"),
+                name: "WIGOS local identifier (character)".into(),
                 unit: BUFRUnit::CCITTIA5,
                 scale: 0,
                 reference_value: 0,
                 data_width: 128,
             },
             (4, 1) => ElementDescriptor {
-                name: String::from("Year"),
+                name: "Year".into(),
                 unit: BUFRUnit::Year,
                 scale: 0,
                 reference_value: 0,
                 data_width: 12,
             },
             (4, 2) => ElementDescriptor {
-                name: String::from("Month"),
+                name: "Month".into(),
                 unit: BUFRUnit::Month,
                 scale: 0,
                 reference_value: 0,
                 data_width: 4,
             },
             (4, 3) => ElementDescriptor {
-                name: String::from("Day"),
+                name: "Day".into(),
                 unit: BUFRUnit::Day,
                 scale: 0,
                 reference_value: 0,
                 data_width: 6,
             },
             (4, 4) => ElementDescriptor {
-                name: String::from("Hour"),
+                name: "Hour".into(),
                 unit: BUFRUnit::Hour,
                 scale: 0,
                 reference_value: 0,
                 data_width: 5,
             },
             (4, 5) => ElementDescriptor {
-                name: String::from("Minute"),
+                name: "Minute".into(),
                 unit: BUFRUnit::Minute,
                 scale: 0,
                 reference_value: 0,
                 data_width: 6,
             },
             (4, 6) => ElementDescriptor {
-                name: String::from("Second"),
+                name: "Second".into(),
                 unit: BUFRUnit::Second,
                 scale: 0,
                 reference_value: 0,
                 data_width: 6,
             },
             (5, 1) => ElementDescriptor {
-                name: String::from("Latitude (high accuracy)"),
+                name: "Latitude (high accuracy)".into(),
                 unit: BUFRUnit::Degree,
                 scale: 5,
                 reference_value: -9000000,
                 data_width: 25,
             },
             (6, 1) => ElementDescriptor {
-                name: String::from("Longitude (high accuracy)"),
+                name: "Longitude (high accuracy)".into(),
                 unit: BUFRUnit::Degree,
                 scale: 5,
                 reference_value: -18000000,
                 data_width: 26,
             },
             (8, 21) => ElementDescriptor {
-                name: String::from("Time significance"),
+                name: "Time significance".into(),
                 unit: BUFRUnit::CodeTable,
                 scale: 0,
                 reference_value: 0,
@@ -0,0 +1,666 @@
+//! Units of the elements in Table B
+//!
+//! Every unit used by the WMO tables is listed once in a registry, with the
+//! spellings found in the tables, its canonical UDUNITS/CF string and its
+//! dimension in terms of the SI base units. Units missing from the registry
+//! are kept as [`BUFRUnit::Other`], so newer tables can still be loaded.
+//!
+//! Values convert between units of the same dimension through their value in
+//! SI units.
+
+use std::convert::Infallible;
+use std::fmt;
+use std::str::FromStr;
+
+use getset::CopyGetters;
+
+use crate::Error;
+
+/// Unit of an element in Table B
+#[allow(missing_docs)]
+#[non_exhaustive]
+#[derive(Clone, Debug, PartialEq)]
+pub enum BUFRUnit {
+    Numeric,
+    CodeTable,
+    FlagTable,
+    CCITTIA5,
+    Year,
+    Month,
+    Day,
+    Hour,
+    Minute,
+    Second,
+    Degree,
+    MeterPerSecond,
+    DegreeTrue,
+    CC1,
+    CC12,
+    CC11,
+    CC14,
+    CodeTableOriginator,
+    Meter,
+    Kelvin,
+    Hertz,
+    Kilogram,
+    KilogramPerLiter,
+    KilogramPerCubicMeter,
+    SquareMeter,
+    CubicMeter,
+    CubicMeterPerSecond,
+    SquareMeterPerSquareSecond,
+    Pascal,
+    Hectopascal,
+    Celsius,
+    Percent,
+    PerMille,
+    PerHour,
+    PerSecond,
+    PerMeter,
+    PerSquareMeter,
+    PerKelvin,
+    Ampere,
+    Volt,
+    VoltPerMeter,
+    SiemensPerMeter,
+    Watt,
+    WattPerSquareMeter,
+    JoulePerSquareMeter,
+    Radiance,
+    RadiancePerWavenumber,
+    RadiancePerInverseMeter,
+    RadiancePerWavelength,
+    RadiancePerMeter,
+    Lumen,
+    CandelaPerSquareMeter,
+    Becquerel,
+    BecquerelPerCubicMeter,
+    BecquerelPerLiter,
+    Millisievert,
+    NanosievertPerHour,
+    Decibel,
+    DecibelPerDegree,
+    DecibelPerMeter,
+    DegreePerSecond,
+    SquareDegree,
+    Radian,
+    RadianPerMeter,
+    Steradian,
+    PerMeterPerSteradian,
+    Foot,
+    GeopotentialMeter,
+    QuarticMeter,
+    KilometerPerHour,
+    Knot,
+    MeterPerSquareSecond,
+    MeterPerSecondPerKelvin,
+    MeterPerSecondPerPascal,
+    KelvinPerMeter,
+    KelvinMeterPerSecond,
+    PascalPerSecond,
+    SquareMeterPerSecond,
+    SquareMeterSecond,
+    SquareMeterSecondPerRadian,
+    SquareMeterPerHertz,
+    EddyDissipationRate,
+    KilogramPerKilogram,
+    GramPerKilogram,
+    KilogramPerMeter,
+    KilogramPerSquareMeter,
+    KilogramPerSquareMeterPerSecond,
+    KilogramPerSquareMeterPerHour,
+    MolePerMole,
+    MolePerSquareCentimeter,
+    MicromolePerKilogram,
+    DobsonUnit,
+    PH,
+    NUnits,
+    NephelometricTurbidityUnit,
+    LogPerMeter,
+    LogPerSquareMeter,
+    LogPerCubicMeter,
+    LogSquareMeterPerSquareMeter,
+    LogCubicMeterPerSquareMeter,
+    /// A unit not in the registry, as written in the table
+    Other(String),
+}
+
+/// Kind of a ratio of two quantities of the same dimension
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Ratio {
+    /// Mass of a constituent per mass, like a mixing ratio in kg kg-1
+    Mass,
+    /// Amount of a constituent per amount, like a mole fraction in mol mol-1
+    Amount,
+}
+
+/// Exponents of the SI base units making up a unit
+///
+/// SI leaves angles and ratios dimensionless, which would let degrees be
+/// converted to a mixing ratio. Plane and solid angles have exponents of
+/// their own here, and ratios of masses or amounts are told apart from
+/// plain numbers, fractions and percentages.
+#[derive(Clone, Copy, CopyGetters, Debug, Default, PartialEq, Eq)]
+#[getset(get_copy = "pub")]
+pub struct Dimension {
+    /// Metre
+    length: i8,
+    /// Kilogram
+    mass: i8,
+    /// Second
+    time: i8,
+    /// Kelvin
+    temperature: i8,
+    /// Ampere
+    current: i8,
+    /// Mole
+    amount: i8,
+    /// Candela
+    luminous_intensity: i8,
+    /// Radian
+    angle: i8,
+    /// Steradian
+    solid_angle: i8,
+    /// Ratio of two masses or amounts, if the unit is one
+    ratio: Option<Ratio>,
+}
+
+impl Dimension {
+    /// Dimension of pure numbers, fractions and percentages
+    pub const DIMENSIONLESS: Dimension = Dimension::new(0, 0, 0, 0, 0, 0, 0);
+
+    const fn new(
+        length: i8,
+        mass: i8,
+        time: i8,
+        temperature: i8,
+        current: i8,
+        amount: i8,
+        luminous_intensity: i8,
+    ) -> Dimension {
+        Dimension {
+            length,
+            mass,
+            time,
+            temperature,
+            current,
+            amount,
+            luminous_intensity,
+            angle: 0,
+            solid_angle: 0,
+            ratio: None,
+        }
+    }
+
+    const fn with_angle(mut self, angle: i8, solid_angle: i8) -> Dimension {
+        self.angle = angle;
+        self.solid_angle = solid_angle;
+        self
+    }
+
+    const fn with_ratio(mut self, ratio: Ratio) -> Dimension {
+        self.ratio = Some(ratio);
+        self
+    }
+
+    /// Whether the quantity is a pure number, not an angle nor a ratio of
+    /// masses or amounts
+    pub fn is_dimensionless(&self) -> bool {
+        *self == Dimension::DIMENSIONLESS
+    }
+}
+
+impl fmt::Display for Dimension {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let exponents = [
+            ("m", self.length),
+            ("kg", self.mass),
+            ("s", self.time),
+            ("K", self.temperature),
+            ("A", self.current),
+            ("mol", self.amount),
+            ("cd", self.luminous_intensity),
+            ("rad", self.angle),
+            ("sr", self.solid_angle),
+        ];
+        let mut parts: Vec<_> = exponents
+            .iter()
+            .filter(|(_, e)| *e != 0)
+            .map(|(symbol, e)| match e {
+                1 => symbol.to_string(),
+                e => format!("{}{}", symbol, e),
+            })
+            .collect();
+        match self.ratio {
+            Some(Ratio::Mass) => parts.push("kg kg-1".to_string()),
+            Some(Ratio::Amount) => parts.push("mol mol-1".to_string()),
+            None => (),
+        }
+        if parts.is_empty() {
+            write!(f, "1")
+        } else {
+            write!(f, "{}", parts.join(" "))
+        }
+    }
+}
+
+/// An entry of the registry
+struct Entry {
+    unit: BUFRUnit,
+    /// Spellings used in the tables, the first one being the usual one
+    symbols: &'static [&'static str],
+    udunits: Option<&'static str>,
+    dimension: Option<Dimension>,
+    /// Value in SI units is `value * factor + offset`
+    factor: f64,
+    offset: f64,
+}
+
+impl Entry {
+    const fn si(mut self, factor: f64, offset: f64) -> Entry {
+        self.factor = factor;
+        self.offset = offset;
+        self
+    }
+}
+
+const fn entry(
+    unit: BUFRUnit,
+    symbols: &'static [&'static str],
+    udunits: Option<&'static str>,
+    dimension: Option<Dimension>,
+) -> Entry {
+    Entry {
+        unit,
+        symbols,
+        udunits,
+        dimension,
+        factor: 1.0,
+        offset: 0.0,
+    }
+}
+
+/// Shorthand for the dimensions in the registry, in the order of [`Dimension::new`]
+const fn dim(l: i8, m: i8, t: i8, k: i8, a: i8, n: i8, j: i8) -> Option<Dimension> {
+    Some(Dimension::new(l, m, t, k, a, n, j))
+}
+
+const DEGREE: f64 = std::f64::consts::PI / 180.0;
+/// As in UDUNITS, the tropical year
+const YEAR: f64 = 3.155_692_597_47e7;
+
+const NONE: Option<Dimension> = None;
+const ONE: Option<Dimension> = Some(Dimension::DIMENSIONLESS);
+const MASS_RATIO: Option<Dimension> = Some(Dimension::DIMENSIONLESS.with_ratio(Ratio::Mass));
+const AMOUNT_RATIO: Option<Dimension> = Some(Dimension::DIMENSIONLESS.with_ratio(Ratio::Amount));
+
+/// Shorthand for dimensions with plane and solid angles, in the order of
+/// [`Dimension::new`] then radian and steradian
+const fn angle(l: i8, m: i8, t: i8, angle: i8, solid_angle: i8) -> Option<Dimension> {
+    Some(Dimension::new(l, m, t, 0, 0, 0, 0).with_angle(angle, solid_angle))
+}
+
+#[rustfmt::skip]
+static REGISTRY: &[Entry] = &[
+    // Not physical quantities
+    entry(BUFRUnit::CodeTable, &["Code table"], None, NONE),
+    entry(BUFRUnit::FlagTable, &["Flag table"], None, NONE),
+    entry(BUFRUnit::CCITTIA5, &["CCITT IA5"], None, NONE),
+    entry(BUFRUnit::CodeTableOriginator, &["Code table defined by originating/generating centre"], None, NONE),
+    entry(BUFRUnit::CC1, &["Common Code table C-1"], None, NONE),
+    entry(BUFRUnit::CC11, &["Common Code table C-11"], None, NONE),
+    entry(BUFRUnit::CC12, &["Common Code table C-12"], None, NONE),
+    entry(BUFRUnit::CC14, &["Common Code table C-14"], None, NONE),
+    // Ratios and angles
+    entry(BUFRUnit::Numeric, &["Numeric"], Some("1"), ONE),
+    entry(BUFRUnit::Percent, &["%"], Some("%"), ONE).si(1e-2, 0.0),
+    entry(BUFRUnit::PerMille, &["0/00"], Some("1e-3"), ONE).si(1e-3, 0.0),
+    entry(BUFRUnit::NUnits, &["N units"], Some("1e-6"), ONE).si(1e-6, 0.0),
+    entry(BUFRUnit::KilogramPerKilogram, &["kg/kg", "kg kg-1"], Some("kg kg-1"), MASS_RATIO),
+    entry(BUFRUnit::GramPerKilogram, &["g/kg", "g kg-1"], Some("g kg-1"), MASS_RATIO).si(1e-3, 0.0),
+    entry(BUFRUnit::MolePerMole, &["mol/mol", "mol mol-1"], Some("mol mol-1"), AMOUNT_RATIO),
+    entry(BUFRUnit::Degree, &["deg", "degree"], Some("degree"), angle(0, 0, 0, 1, 0)).si(DEGREE, 0.0),
+    entry(BUFRUnit::DegreeTrue, &["degree true"], Some("degree"), angle(0, 0, 0, 1, 0)).si(DEGREE, 0.0),
+    entry(BUFRUnit::SquareDegree, &["deg2"], Some("degree2"), angle(0, 0, 0, 2, 0)).si(DEGREE * DEGREE, 0.0),
+    entry(BUFRUnit::Radian, &["rad"], Some("rad"), angle(0, 0, 0, 1, 0)),
+    entry(BUFRUnit::Steradian, &["sr"], Some("sr"), angle(0, 0, 0, 0, 1)),
+    // Time
+    entry(BUFRUnit::Year, &["a"], Some("year"), dim(0, 0, 1, 0, 0, 0, 0)).si(YEAR, 0.0),
+    entry(BUFRUnit::Month, &["mon"], Some("month"), dim(0, 0, 1, 0, 0, 0, 0)).si(YEAR / 12.0, 0.0),
+    entry(BUFRUnit::Day, &["d"], Some("day"), dim(0, 0, 1, 0, 0, 0, 0)).si(86400.0, 0.0),
+    entry(BUFRUnit::Hour, &["h"], Some("hour"), dim(0, 0, 1, 0, 0, 0, 0)).si(3600.0, 0.0),
+    entry(BUFRUnit::Minute, &["min"], Some("min"), dim(0, 0, 1, 0, 0, 0, 0)).si(60.0, 0.0),
+    entry(BUFRUnit::Second, &["s"], Some("s"), dim(0, 0, 1, 0, 0, 0, 0)),
+    entry(BUFRUnit::Hertz, &["Hz"], Some("Hz"), dim(0, 0, -1, 0, 0, 0, 0)),
+    entry(BUFRUnit::PerSecond, &["/s", "s-1"], Some("s-1"), dim(0, 0, -1, 0, 0, 0, 0)),
+    entry(BUFRUnit::PerHour, &["/h", "h-1"], Some("h-1"), dim(0, 0, -1, 0, 0, 0, 0)).si(1.0 / 3600.0, 0.0),
+    entry(BUFRUnit::DegreePerSecond, &["deg/s", "deg s-1"], Some("degree s-1"), angle(0, 0, -1, 1, 0)).si(DEGREE, 0.0),
+    // Length, area and volume
+    entry(BUFRUnit::Meter, &["m"], Some("m"), dim(1, 0, 0, 0, 0, 0, 0)),
+    entry(BUFRUnit::GeopotentialMeter, &["gpm"], Some("m"), dim(1, 0, 0, 0, 0, 0, 0)),
+    entry(BUFRUnit::Foot, &["ft"], Some("ft"), dim(1, 0, 0, 0, 0, 0, 0)).si(0.3048, 0.0),
+    entry(BUFRUnit::SquareMeter, &["m2"], Some("m2"), dim(2, 0, 0, 0, 0, 0, 0)),
+    entry(BUFRUnit::CubicMeter, &["m3"], Some("m3"), dim(3, 0, 0, 0, 0, 0, 0)),
+    entry(BUFRUnit::QuarticMeter, &["m4"], Some("m4"), dim(4, 0, 0, 0, 0, 0, 0)),
+    entry(BUFRUnit::PerMeter, &["m-1", "/m"], Some("m-1"), dim(-1, 0, 0, 0, 0, 0, 0)),
+    entry(BUFRUnit::PerSquareMeter, &["m-2"], Some("m-2"), dim(-2, 0, 0, 0, 0, 0, 0)),
+    entry(BUFRUnit::PerMeterPerSteradian, &["m-1 sr-1"], Some("m-1 sr-1"), angle(-1, 0, 0, 0, -1)),
+    entry(BUFRUnit::RadianPerMeter, &["rad/m", "rad m-1"], Some("rad m-1"), angle(-1, 0, 0, 1, 0)),
+    // Kinematics
+    entry(BUFRUnit::MeterPerSecond, &["m/s", "m s-1"], Some("m s-1"), dim(1, 0, -1, 0, 0, 0, 0)),
+    entry(BUFRUnit::KilometerPerHour, &["km/h", "km h-1"], Some("km h-1"), dim(1, 0, -1, 0, 0, 0, 0)).si(1.0 / 3.6, 0.0),
+    entry(BUFRUnit::Knot, &["kt"], Some("knot"), dim(1, 0, -1, 0, 0, 0, 0)).si(1852.0 / 3600.0, 0.0),
+    entry(BUFRUnit::MeterPerSquareSecond, &["m s-2"], Some("m s-2"), dim(1, 0, -2, 0, 0, 0, 0)),
+    entry(BUFRUnit::CubicMeterPerSecond, &["m3/s", "m3 s-1"], Some("m3 s-1"), dim(3, 0, -1, 0, 0, 0, 0)),
+    entry(BUFRUnit::SquareMeterPerSecond, &["m2/s", "m2 s-1"], Some("m2 s-1"), dim(2, 0, -1, 0, 0, 0, 0)),
+    entry(BUFRUnit::SquareMeterPerSquareSecond, &["m2 s-2"], Some("m2 s-2"), dim(2, 0, -2, 0, 0, 0, 0)),
+    entry(BUFRUnit::SquareMeterSecond, &["m2 s"], Some("m2 s"), dim(2, 0, 1, 0, 0, 0, 0)),
+    entry(BUFRUnit::SquareMeterSecondPerRadian, &["m2 s rad-1", "m2 rad-1 s"], Some("m2 s rad-1"), angle(2, 0, 1, -1, 0)),
+    entry(BUFRUnit::SquareMeterPerHertz, &["m2/Hz", "m2 Hz-1"], Some("m2 Hz-1"), dim(2, 0, 1, 0, 0, 0, 0)),
+    // m^(2/3) s-1 has no integer exponents, and no UDUNITS spelling
+    entry(BUFRUnit::EddyDissipationRate, &["m2/3 s-1"], None, NONE),
+    // Mass and density
+    entry(BUFRUnit::Kilogram, &["kg"], Some("kg"), dim(0, 1, 0, 0, 0, 0, 0)),
+    entry(BUFRUnit::KilogramPerMeter, &["kg/m", "kg m-1"], Some("kg m-1"), dim(-1, 1, 0, 0, 0, 0, 0)),
+    entry(BUFRUnit::KilogramPerSquareMeter, &["kg m-2"], Some("kg m-2"), dim(-2, 1, 0, 0, 0, 0, 0)),
+    entry(BUFRUnit::KilogramPerCubicMeter, &["kg m-3"], Some("kg m-3"), dim(-3, 1, 0, 0, 0, 0, 0)),
+    entry(BUFRUnit::KilogramPerLiter, &["kg l-1", "kg/l"], Some("kg L-1"), dim(-3, 1, 0, 0, 0, 0, 0)).si(1e3, 0.0),
+    entry(BUFRUnit::KilogramPerSquareMeterPerSecond, &["kg m-2 s-1"], Some("kg m-2 s-1"), dim(-2, 1, -1, 0, 0, 0, 0)),
+    entry(BUFRUnit::KilogramPerSquareMeterPerHour, &["kg m-2 h-1"], Some("kg m-2 h-1"), dim(-2, 1, -1, 0, 0, 0, 0)).si(1.0 / 3600.0, 0.0),
+    // Pressure
+    entry(BUFRUnit::Pascal, &["Pa"], Some("Pa"), dim(-1, 1, -2, 0, 0, 0, 0)),
+    entry(BUFRUnit::Hectopascal, &["hPa"], Some("hPa"), dim(-1, 1, -2, 0, 0, 0, 0)).si(100.0, 0.0),
+    entry(BUFRUnit::PascalPerSecond, &["Pa/s", "Pa s-1"], Some("Pa s-1"), dim(-1, 1, -3, 0, 0, 0, 0)),
+    entry(BUFRUnit::MeterPerSecondPerPascal, &["m s-1 Pa-1"], Some("m s-1 Pa-1"), dim(2, -1, 1, 0, 0, 0, 0)),
+    // Temperature
+    entry(BUFRUnit::Kelvin, &["K"], Some("K"), dim(0, 0, 0, 1, 0, 0, 0)),
+    entry(BUFRUnit::Celsius, &["C", "°C", "degC"], Some("degC"), dim(0, 0, 0, 1, 0, 0, 0)).si(1.0, 273.15),
+    entry(BUFRUnit::PerKelvin, &["K-1"], Some("K-1"), dim(0, 0, 0, -1, 0, 0, 0)),
+    entry(BUFRUnit::KelvinPerMeter, &["K m-1", "K/m"], Some("K m-1"), dim(-1, 0, 0, 1, 0, 0, 0)),
+    entry(BUFRUnit::KelvinMeterPerSecond, &["K m s-1"], Some("K m s-1"), dim(1, 0, -1, 1, 0, 0, 0)),
+    entry(BUFRUnit::MeterPerSecondPerKelvin, &["m s-1 K-1"], Some("m s-1 K-1"), dim(1, 0, -1, -1, 0, 0, 0)),
+    // Electricity
+    entry(BUFRUnit::Ampere, &["A"], Some("A"), dim(0, 0, 0, 0, 1, 0, 0)),
+    entry(BUFRUnit::Volt, &["V"], Some("V"), dim(2, 1, -3, 0, -1, 0, 0)),
+    entry(BUFRUnit::VoltPerMeter, &["V/m", "V m-1"], Some("V m-1"), dim(1, 1, -3, 0, -1, 0, 0)),
+    entry(BUFRUnit::SiemensPerMeter, &["S/m", "S m-1"], Some("S m-1"), dim(-3, -1, 3, 0, 2, 0, 0)),
+    // Energy and radiation
+    entry(BUFRUnit::Watt, &["W"], Some("W"), dim(2, 1, -3, 0, 0, 0, 0)),
+    entry(BUFRUnit::WattPerSquareMeter, &["W m-2"], Some("W m-2"), dim(0, 1, -3, 0, 0, 0, 0)),
+    entry(BUFRUnit::JoulePerSquareMeter, &["J m-2"], Some("J m-2"), dim(0, 1, -2, 0, 0, 0, 0)),
+    entry(BUFRUnit::Radiance, &["W m-2 sr-1"], Some("W m-2 sr-1"), angle(0, 1, -3, 0, -1)),
+    entry(BUFRUnit::RadiancePerWavenumber, &["W m-2 sr-1 cm"], Some("W m-2 sr-1 cm"), angle(1, 1, -3, 0, -1)).si(1e-2, 0.0),
+    entry(BUFRUnit::RadiancePerInverseMeter, &["W m-2 sr-1 m"], Some("W m-2 sr-1 m"), angle(1, 1, -3, 0, -1)),
+    entry(BUFRUnit::RadiancePerWavelength, &["W m-2 sr-1 um-1"], Some("W m-2 sr-1 um-1"), angle(-1, 1, -3, 0, -1)).si(1e6, 0.0),
+    entry(BUFRUnit::RadiancePerMeter, &["W m-3 sr-1"], Some("W m-3 sr-1"), angle(-1, 1, -3, 0, -1)),
+    entry(BUFRUnit::Lumen, &["lm"], Some("lm"), dim(0, 0, 0, 0, 0, 0, 1)),
+    entry(BUFRUnit::CandelaPerSquareMeter, &["Cd m-2", "cd m-2"], Some("cd m-2"), dim(-2, 0, 0, 0, 0, 0, 1)),
+    // Radioactivity
+    entry(BUFRUnit::Becquerel, &["Bq"], Some("Bq"), dim(0, 0, -1, 0, 0, 0, 0)),
+    entry(BUFRUnit::BecquerelPerCubicMeter, &["Bq m-3"], Some("Bq m-3"), dim(-3, 0, -1, 0, 0, 0, 0)),
+    entry(BUFRUnit::BecquerelPerLiter, &["Bq/l", "Bq l-1"], Some("Bq L-1"), dim(-3, 0, -1, 0, 0, 0, 0)).si(1e3, 0.0),
+    entry(BUFRUnit::Millisievert, &["mSv"], Some("mSv"), dim(2, 0, -2, 0, 0, 0, 0)).si(1e-3, 0.0),
+    entry(BUFRUnit::NanosievertPerHour, &["nSv/h", "nSv h-1"], Some("nSv h-1"), dim(2, 0, -3, 0, 0, 0, 0)).si(1e-9 / 3600.0, 0.0),
+    // Chemistry
+    entry(BUFRUnit::MolePerSquareCentimeter, &["mol cm-2"], Some("mol cm-2"), dim(-2, 0, 0, 0, 0, 1, 0)).si(1e4, 0.0),
+    entry(BUFRUnit::MicromolePerKilogram, &["umol/kg", "umol kg-1"], Some("umol kg-1"), dim(0, -1, 0, 0, 0, 1, 0)).si(1e-6, 0.0),
+    entry(BUFRUnit::DobsonUnit, &["DU"], Some("4.4615e-4 mol m-2"), dim(-2, 0, 0, 0, 0, 1, 0)).si(4.4615e-4, 0.0),
+    // Logarithmic and empirical scales
+    entry(BUFRUnit::Decibel, &["dB"], Some("dB"), NONE),
+    entry(BUFRUnit::DecibelPerDegree, &["dB/deg", "dB deg-1"], Some("dB degree-1"), NONE),
+    entry(BUFRUnit::DecibelPerMeter, &["dB/m", "dB m-1"], Some("dB m-1"), NONE),
+    entry(BUFRUnit::LogPerMeter, &["log (m-1)", "log(m-1)"], Some("lg(re 1 m-1)"), NONE),
+    entry(BUFRUnit::LogPerSquareMeter, &["log (m-2)", "log(m-2)"], Some("lg(re 1 m-2)"), NONE),
+    entry(BUFRUnit::LogPerCubicMeter, &["log (m-3)", "log(m-3)"], Some("lg(re 1 m-3)"), NONE),
+    entry(BUFRUnit::LogSquareMeterPerSquareMeter, &["log(m2 m-2)", "log (m2 m-2)"], Some("lg(re 1 m2 m-2)"), NONE),
+    entry(BUFRUnit::LogCubicMeterPerSquareMeter, &["log(m3 m-2)", "log (m3 m-2)"], Some("lg(re 1 m3 m-2)"), NONE),
+    entry(BUFRUnit::PH, &["pH unit"], None, NONE),
+    entry(BUFRUnit::NephelometricTurbidityUnit, &["NTU"], None, NONE),
+];
+
+impl BUFRUnit {
+    fn entry(&self) -> Option<&'static Entry> {
+        REGISTRY.iter().find(|e| e.unit == *self)
+    }
+
+    /// Canonical UDUNITS/CF spelling, if the unit has one
+    ///
+    /// Code and flag tables, character strings and some empirical scales
+    /// are not units in the UDUNITS sense.
+    pub fn udunits(&self) -> Option<&'static str> {
+        self.entry().and_then(|e| e.udunits)
+    }
+
+    /// Dimension in SI base units, if the unit is a physical quantity on a
+    /// linear scale
+    pub fn dimension(&self) -> Option<Dimension> {
+        self.entry().and_then(|e| e.dimension)
+    }
+
+    /// Converts a value in this unit to another unit of the same dimension
+    ///
+    /// ```
+    /// use bufr::BUFRUnit;
+    ///
+    /// let celsius = BUFRUnit::Kelvin.convert(273.15, &BUFRUnit::Celsius).unwrap();
+    /// assert!(celsius.abs() < 1e-9);
+    /// assert!(BUFRUnit::Kelvin.convert(273.15, &BUFRUnit::Pascal).is_err());
+    /// ```
+    pub fn convert(&self, value: f64, to: &BUFRUnit) -> Result<f64, Error> {
+        let from_entry = self.entry();
+        let to_entry = to.entry();
+        match (from_entry, to_entry) {
+            (Some(a), Some(b)) if a.dimension.is_some() && a.dimension == b.dimension => {
+                Ok((value * a.factor + a.offset - b.offset) / b.factor)
+            }
+            _ => Err(Error::IncompatibleUnits(self.to_string(), to.to_string())),
+        }
+    }
+
+    /// Whether values in this unit can be converted to the other unit
+    pub fn is_convertible_to(&self, to: &BUFRUnit) -> bool {
+        self.convert(0.0, to).is_ok()
+    }
+
+    /// Whether the values are entries of a code or flag table
+    pub fn is_code(&self) -> bool {
+        matches!(
+            self,
+            BUFRUnit::CodeTable
+                | BUFRUnit::FlagTable
+                | BUFRUnit::CC1
+                | BUFRUnit::CC11
+                | BUFRUnit::CC12
+                | BUFRUnit::CC14
+                | BUFRUnit::CodeTableOriginator
+        )
+    }
+}
+
+impl From<&str> for BUFRUnit {
+    fn from(s: &str) -> Self {
+        // Some entries of the WMO tables have trailing spaces
+        let s = s.trim();
+        // Other tables, like those of ecCodes, spell units in capitals
+        let entry = REGISTRY
+            .iter()
+            .find(|e| e.symbols.contains(&s))
+            .or_else(|| {
+                REGISTRY
+                    .iter()
+                    .find(|e| e.symbols.iter().any(|sym| sym.eq_ignore_ascii_case(s)))
+            });
+        match entry {
+            Some(e) => e.unit.clone(),
+            None => BUFRUnit::Other(s.to_string()),
+        }
+    }
+}
+
+impl FromStr for BUFRUnit {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(BUFRUnit::from(s))
+    }
+}
+
+/// Writes the unit as in the WMO tables
+impl fmt::Display for BUFRUnit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (self, self.entry()) {
+            (BUFRUnit::Other(s), _) => write!(f, "{}", s),
+            (_, Some(e)) => write!(f, "{}", e.symbols[0]),
+            (unit, None) => write!(f, "{:?}", unit),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_units {
+    use super::{BUFRUnit, Dimension, Ratio, REGISTRY};
+
+    #[test]
+    fn spellings() {
+        assert_eq!(BUFRUnit::from("Pa s-1"), BUFRUnit::PascalPerSecond);
+        assert_eq!(BUFRUnit::from("Pa/s"), BUFRUnit::PascalPerSecond);
+        assert_eq!(BUFRUnit::from("Code table "), BUFRUnit::CodeTable);
+        assert_eq!(BUFRUnit::from("CODE TABLE"), BUFRUnit::CodeTable);
+        assert_eq!(BUFRUnit::from("A"), BUFRUnit::Ampere);
+        assert_eq!(BUFRUnit::from("log (m-2)"), BUFRUnit::LogPerSquareMeter);
+        assert_eq!(BUFRUnit::from("log(m-2)"), BUFRUnit::LogPerSquareMeter);
+        assert_eq!(BUFRUnit::from("K m-1"), BUFRUnit::KelvinPerMeter);
+        assert_eq!(
+            BUFRUnit::from("furlong/fortnight"),
+            BUFRUnit::Other("furlong/fortnight".to_string())
+        );
+        assert_eq!("m s-1".parse(), Ok(BUFRUnit::MeterPerSecond));
+        assert_eq!(BUFRUnit::MeterPerSecond.to_string(), "m/s");
+        assert_eq!(BUFRUnit::Other("x y".to_string()).to_string(), "x y");
+    }
+
+    #[test]
+    fn registry() {
+        for (i, e) in REGISTRY.iter().enumerate() {
+            assert!(!e.symbols.is_empty(), "{:?}", e.unit);
+            // Every unit and spelling is registered once
+            for other in &REGISTRY[i + 1..] {
+                assert_ne!(e.unit, other.unit);
+                for symbol in e.symbols {
+                    assert!(!other.symbols.contains(symbol), "{}", symbol);
+                }
+            }
+            // Round trip through the usual spelling
+            assert_eq!(BUFRUnit::from(e.symbols[0]), e.unit);
+        }
+    }
+
+    #[test]
+    fn dimensions() {
+        let pressure = BUFRUnit::Pascal.dimension().unwrap();
+        assert_eq!(pressure.length(), -1);
+        assert_eq!(pressure.mass(), 1);
+        assert_eq!(pressure.time(), -2);
+        assert_eq!(pressure.to_string(), "m-1 kg s-2");
+
+        assert_eq!(
+            BUFRUnit::Knot.dimension(),
+            BUFRUnit::MeterPerSecond.dimension()
+        );
+        assert_eq!(BUFRUnit::Kelvin.dimension(), BUFRUnit::Celsius.dimension());
+        assert!(BUFRUnit::Percent.dimension().unwrap().is_dimensionless());
+        assert_eq!(Dimension::DIMENSIONLESS.to_string(), "1");
+
+        // Angles and ratios aren't pure numbers
+        let degree = BUFRUnit::Degree.dimension().unwrap();
+        assert!(!degree.is_dimensionless());
+        assert_eq!(degree.angle(), 1);
+        assert_eq!(degree.to_string(), "rad");
+        let mixing_ratio = BUFRUnit::KilogramPerKilogram.dimension().unwrap();
+        assert_eq!(mixing_ratio.ratio(), Some(Ratio::Mass));
+        assert_eq!(mixing_ratio.to_string(), "kg kg-1");
+        assert_eq!(
+            BUFRUnit::MolePerMole.dimension().unwrap().ratio(),
+            Some(Ratio::Amount)
+        );
+        assert_eq!(
+            BUFRUnit::Radiance.dimension().unwrap().to_string(),
+            "kg s-3 sr-1"
+        );
+
+        assert_eq!(BUFRUnit::CodeTable.dimension(), None);
+        assert_eq!(BUFRUnit::CodeTable.udunits(), None);
+        assert_eq!(BUFRUnit::Other("x".to_string()).dimension(), None);
+        assert_eq!(BUFRUnit::Knot.udunits(), Some("knot"));
+        assert_eq!(BUFRUnit::LogPerSquareMeter.udunits(), Some("lg(re 1 m-2)"));
+    }
+
+    #[test]
+    fn conversions() {
+        let close = |a: f64, b: f64| (a - b).abs() < 1e-6 * b.abs().max(1.0);
+
+        let convert = |v, from: BUFRUnit, to| from.convert(v, &to).unwrap();
+        assert!(close(
+            convert(293.15, BUFRUnit::Kelvin, BUFRUnit::Celsius),
+            20.0
+        ));
+        assert!(close(
+            convert(-40.0, BUFRUnit::Celsius, BUFRUnit::Kelvin),
+            233.15
+        ));
+        assert!(close(
+            convert(10.0, BUFRUnit::MeterPerSecond, BUFRUnit::Knot),
+            19.438445
+        ));
+        assert!(close(
+            convert(101325.0, BUFRUnit::Pascal, BUFRUnit::Hectopascal),
+            1013.25
+        ));
+        assert!(close(
+            convert(1000.0, BUFRUnit::Meter, BUFRUnit::Foot),
+            3280.8399
+        ));
+        assert!(close(
+            convert(180.0, BUFRUnit::Degree, BUFRUnit::Radian),
+            std::f64::consts::PI
+        ));
+        assert!(close(
+            convert(36.0, BUFRUnit::KilometerPerHour, BUFRUnit::MeterPerSecond),
+            10.0
+        ));
+        assert!(close(
+            convert(50.0, BUFRUnit::Percent, BUFRUnit::Numeric),
+            0.5
+        ));
+
+        assert!(BUFRUnit::Kelvin.is_convertible_to(&BUFRUnit::Celsius));
+        assert!(!BUFRUnit::Kelvin.is_convertible_to(&BUFRUnit::Pascal));
+        assert!(!BUFRUnit::CodeTable.is_convertible_to(&BUFRUnit::CodeTable));
+        assert!(!BUFRUnit::Decibel.is_convertible_to(&BUFRUnit::Numeric));
+
+        // Dimensionless in SI, but of different kinds
+        assert!(close(
+            convert(
+                5.0,
+                BUFRUnit::GramPerKilogram,
+                BUFRUnit::KilogramPerKilogram
+            ),
+            0.005
+        ));
+        for (from, to) in [
+            (BUFRUnit::Degree, BUFRUnit::Numeric),
+            (BUFRUnit::Degree, BUFRUnit::Percent),
+            (BUFRUnit::Percent, BUFRUnit::KilogramPerKilogram),
+            (BUFRUnit::Numeric, BUFRUnit::MolePerMole),
+            (BUFRUnit::KilogramPerKilogram, BUFRUnit::MolePerMole),
+            (BUFRUnit::Radian, BUFRUnit::Steradian),
+            (BUFRUnit::SquareDegree, BUFRUnit::Degree),
+            (BUFRUnit::DegreePerSecond, BUFRUnit::Hertz),
+            (BUFRUnit::RadianPerMeter, BUFRUnit::PerMeter),
+        ] {
+            assert!(!from.is_convertible_to(&to), "{} to {}", from, to);
+            assert!(!to.is_convertible_to(&from), "{} to {}", to, from);
+        }
+        assert!(!BUFRUnit::Other("m".to_string()).is_convertible_to(&BUFRUnit::Meter));
+    }
+}
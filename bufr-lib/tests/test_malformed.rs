@@ -0,0 +1,248 @@
+//! Malformed input must be rejected with an error, never with a panic.
+//!
+//! This is a deterministic subset of what the fuzz target in `fuzz/` does.
+
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::PathBuf;
+
+fn load(name: &str) -> Vec<u8> {
+    let mut filename = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    filename.push("../tests/data");
+    filename.push(name);
+
+    let file =
+        File::open(&filename).unwrap_or_else(|_| panic!("Error loading file: {:?}", &filename));
+    let mut reader = BufReader::new(file);
+
+    let mut buffer = Vec::new();
+    reader.read_to_end(&mut buffer).unwrap();
+    buffer
+}
+
+/// Edition 4 message of one uncompressed subset with the descriptors, as
+/// "FXXYYY" codes, and the data of section 4
+fn message(descriptors: &[&str], data: &[u8]) -> Vec<u8> {
+    let section3 = 7 + 2 * descriptors.len();
+    let section4 = 4 + data.len();
+    let total = 8 + 22 + section3 + section4 + 4;
+    let mut buf = b"BUFR".to_vec();
+    buf.extend_from_slice(&[0, 0, total as u8, 4]);
+    buf.extend_from_slice(&[
+        0, 0, 22, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 35, 0, 7, 228, 1, 1, 0, 0, 0,
+    ]);
+    buf.extend_from_slice(&[0, 0, section3 as u8, 0, 0, 1, 0x80]);
+    for code in descriptors {
+        let descriptor: bufr::Descriptor = code.parse().unwrap();
+        let (f, x, y) = (descriptor.f(), descriptor.x(), descriptor.y());
+        buf.extend_from_slice(&[(f << 6) | x, y]);
+    }
+    buf.extend_from_slice(&[0, 0, section4 as u8, 0]);
+    buf.extend_from_slice(data);
+    buf.extend_from_slice(b"7777");
+    buf
+}
+
+fn decode_all(buf: &[u8]) {
+    if let Ok(message) = bufr::decode(buf) {
+        let _ = message.subsets();
+    }
+}
+
+#[test]
+fn truncated() {
+    for name in &["wmo_sarep.bufr", "Melonhead_2.bufr"] {
+        let buffer = load(name);
+        let step = (buffer.len() / 300).max(1);
+        for n in (0..buffer.len()).step_by(step) {
+            decode_all(&buffer[..n]);
+        }
+    }
+}
+
+#[test]
+fn corrupted() {
+    let buffer = load("wmo_sarep.bufr");
+    for i in 0..buffer.len() {
+        for value in &[0x00, 0x01, 0x7f, 0x80, 0xff] {
+            let mut corrupted = buffer.clone();
+            corrupted[i] = *value;
+            decode_all(&corrupted);
+        }
+    }
+}
+
+#[test]
+fn reserved_byte() {
+    let mut buffer = load("wmo_sarep.bufr");
+    // 4th byte of section 3, right after section 1
+    buffer[8 + 22 + 3] = 1;
+    match bufr::decode(&buffer) {
+        Err(bufr::Error::ReservedNotZero {
+            section: 3,
+            offset: 33,
+            value: 1,
+        }) => (),
+        _ => panic!(),
+    };
+
+    let lenient = bufr::DecodeOptions::default().strict(false);
+    assert!(bufr::decode_with(&buffer, &lenient).is_ok());
+}
+
+#[test]
+fn invalid_flags() {
+    let mut buffer = load("wmo_sarep.bufr");
+    // Optional section flag in section 1
+    buffer[8 + 9] = 1;
+    match bufr::decode(&buffer) {
+        Err(bufr::Error::InvalidFlags {
+            section: 1,
+            offset: 17,
+            value: 1,
+        }) => (),
+        _ => panic!(),
+    };
+    let lenient = bufr::DecodeOptions::default().strict(false);
+    let message = bufr::decode_with(&buffer, &lenient).unwrap();
+    assert!(!message.section1().optional_section());
+    assert!(matches!(
+        message.warnings()[0],
+        bufr::Error::InvalidFlags { section: 1, .. }
+    ));
+
+    // Reserved bits of the observed and compressed flags in section 3
+    let mut buffer = load("wmo_sarep.bufr");
+    buffer[30 + 6] = 0x81;
+    match bufr::decode(&buffer) {
+        Err(bufr::Error::InvalidFlags {
+            section: 3,
+            offset: 36,
+            value: 0x81,
+        }) => (),
+        _ => panic!(),
+    };
+    let message = bufr::decode_with(&buffer, &lenient).unwrap();
+    assert!(message.section3().is_observed());
+    assert!(!message.section3().is_compressed());
+    assert!(matches!(
+        message.warnings()[0],
+        bufr::Error::InvalidFlags { section: 3, .. }
+    ));
+}
+
+#[test]
+fn missing_end_section() {
+    let mut buffer = load("wmo_sarep.bufr");
+    let n = buffer.len();
+    buffer[n - 1] = b'8';
+    match bufr::decode(&buffer) {
+        Err(bufr::Error::EndSection { offset: 142 }) => (),
+        _ => panic!(),
+    };
+}
+
+#[test]
+fn padded_end_section() {
+    let mut buffer = load("wmo_sarep.bufr");
+    let n = buffer.len();
+    buffer.splice(n - 4..n - 4, [0, 0]);
+    buffer[6] += 2;
+    match bufr::decode(&buffer) {
+        Err(bufr::Error::EndSection { offset: 142 }) => (),
+        _ => panic!(),
+    };
+
+    let lenient = bufr::DecodeOptions::default().strict(false);
+    assert!(bufr::decode_with(&buffer, &lenient).is_ok());
+}
+
+#[cfg(feature = "wmo-35")]
+#[test]
+fn partial() {
+    let buffer = load("wmo_sarep.bufr");
+    let options = bufr::DecodeOptions::default();
+
+    // Section 4 extends beyond the message
+    let mut corrupted = buffer.clone();
+    corrupted[8 + 22 + 67] = 0xff;
+    let partial = bufr::decode_partial(&corrupted, &options);
+    assert!(partial.section1().is_some());
+    assert!(partial.section3().is_some());
+    assert!(partial.section4().is_none());
+    assert!(partial.subsets().is_empty());
+    match partial.errors().next().map(|e| e.kind()) {
+        Some(bufr::Error::TruncatedSection { section: 4, .. }) => (),
+        _ => panic!(),
+    };
+
+    // Tolerated problems are warnings, as well as decoding the data of
+    // master table version 12 with the embedded tables
+    let mut corrupted = buffer;
+    corrupted[8 + 22 + 3] = 1;
+    let lenient = options.strict(false);
+    let partial = bufr::decode_partial(&corrupted, &lenient);
+    let warnings: Vec<_> = partial.warnings().collect();
+    assert_eq!(warnings.len(), 2);
+    assert!(matches!(
+        warnings[1],
+        bufr::Error::TableVersion {
+            master_table: 0,
+            requested: 12,
+            used: 35,
+        }
+    ));
+    assert!(partial.section4().is_some());
+
+    // The complete message keeps them, even without decoding the data
+    for options in [lenient.clone(), lenient.decode_data(true)] {
+        let message = bufr::decode_with(&corrupted, &options).unwrap();
+        assert_eq!(message.warnings().len(), 2);
+        assert!(matches!(
+            message.warnings()[1],
+            bufr::Error::TableVersion { requested: 12, .. }
+        ));
+    }
+}
+
+#[test]
+fn nested_fixed_replications() {
+    // 255^9 repetitions of 0-01-019 don't fit in the width of a usize
+    let buffer = message(
+        &[
+            "109255", "108255", "107255", "106255", "105255", "104255", "103255", "102255",
+            "101255", "001019",
+        ],
+        &[0; 64],
+    );
+    let year = "004001".parse().unwrap();
+    let options = bufr::DecodeOptions::default().select(vec![year]);
+    let partial = bufr::decode_partial(&buffer, &options);
+    assert!(partial.errors().next().is_some());
+}
+
+#[test]
+#[cfg(feature = "wmo-35")]
+fn replications_without_data() {
+    // 255^5 repetitions of an operator, which reads no bits, before the
+    // unknown 0-63-255
+    let buffer = message(
+        &[
+            "105255", "104255", "103255", "102255", "101255", "201000", "063255",
+        ],
+        &[0; 2],
+    );
+    let (sender, receiver) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        let partial = bufr::decode_partial(&buffer, &bufr::DecodeOptions::default());
+        let errors: Vec<_> = partial.errors().map(|e| e.kind().to_string()).collect();
+        sender.send(errors).unwrap();
+    });
+    let errors = receiver
+        .recv_timeout(std::time::Duration::from_secs(30))
+        .expect("Decoding didn't end");
+    assert_eq!(
+        errors,
+        ["Descriptors expand to more than the limit of 4194304"]
+    );
+}
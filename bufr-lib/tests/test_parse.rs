@@ -1,3 +1,10 @@
+// The original assertions are kept as they were written
+#![allow(
+    clippy::assertions_on_constants,
+    clippy::bool_assert_comparison,
+    clippy::expect_fun_call
+)]
+
 use std::fs::File;
 use std::io::{BufReader, Read};
 use std::path::PathBuf;
@@ -45,6 +52,10 @@ fn simple_parse() -> Result<(), Box<dyn std::error::Error>> {
             assert_eq!(data.local_use(), &[]);
         }
     }
+    let text = section1.to_string();
+    assert!(text.contains("Center: 34 (Tokyo (RSMC), Japan Meteorological Agency)"));
+    assert!(text.contains("Data category: 12 (Surface data (satellite))"));
+    assert!(text.contains("Sub-center: 0\n"));
 
     let section3 = message.section3();
     assert_eq!(section3.length(), 67);
@@ -136,9 +147,10 @@ fn encode_1() -> Result<(), Box<dyn std::error::Error>> {
 
     let message = bufr::decode(&buffer)?;
     let mut encoded = vec![];
-    message.encode(&mut encoded)?;
+    let n = message.encode(&mut encoded)?;
 
-    assert_eq!(buffer[0..30], encoded);
+    assert_eq!(n, buffer.len());
+    assert_eq!(buffer, encoded);
 
     Ok(())
 }
@@ -180,3 +192,156 @@ fn version_not_supported() {
         _ => assert!(false),
     };
 }
+
+#[cfg(feature = "wmo-35")]
+#[test]
+fn decode_data() -> Result<(), Box<dyn std::error::Error>> {
+    let mut filename = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    filename.push("../tests/data/wmo_sarep.bufr");
+
+    let file =
+        File::open(&filename).unwrap_or_else(|_| panic!("Error loading file: {:?}", &filename));
+    let mut reader = BufReader::new(file);
+
+    let mut buffer = Vec::new();
+    reader.read_to_end(&mut buffer)?;
+
+    let eager = bufr::DecodeOptions::default().decode_data(true);
+    let message = bufr::decode_with(&buffer, &eager)?;
+    let subsets = message.subsets()?;
+    assert_eq!(subsets.len(), 1);
+    // 0-25-150 is in class 25 of Table B
+    assert!(subsets[0]
+        .fields()
+        .iter()
+        .any(|f| f.descriptor().to_string() == "0-25-150"));
+
+    Ok(())
+}
+
+#[cfg(feature = "wmo-35")]
+#[test]
+fn output_units() -> Result<(), Box<dyn std::error::Error>> {
+    let mut filename = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    filename.push("../tests/data/wmo_sarep.bufr");
+
+    let file =
+        File::open(&filename).unwrap_or_else(|_| panic!("Error loading file: {:?}", &filename));
+    let mut reader = BufReader::new(file);
+
+    let mut buffer = Vec::new();
+    reader.read_to_end(&mut buffer)?;
+
+    let options = bufr::DecodeOptions::default()
+        .decode_data(true)
+        .output_unit(bufr::BUFRUnit::MeterPerSecond, bufr::BUFRUnit::Knot)?;
+    let message = bufr::decode_with(&buffer, &options)?;
+    let subsets = message.subsets()?;
+    let value = |code: &str| {
+        subsets[0]
+            .fields()
+            .iter()
+            .find(|f| f.descriptor().to_string() == code)
+            .map(|f| f.value().clone())
+    };
+
+    // Speed of motion of the storm, 4.12 m/s
+    match value("0-19-006") {
+        Some(bufr::Value::Float(v)) => assert!((v - 8.0086).abs() < 1e-3),
+        v => panic!("{:?}", v),
+    }
+    // Other units are left alone
+    assert_eq!(value("0-05-002"), Some(bufr::Value::Float(14.3)));
+
+    Ok(())
+}
+
+#[cfg(feature = "wmo-35")]
+#[test]
+fn table_set() -> Result<(), Box<dyn std::error::Error>> {
+    use bufr::TableProvider;
+
+    let mut filename = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    filename.push("../tests/data/wmo_sarep.bufr");
+
+    let file =
+        File::open(&filename).unwrap_or_else(|_| panic!("Error loading file: {:?}", &filename));
+    let mut reader = BufReader::new(file);
+
+    let mut buffer = Vec::new();
+    reader.read_to_end(&mut buffer)?;
+
+    // Version 12 from the test fixture, see tests/tables/README.md
+    let mut dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    dir.push("../tests/tables");
+    let mut set = bufr::TableSet::wmo();
+    set.master_mut().load_dir(&dir)?;
+    assert_eq!(
+        set.master().versions().collect::<Vec<_>>(),
+        [(0, 12), (0, 35)]
+    );
+
+    let (version, fixture) = set.master().select(0, 12)?;
+    assert_eq!(version, 12);
+    assert!(fixture.element(2, 1).is_none());
+    assert!(fixture.sequence(1, 13).is_none());
+    let (version, embedded) = set.master().select(0, 13)?;
+    assert_eq!(version, 35);
+    assert!(embedded.element(2, 1).is_some());
+    assert!(embedded.sequence(1, 13).is_some());
+
+    let options = bufr::DecodeOptions::default().tables(set);
+    let partial = bufr::decode_partial(&buffer, &options);
+    assert!(partial.is_complete());
+    assert_eq!(partial.warnings().count(), 0);
+    assert_eq!(partial.subsets().len(), 1);
+
+    Ok(())
+}
+
+#[cfg(feature = "wmo-35")]
+#[test]
+fn borrowed_message() -> Result<(), Box<dyn std::error::Error>> {
+    let mut filename = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    filename.push("../tests/data/wmo_sarep.bufr");
+    let buffer = std::fs::read(&filename)?;
+
+    let message = bufr::decode(&buffer)?;
+    let data = message.section4().data();
+    assert!(buffer.as_ptr_range().contains(&data.as_ptr()));
+    let subsets = message.subsets()?;
+
+    let owned = message.into_owned();
+    drop(buffer);
+    assert_eq!(owned.section4().length(), 45);
+    assert_eq!(owned.subsets()?, subsets);
+    Ok(())
+}
+
+#[cfg(feature = "wmo-35")]
+#[test]
+fn local_glider_tables() -> Result<(), Box<dyn std::error::Error>> {
+    let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let buffer = std::fs::read(manifest_dir.join("../tests/data/Cabot.bufr"))?;
+
+    // The draft 3-15-012 of the sample differs from the one of WMO
+    let eager = bufr::DecodeOptions::default().decode_data(true);
+    assert!(bufr::decode_with(&buffer, &eager).is_err());
+
+    let mut glider = bufr::Tables::default();
+    glider.load_table_d(manifest_dir.join("tables/glider.csv"))?;
+    let mut set = bufr::TableSet::wmo();
+    set.set_default_local(glider);
+    let options = eager.tables(set);
+    // The draft lists 0-12-169, which isn't in the WMO Table B
+    match bufr::decode_with(&buffer, &options) {
+        Err(e) => assert!(
+            matches!(e.kind(), bufr::Error::UnknownDescriptor(0, 12, 169)),
+            "{}",
+            e
+        ),
+        Ok(_) => panic!("0-12-169 is unknown"),
+    }
+
+    Ok(())
+}